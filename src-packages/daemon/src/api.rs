@@ -0,0 +1,151 @@
+use std::{env, fs, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+use openssl::{memcmp, rand::rand_bytes};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::io::AsyncWriteExt;
+
+/// Overrides where the daemon listens (a socket path on unix, `host:port` everywhere else)
+pub const DAEMON_SOCKET_ENV: &str = "ENKRYPTON_DAEMON_SOCKET";
+
+/// The address the daemon listens to on platforms without unix sockets
+#[cfg(not(unix))]
+const DEFAULT_DAEMON_ADDR: &str = "127.0.0.1:5476";
+
+/// The stream a client and the daemon talk over
+#[cfg(unix)]
+pub type LocalStream = tokio::net::UnixStream;
+/// The stream a client and the daemon talk over
+#[cfg(not(unix))]
+pub type LocalStream = tokio::net::TcpStream;
+
+/// A request a client sends to the daemon, one json object per line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Request {
+    /// Proves that the client may use the daemon, has to be the first request of every connection
+    Auth { token: String },
+    /// Whether tor is up, the storage is unlocked and which connections exist
+    Status,
+    /// Unlocks the storage, creating it if it does not exist yet
+    Unlock { password: String },
+    /// Saves the storage and wipes the decrypted data from memory
+    Lock,
+    /// Our own onion hostname
+    Hostname,
+    /// Every chat with its nickname and message count
+    Chats,
+    /// The messages of a single chat
+    Messages {
+        hostname: String,
+        limit: Option<usize>,
+    },
+    /// Connects to the given onion hostname
+    Connect { hostname: String },
+    /// Sends a message, connecting first if needed
+    Send { hostname: String, message: String },
+    /// Sets the average interval dummy packets are sent in, None disables cover traffic
+    CoverTraffic { interval_ms: Option<u64> },
+    /// Streams every event of the core until the client disconnects
+    Tail,
+    /// Saves the storage, stops tor and exits the daemon
+    Shutdown,
+}
+
+/// A line the daemon answers with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    /// The request was handled
+    Ok { data: Value },
+    /// The request failed
+    Error { message: String },
+    /// An event of the core, only sent to clients which requested `tail`
+    Event { name: String, payload: Value },
+}
+
+/// Where the daemon listens on unix, `enkrypton_root/daemon.sock` by default
+#[cfg(unix)]
+pub fn socket_path() -> PathBuf {
+    env::var(DAEMON_SOCKET_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| shared::get_root_dir().join("daemon.sock"))
+}
+
+/// Where the daemon listens on platforms without unix sockets
+#[cfg(not(unix))]
+pub fn socket_addr() -> String {
+    env::var(DAEMON_SOCKET_ENV).unwrap_or(DEFAULT_DAEMON_ADDR.to_string())
+}
+
+/// The file holding the token clients authenticate with. It must only be readable by the current user,
+/// on windows it is stored in the local app data of the user for that reason.
+pub fn token_path() -> PathBuf {
+    #[cfg(not(unix))]
+    if let Some(dir) = env::var_os("LOCALAPPDATA") {
+        return PathBuf::from(dir).join("enkrypton").join("daemon.token");
+    }
+
+    shared::get_root_dir().join("daemon.token")
+}
+
+/// Generates a new random token and writes it to the token file, only readable by the current user
+///
+/// # Returns
+///
+/// The generated token
+pub fn create_token() -> Result<String> {
+    let mut token = [0u8; 32];
+    rand_bytes(&mut token)?;
+    let token = hex::encode(token);
+
+    let path = token_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    // The permissions of an existing file would be kept, so a new one is created
+    if path.exists() {
+        fs::remove_file(&path)?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    std::io::Write::write_all(&mut options.open(&path)?, token.as_bytes())?;
+    Ok(token)
+}
+
+/// Checks the token a client sent in constant time
+///
+/// # Arguments
+///
+/// * `expected` - The token of this daemon
+/// * `given` - The token the client sent
+///
+/// # Returns
+///
+/// Whether the client may use the daemon
+pub fn verify_token(expected: &str, given: &str) -> bool {
+    expected.len() == given.len() && memcmp::eq(expected.as_bytes(), given.as_bytes())
+}
+
+/// Connects to the running daemon and authenticates with the token of the token file
+pub async fn connect() -> Result<LocalStream> {
+    let token = fs::read_to_string(token_path())
+        .map_err(|e| anyhow!("Could not read the token of the daemon ({})", e))?;
+
+    #[cfg(unix)]
+    let mut stream = LocalStream::connect(socket_path()).await?;
+    #[cfg(not(unix))]
+    let mut stream = LocalStream::connect(socket_addr()).await?;
+
+    let mut line = serde_json::to_string(&Request::Auth { token: token.trim().to_string() })?;
+    line.push('\n');
+    stream.write_all(line.as_bytes()).await?;
+
+    Ok(stream)
+}
@@ -0,0 +1,61 @@
+use std::io::{self, BufRead, Write};
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use crate::api::{connect, Request, Response};
+
+/// Sends a single request to the running daemon and prints what it answers.
+/// For `tail` every following event is printed as one json line.
+///
+/// # Arguments
+///
+/// * `req` - The request to send
+///
+pub async fn run_client(req: Request) -> Result<()> {
+    let is_tail = matches!(req, Request::Tail);
+
+    let stream = connect()
+        .await
+        .map_err(|e| anyhow!("Could not connect to the daemon, is it running? ({})", e))?;
+    let (read, mut write) = tokio::io::split(stream);
+
+    let mut line = serde_json::to_string(&req)?;
+    line.push('\n');
+    write.write_all(line.as_bytes()).await?;
+    write.flush().await?;
+
+    let mut lines = BufReader::new(read).lines();
+    while let Some(line) = lines.next_line().await? {
+        match serde_json::from_str::<Response>(&line)? {
+            Response::Ok { data } => {
+                if data != Value::Null {
+                    println!("{}", serde_json::to_string_pretty(&data)?);
+                }
+            }
+            Response::Error { message } => return Err(anyhow!(message)),
+            Response::Event { name, payload } => {
+                println!("{}", serde_json::json!({ "event": name, "payload": payload }));
+                io::stdout().flush()?;
+            }
+        }
+
+        if !is_tail {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the storage password from stdin, so it does not end up in the shell history or process list
+pub fn read_password() -> Result<String> {
+    eprint!("Password: ");
+    io::stderr().flush()?;
+
+    let mut password = String::new();
+    io::stdin().lock().read_line(&mut password)?;
+
+    Ok(password.trim_end_matches(['\r', '\n']).to_string())
+}
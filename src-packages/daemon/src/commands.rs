@@ -0,0 +1,200 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use messaging::general::{get_cover_traffic, set_cover_traffic, sync_client_auth, MESSAGING};
+use serde_json::{json, Value};
+use shared::onion::parse_onion_hostname;
+use storage_internal::STORAGE;
+use tor_proxy::{consts::TOR_START_LOCK, service::get_service_hostname, supervisor::current_status};
+
+use crate::api::Request;
+
+/// Handles every request except `tail` and `shutdown`, which need the connection / the daemon itself
+///
+/// # Arguments
+///
+/// * `req` - The request of the client
+///
+/// # Returns
+///
+/// The data to answer the client with
+pub async fn handle_request(req: Request) -> Result<Value> {
+    match req {
+        Request::Status => status().await,
+        Request::Unlock { password } => unlock(&password).await,
+        Request::Lock => lock().await,
+        Request::Hostname => hostname().await,
+        Request::Chats => chats().await,
+        Request::Messages { hostname, limit } => messages(&hostname, limit).await,
+        Request::Connect { hostname } => connect(&hostname).await,
+        Request::Send { hostname, message } => send(&hostname, &message).await,
+        Request::CoverTraffic { interval_ms } => cover_traffic(interval_ms),
+        Request::Auth { .. } | Request::Tail | Request::Shutdown => Err(anyhow!("Request has to be handled by the server")),
+    }
+}
+
+/// Fails if the storage has not been unlocked yet
+async fn assert_unlocked() -> Result<()> {
+    if !STORAGE.read().await.is_unlocked()? {
+        return Err(anyhow!("Storage is locked, unlock it first"));
+    }
+
+    Ok(())
+}
+
+/// Checks and normalizes a hostname of the api, so typos are caught before tor tries to find the service
+fn parse_hostname(hostname: &str) -> Result<String> {
+    parse_onion_hostname(hostname).map_err(|e| anyhow!("Invalid onion hostname: {}", e))
+}
+
+async fn status() -> Result<Value> {
+    let storage = STORAGE.read().await;
+    let exists = storage.exists()?;
+    let unlocked = storage.is_unlocked()?;
+    drop(storage);
+
+    let connections = MESSAGING.read().await.get_connection_states().await;
+    Ok(json!({
+        "tor_status": current_status(),
+        "storage_exists": exists,
+        "storage_unlocked": unlocked,
+        "connections": connections,
+        "cover_traffic_ms": get_cover_traffic().map(|i| i.as_millis() as u64),
+    }))
+}
+
+/// Unlocks the storage, or creates a new one with the given password if there is none.
+/// Writes the client authorization keys for tor and reconnects to active peers afterwards,
+/// failing to write the keys is reported as warning
+async fn unlock(password: &str) -> Result<Value> {
+    unlock_storage(password).await?;
+
+    // Tor could not read the client authorization keys while the storage was locked
+    let synced = sync_client_auth().await;
+
+    // Connections have been closed when the storage was locked
+    MESSAGING.read().await.reconnect_active().await;
+
+    if let Err(e) = synced {
+        warn!("Could not write client authorization keys: {:?}", e);
+        return Ok(json!({ "warning": format!("Could not write client authorization keys: {}", e) }));
+    }
+
+    Ok(Value::Null)
+}
+
+async fn unlock_storage(password: &str) -> Result<()> {
+    let mut state = STORAGE.write().await;
+    if !state.has_parsed() {
+        state.read_or_generate(password).await?;
+
+        if state.is_unlocked()? {
+            return Ok(());
+        }
+    }
+
+    state.try_unlock(password.as_bytes()).await
+}
+
+/// Closes every connection, saves the storage and wipes its decrypted data, it has to be unlocked again afterwards
+async fn lock() -> Result<Value> {
+    // Messages arriving after locking could not be decrypted anymore
+    MESSAGING.read().await.close_all().await;
+    STORAGE.write().await.lock().await?;
+    Ok(Value::Null)
+}
+
+async fn hostname() -> Result<Value> {
+    // Wait for the tor start to finish first
+    let _ = TOR_START_LOCK.read().await;
+    let hostname = get_service_hostname(false).await?;
+
+    match hostname {
+        Some(h) if !h.is_empty() => Ok(json!(h)),
+        _ => Err(anyhow!("Hostname is empty, tor has probably not started")),
+    }
+}
+
+/// Lists the chats without their keys, those never leave the daemon
+async fn chats() -> Result<Value> {
+    assert_unlocked().await?;
+
+    let chats = STORAGE
+        .read()
+        .await
+        .get_data(|data| {
+            let chats: Vec<Value> = data
+                .chats
+                .iter()
+                .map(|(hostname, chat)| {
+                    json!({
+                        "hostname": hostname,
+                        "nickname": chat.nickname,
+                        "messages": chat.messages.len(),
+                        "last_message": chat.messages.last().map(|m| m.date),
+                    })
+                })
+                .collect();
+
+            Ok(chats)
+        })
+        .await?;
+
+    Ok(json!(chats))
+}
+
+/// Gets the messages of a chat, only the latest `limit` ones if given
+async fn messages(hostname: &str, limit: Option<usize>) -> Result<Value> {
+    assert_unlocked().await?;
+    let hostname = &parse_hostname(hostname)?;
+
+    let messages = STORAGE
+        .read()
+        .await
+        .get_data(|data| {
+            let chat = data
+                .chats
+                .get(hostname)
+                .ok_or(anyhow!("There is no chat with {}", hostname))?;
+
+            let skip = limit.map_or(0, |l| chat.messages.len().saturating_sub(l));
+            Ok(chat.messages[skip..].to_vec())
+        })
+        .await?;
+
+    Ok(serde_json::to_value(messages)?)
+}
+
+async fn connect(hostname: &str) -> Result<Value> {
+    assert_unlocked().await?;
+    let hostname = &parse_hostname(hostname)?;
+
+    if MESSAGING.read().await.is_connected(hostname).await {
+        return Ok(Value::Null);
+    }
+
+    // Dialing takes a while, other messaging operations should not have to wait for it
+    MESSAGING.read().await.get_or_connect(hostname).await?;
+    Ok(Value::Null)
+}
+
+async fn send(hostname: &str, message: &str) -> Result<Value> {
+    assert_unlocked().await?;
+    let hostname = &parse_hostname(hostname)?;
+
+    let manager = MESSAGING.read().await;
+    let conn = manager.get_or_connect(hostname).await?;
+
+    debug!("Waiting until {} is verified...", hostname);
+    manager.wait_until_verified(hostname).await?;
+    conn.send_msg(message).await?;
+
+    Ok(Value::Null)
+}
+
+/// Enables cover traffic with the given average interval or disables it, the setting is stored
+fn cover_traffic(interval_ms: Option<u64>) -> Result<Value> {
+    set_cover_traffic(interval_ms.map(Duration::from_millis))?;
+    Ok(Value::Null)
+}
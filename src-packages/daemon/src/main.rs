@@ -0,0 +1,110 @@
+//! Headless enkrypton. `enkrypton-daemon run` starts tor and the messaging server,
+//! every other subcommand talks to the running daemon over a local socket
+//! (`enkrypton_root/daemon.sock`, or `ENKRYPTON_DAEMON_SOCKET`) using one json object per line.
+//! Clients authenticate with the token the daemon writes to `daemon.token`, which only the user can read.
+
+mod api;
+mod client;
+mod commands;
+mod server;
+mod sink;
+mod startup;
+#[cfg(test)]
+mod tests;
+
+use std::{env, process::ExitCode};
+
+use anyhow::{anyhow, Result};
+use log::error;
+
+use api::Request;
+use client::{read_password, run_client};
+use startup::run_daemon;
+
+const USAGE: &str = "Usage: enkrypton-daemon <command>
+
+Commands:
+    run                           Starts tor, the messaging server and the local api
+    status                        Shows the tor, storage and connection status
+    unlock                        Unlocks (or creates) the storage, reads the password from stdin
+    lock                          Saves the storage and wipes its decrypted data from memory
+    hostname                      Prints our own onion hostname
+    chats                         Lists every chat
+    messages <hostname> [limit]   Prints the (latest `limit`) messages of a chat
+    connect <hostname>            Connects to the given onion hostname
+    send <hostname> <message...>  Sends a message
+    cover <interval_ms|off>       Sends cover traffic in the given average interval, or disables it
+    tail                          Prints every event as json line until interrupted
+    shutdown                      Stops the daemon";
+
+/// Parses the arguments into the request to send to the daemon
+///
+/// # Returns
+///
+/// `None` if the daemon itself should run
+fn parse_args(args: &[String]) -> Result<Option<Request>> {
+    let hostname = || args.get(1).cloned().ok_or(anyhow!("Missing hostname"));
+
+    let req = match args.first().map(String::as_str) {
+        Some("run") => return Ok(None),
+        Some("status") => Request::Status,
+        Some("unlock") => Request::Unlock { password: read_password()? },
+        Some("lock") => Request::Lock,
+        Some("hostname") => Request::Hostname,
+        Some("chats") => Request::Chats,
+        Some("messages") => Request::Messages {
+            hostname: hostname()?,
+            limit: args.get(2).map(|l| l.parse()).transpose()?,
+        },
+        Some("connect") => Request::Connect { hostname: hostname()? },
+        Some("send") => {
+            if args.len() < 3 {
+                return Err(anyhow!("Missing message"));
+            }
+
+            Request::Send {
+                hostname: hostname()?,
+                message: args[2..].join(" "),
+            }
+        }
+        Some("cover") => Request::CoverTraffic {
+            interval_ms: match args.get(1).map(String::as_str) {
+                Some("off") => None,
+                Some(ms) => Some(ms.parse()?),
+                None => return Err(anyhow!("Missing interval")),
+            },
+        },
+        Some("tail") => Request::Tail,
+        Some("shutdown") => Request::Shutdown,
+        Some(cmd) => return Err(anyhow!("Unknown command {}", cmd)),
+        None => return Err(anyhow!("Missing command")),
+    };
+
+    Ok(Some(req))
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let req = match parse_args(&args) {
+        Ok(req) => req,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let res = match req {
+        None => run_daemon().await,
+        Some(req) => run_client(req).await,
+    };
+
+    if let Err(e) = res {
+        error!("{:?}", e);
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
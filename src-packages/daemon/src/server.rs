@@ -0,0 +1,216 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use log::{error, info, warn};
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    sync::{broadcast::error::RecvError, Notify},
+};
+
+use crate::{
+    api::{create_token, token_path, verify_token, Request, Response},
+    commands::handle_request,
+    sink::DaemonEventSink,
+};
+
+/// The longest line an unauthenticated client may send, the auth request is far shorter
+pub const MAX_AUTH_LINE: usize = 1024;
+
+/// Listens on the local socket until a client requests a shutdown
+///
+/// # Arguments
+///
+/// * `sink` - The sink tailing clients subscribe to
+///
+pub async fn serve(sink: Arc<DaemonEventSink>) -> Result<()> {
+    let shutdown = Arc::new(Notify::new());
+    // Whoever can talk to the daemon can read and send messages, so clients have to prove they can read this file
+    let token = Arc::new(create_token()?);
+
+    #[cfg(unix)]
+    let listener = {
+        use std::{
+            fs::{self, DirBuilder},
+            os::unix::fs::{DirBuilderExt, PermissionsExt},
+        };
+
+        let path = crate::api::socket_path();
+        // A socket left over from a crashed daemon would make binding fail
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+
+        // The socket is bound in a directory only we can access and moved once its permissions are set,
+        // so there is no moment where others could connect to it
+        let private_dir = path.with_file_name(format!(".daemon-{}", std::process::id()));
+        if private_dir.exists() {
+            fs::remove_dir_all(&private_dir)?;
+        }
+        DirBuilder::new().mode(0o700).create(&private_dir)?;
+
+        let tmp_path = private_dir.join("daemon.sock");
+        let listener = tokio::net::UnixListener::bind(&tmp_path)?;
+        fs::set_permissions(&tmp_path, fs::Permissions::from_mode(0o600))?;
+        fs::rename(&tmp_path, &path)?;
+        fs::remove_dir(&private_dir)?;
+
+        info!("Listening on {:?}", path);
+        listener
+    };
+
+    #[cfg(not(unix))]
+    let listener = {
+        let addr = crate::api::socket_addr();
+        let listener = tokio::net::TcpListener::bind(&addr).await?;
+
+        info!("Listening on {}", addr);
+        listener
+    };
+
+    loop {
+        let stream = tokio::select! {
+            res = listener.accept() => res?.0,
+            _ = shutdown.notified() => break,
+        };
+
+        let sink = sink.clone();
+        let shutdown = shutdown.clone();
+        let token = token.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_client(stream, &token, sink, shutdown).await {
+                warn!("Client connection failed: {:?}", e);
+            }
+        });
+    }
+
+    #[cfg(unix)]
+    let _ = std::fs::remove_file(crate::api::socket_path());
+    let _ = std::fs::remove_file(token_path());
+
+    Ok(())
+}
+
+/// Answers every request of a single client, one json line per request.
+/// The first line has to authenticate the client, otherwise the connection is closed.
+pub async fn handle_client<S: AsyncRead + AsyncWrite>(
+    stream: S,
+    token: &str,
+    sink: Arc<DaemonEventSink>,
+    shutdown: Arc<Notify>,
+) -> Result<()> {
+    let (read, mut write) = tokio::io::split(stream);
+    let mut reader = BufReader::new(read);
+
+    let authenticated = match read_line_limited(&mut reader, MAX_AUTH_LINE).await? {
+        Some(line) => matches!(
+            serde_json::from_str::<Request>(&line),
+            Ok(Request::Auth { token: given }) if verify_token(token, &given)
+        ),
+        None => return Ok(()),
+    };
+
+    if !authenticated {
+        warn!("Client sent an invalid token, closing connection");
+        write_response(&mut write, &Response::Error { message: "Invalid token".to_string() }).await?;
+        return Ok(());
+    }
+
+    let mut lines = reader.lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let req = match serde_json::from_str::<Request>(&line) {
+            Ok(req) => req,
+            Err(e) => {
+                write_response(&mut write, &Response::Error { message: format!("Invalid request: {}", e) }).await?;
+                continue;
+            }
+        };
+
+        match req {
+            Request::Tail => {
+                write_response(&mut write, &Response::Ok { data: serde_json::Value::Null }).await?;
+                return tail(&mut write, &sink).await;
+            }
+            Request::Shutdown => {
+                info!("Shutdown requested");
+                write_response(&mut write, &Response::Ok { data: serde_json::Value::Null }).await?;
+                // notify_one stores a permit, so the accept loop sees it even if it is not waiting right now
+                shutdown.notify_one();
+                return Ok(());
+            }
+            req => {
+                let res = match handle_request(req).await {
+                    Ok(data) => Response::Ok { data },
+                    Err(e) => {
+                        error!("Could not handle request: {:?}", e);
+                        Response::Error { message: e.to_string() }
+                    }
+                };
+
+                write_response(&mut write, &res).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a single line without buffering more than `max` bytes of it
+///
+/// # Arguments
+///
+/// * `reader` - The reader to read the line from
+/// * `max` - The maximum length of the line, without the line break
+///
+/// # Returns
+///
+/// The line or `None` if the stream ended, fails if the line is longer than `max`
+async fn read_line_limited<R: AsyncBufRead + Unpin>(reader: &mut R, max: usize) -> Result<Option<String>> {
+    let mut buf = Vec::new();
+    let read = (&mut *reader).take(max as u64 + 1).read_until(b'\n', &mut buf).await?;
+    if read == 0 {
+        return Ok(None);
+    }
+
+    if buf.last() == Some(&b'\n') {
+        buf.pop();
+    } else if buf.len() > max {
+        return Err(anyhow!("Line exceeds {} bytes, closing connection", max));
+    }
+
+    if buf.last() == Some(&b'\r') {
+        buf.pop();
+    }
+
+    Ok(Some(String::from_utf8(buf)?))
+}
+
+/// Forwards every event to the client until it disconnects
+async fn tail<W: AsyncWriteExt + Unpin>(write: &mut W, sink: &DaemonEventSink) -> Result<()> {
+    let mut rx = sink.subscribe();
+
+    loop {
+        let (name, payload) = match rx.recv().await {
+            Ok(e) => e,
+            Err(RecvError::Lagged(n)) => {
+                warn!("Tailing client missed {} events", n);
+                continue;
+            }
+            Err(RecvError::Closed) => return Ok(()),
+        };
+
+        write_response(write, &Response::Event { name, payload }).await?;
+    }
+}
+
+async fn write_response<W: AsyncWriteExt + Unpin>(write: &mut W, res: &Response) -> Result<()> {
+    let mut line = serde_json::to_string(res)?;
+    line.push('\n');
+
+    write.write_all(line.as_bytes()).await?;
+    write.flush().await?;
+    Ok(())
+}
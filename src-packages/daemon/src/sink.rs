@@ -0,0 +1,37 @@
+use anyhow::Result;
+use payloads::event::EventSink;
+use serde_json::Value;
+use tokio::sync::broadcast::{self, Receiver, Sender};
+
+/// How many events a slow `tail` client may lag behind before it misses some
+const EVENT_BUFFER: usize = 256;
+
+/// Forwards every event of the core to the clients that are tailing the daemon
+pub struct DaemonEventSink {
+    tx: Sender<(String, Value)>,
+}
+
+impl DaemonEventSink {
+    /// Creates a new sink without any subscribers
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(EVENT_BUFFER);
+        Self { tx }
+    }
+
+    /// Subscribes to every event emitted from now on
+    ///
+    /// # Returns
+    ///
+    /// A receiver of the event names and their payloads
+    pub fn subscribe(&self) -> Receiver<(String, Value)> {
+        self.tx.subscribe()
+    }
+}
+
+impl EventSink for DaemonEventSink {
+    fn emit_event(&self, name: &str, payload: Value) -> Result<()> {
+        // Sending only fails if nobody is tailing, which is fine
+        let _ = self.tx.send((name.to_string(), payload));
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use encryption::disable_core_dumps;
+use log::{debug, error, info, warn};
+use messaging::server::server::start_webserver;
+use payloads::event::{emit_payload, set_event_sink};
+use storage_internal::STORAGE;
+use tor_proxy::{
+    consts::setup_tor_channels,
+    manager::{start_tor, stop_tor, wait_for_exit},
+    supervisor::{spawn_supervisor, stop_supervisor},
+};
+
+use crate::{server::serve, sink::DaemonEventSink};
+
+/// Starts tor, the messaging server and the local api and runs until
+/// a client requests a shutdown or the process is told to terminate
+pub async fn run_daemon() -> Result<()> {
+    // Decrypted messages and keys should never end up in a core dump
+    if let Err(e) = disable_core_dumps() {
+        warn!("Could not disable core dumps: {:?}", e);
+    }
+
+    let sink = Arc::new(DaemonEventSink::new());
+    set_event_sink(sink.clone());
+
+    setup_tor_channels().await;
+    // Start the local server which is used for receiving / sending messages to clients
+    start_webserver();
+
+    // Clients may already unlock the storage while tor is still starting
+    let mut server = tokio::spawn(serve(sink));
+
+    start_tor(|payload| {
+        if let Err(e) = emit_payload(payload) {
+            warn!("Tor start could not send payload {:?}", e)
+        }
+    })
+    .await?;
+
+    // Watching tor from now on, so it is restarted if it crashes
+    spawn_supervisor().await?;
+    info!("Tor is running, daemon is ready");
+
+    tokio::select! {
+        res = &mut server => res??,
+        _ = wait_for_signal() => info!("Received exit signal"),
+    }
+
+    on_exit().await
+}
+
+/// Waits for ctrl-c or, on unix, SIGTERM
+async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {},
+                    _ = term.recv() => {},
+                }
+                return;
+            }
+            Err(e) => error!("Could not listen for SIGTERM: {}", e),
+        }
+    }
+
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        error!("Could not listen for exit signals: {}", e);
+    }
+}
+
+/// Saves the storage and stops tor, same as the app does when its window is closed
+async fn on_exit() -> Result<()> {
+    debug!("Acquiring storage lock...");
+
+    let mut e = STORAGE.write().await;
+    // A storage that was never unlocked has nothing to save
+    if e.is_unlocked()? {
+        debug!("Saving storage...");
+        e.save().await?;
+    }
+
+    // Otherwise the supervisor would restart tor right away
+    stop_supervisor().await;
+    stop_tor().await?;
+    wait_for_exit().await;
+    e.exit().await?;
+
+    Ok(())
+}
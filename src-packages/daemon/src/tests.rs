@@ -0,0 +1,165 @@
+use std::sync::Arc;
+
+use serde_json::json;
+use tokio::{
+    io::{duplex, AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream},
+    sync::Notify,
+};
+
+use crate::{
+    api::{verify_token, Request, Response},
+    parse_args,
+    server::{handle_client, MAX_AUTH_LINE},
+    sink::DaemonEventSink,
+};
+
+const TOKEN: &str = "0123456789abcdef";
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|a| a.to_string()).collect()
+}
+
+/// Runs `handle_client` on one end of an in-memory stream and returns the other end
+fn spawn_client(shutdown: Arc<Notify>) -> (DuplexStream, tokio::task::JoinHandle<anyhow::Result<()>>) {
+    let (client, server) = duplex(4 * MAX_AUTH_LINE);
+    let handle = tokio::spawn(async move {
+        handle_client(server, TOKEN, Arc::new(DaemonEventSink::new()), shutdown).await
+    });
+
+    (client, handle)
+}
+
+async fn send(client: &mut DuplexStream, req: &Request) {
+    let mut line = serde_json::to_string(req).unwrap();
+    line.push('\n');
+    client.write_all(line.as_bytes()).await.unwrap();
+}
+
+async fn read_response(client: &mut DuplexStream) -> Option<Response> {
+    let mut line = String::new();
+    BufReader::new(client).read_line(&mut line).await.unwrap();
+    (!line.is_empty()).then(|| serde_json::from_str(&line).unwrap())
+}
+
+#[test]
+fn parse_commands() {
+    assert!(parse_args(&args(&["run"])).unwrap().is_none());
+    assert!(matches!(parse_args(&args(&["status"])).unwrap(), Some(Request::Status)));
+    assert!(matches!(parse_args(&args(&["shutdown"])).unwrap(), Some(Request::Shutdown)));
+
+    match parse_args(&args(&["send", "abc.onion", "hello", "there"])).unwrap() {
+        Some(Request::Send { hostname, message }) => {
+            assert_eq!(hostname, "abc.onion");
+            assert_eq!(message, "hello there");
+        }
+        req => panic!("Unexpected request {:?}", req),
+    }
+
+    match parse_args(&args(&["messages", "abc.onion", "10"])).unwrap() {
+        Some(Request::Messages { hostname, limit }) => {
+            assert_eq!(hostname, "abc.onion");
+            assert_eq!(limit, Some(10));
+        }
+        req => panic!("Unexpected request {:?}", req),
+    }
+
+    assert!(matches!(
+        parse_args(&args(&["cover", "off"])).unwrap(),
+        Some(Request::CoverTraffic { interval_ms: None })
+    ));
+    assert!(matches!(
+        parse_args(&args(&["cover", "5000"])).unwrap(),
+        Some(Request::CoverTraffic { interval_ms: Some(5000) })
+    ));
+}
+
+#[test]
+fn parse_invalid_commands() {
+    assert!(parse_args(&[]).is_err());
+    assert!(parse_args(&args(&["unknown"])).is_err());
+    assert!(parse_args(&args(&["connect"])).is_err());
+    assert!(parse_args(&args(&["send", "abc.onion"])).is_err());
+    assert!(parse_args(&args(&["messages", "abc.onion", "many"])).is_err());
+    assert!(parse_args(&args(&["cover"])).is_err());
+}
+
+#[test]
+fn request_serialization() {
+    let req = Request::Send { hostname: "abc.onion".to_string(), message: "hi".to_string() };
+    assert_eq!(
+        serde_json::to_value(&req).unwrap(),
+        json!({ "cmd": "send", "hostname": "abc.onion", "message": "hi" })
+    );
+
+    let req = serde_json::from_value::<Request>(json!({ "cmd": "cover_traffic", "interval_ms": null })).unwrap();
+    assert!(matches!(req, Request::CoverTraffic { interval_ms: None }));
+
+    let req = serde_json::from_str::<Request>(r#"{"cmd":"messages","hostname":"abc.onion"}"#).unwrap();
+    assert!(matches!(req, Request::Messages { limit: None, .. }));
+
+    assert!(serde_json::from_str::<Request>(r#"{"cmd":"unknown"}"#).is_err());
+    assert!(serde_json::from_str::<Request>(r#"{"cmd":"connect"}"#).is_err());
+}
+
+#[test]
+fn response_serialization() {
+    let res = Response::Error { message: "failed".to_string() };
+    assert_eq!(serde_json::to_value(&res).unwrap(), json!({ "type": "error", "message": "failed" }));
+
+    let res = serde_json::from_value::<Response>(json!({ "type": "event", "name": "ws_msgs", "payload": [1] })).unwrap();
+    assert!(matches!(res, Response::Event { name, payload } if name == "ws_msgs" && payload == json!([1])));
+}
+
+#[test]
+fn token_verification() {
+    assert!(verify_token(TOKEN, TOKEN));
+    assert!(!verify_token(TOKEN, "0123456789abcdeF"));
+    assert!(!verify_token(TOKEN, "0123456789abcde"));
+    assert!(!verify_token(TOKEN, ""));
+}
+
+#[tokio::test]
+async fn invalid_token_closes_connection() {
+    let (mut client, handle) = spawn_client(Arc::new(Notify::new()));
+
+    send(&mut client, &Request::Auth { token: "wrong".to_string() }).await;
+    assert!(matches!(read_response(&mut client).await, Some(Response::Error { .. })));
+    assert!(read_response(&mut client).await.is_none());
+    handle.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn request_before_auth_closes_connection() {
+    let (mut client, handle) = spawn_client(Arc::new(Notify::new()));
+
+    send(&mut client, &Request::Shutdown).await;
+    assert!(matches!(read_response(&mut client).await, Some(Response::Error { .. })));
+    handle.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn overlong_auth_line_closes_connection() {
+    let (mut client, handle) = spawn_client(Arc::new(Notify::new()));
+
+    client.write_all(&vec![b'a'; MAX_AUTH_LINE + 1]).await.unwrap();
+    assert!(handle.await.unwrap().is_err());
+    assert!(read_response(&mut client).await.is_none());
+}
+
+#[tokio::test]
+async fn authenticated_client_is_answered() {
+    let shutdown = Arc::new(Notify::new());
+    let (mut client, handle) = spawn_client(shutdown.clone());
+
+    send(&mut client, &Request::Auth { token: TOKEN.to_string() }).await;
+    client.write_all(b"{\"cmd\":\"unknown\"}\n").await.unwrap();
+    match read_response(&mut client).await {
+        Some(Response::Error { message }) => assert!(message.starts_with("Invalid request")),
+        res => panic!("Unexpected response {:?}", res),
+    }
+
+    send(&mut client, &Request::Shutdown).await;
+    assert!(matches!(read_response(&mut client).await, Some(Response::Ok { .. })));
+    handle.await.unwrap().unwrap();
+    shutdown.notified().await;
+}
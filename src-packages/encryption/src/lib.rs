@@ -1,33 +1,37 @@
 pub mod consts;
+mod pool;
+mod secret;
 #[cfg(test)]
 mod tests;
 
+pub use pool::*;
+pub use secret::*;
+
 use anyhow::Result;
 use consts::{RSA_KEY_SIZE, RSA_PADDING};
 use openssl::{
     encrypt::{Decrypter, Encrypter},
     error::ErrorStack,
+    hash::{hash, MessageDigest},
     pkey::{PKey, Private, Public},
     rsa::Rsa,
 };
-use zeroize::{Zeroize, ZeroizeOnDrop};
-use lazy_static::lazy_static;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
 
-lazy_static! {
-    pub static ref ZEROIZE_RSA_KEY: Rsa<Private> = Rsa::private_key_from_pem(include_bytes!("../zeroize_key")).unwrap();
-}
+/// A RSA private key. It is kept DER encoded in locked memory and only parsed for the single operation
+/// that needs it, openssl wipes the parsed key again when it is freed.
+#[derive(Debug, Clone, Zeroize, ZeroizeOnDrop)]
+pub struct PrivateKey(SecretBytes);
 
-/// Just a wrapper to the openssl RSA Key. Used for serialization and deserialization.
-#[derive(Clone, Debug)]
-pub struct PrivateKey(pub Rsa<Private>);
+/// Moves the key into locked memory, the openssl key is wiped when it is dropped afterwards
+impl TryFrom<Rsa<Private>> for PrivateKey {
+    type Error = ErrorStack;
 
-impl Zeroize for PrivateKey {
-    fn zeroize(&mut self) {
-        ZEROIZE_RSA_KEY.clone_into(&mut self.0);
+    fn try_from(key: Rsa<Private>) -> Result<Self, Self::Error> {
+        Ok(Self(SecretBytes::from(key.private_key_to_der()?)))
     }
 }
-impl ZeroizeOnDrop for PrivateKey {}
 
 /// Serialize the private key to a PEM string
 impl Serialize for PrivateKey {
@@ -36,10 +40,10 @@ impl Serialize for PrivateKey {
         S: Serializer,
     {
         let bytes = self
-            .0
-            .private_key_to_pem()
+            .rsa()
+            .and_then(|key| key.private_key_to_pem())
             .map_err(|e| ser::Error::custom(e.to_string()))?;
-        serializer.serialize_bytes(&bytes)
+        serializer.serialize_bytes(&Zeroizing::new(bytes))
     }
 }
 
@@ -51,8 +55,9 @@ impl<'a> Deserialize<'a> for PrivateKey {
     {
         // We are deserializing the key from bytes and adding it to the deserializer
         <Vec<u8>>::deserialize(deserializer).and_then(|s| {
+            let s = Zeroizing::new(s);
             Rsa::private_key_from_pem(&s)
-                .and_then(|e| Ok(PrivateKey(e)))
+                .and_then(PrivateKey::try_from)
                 .map_err(|e| de::Error::custom(e.to_string()))
         })
     }
@@ -67,7 +72,7 @@ impl TryInto<PublicKey> for PrivateKey {
     type Error = ErrorStack;
 
     fn try_into(self) -> Result<PublicKey, Self::Error> {
-        let pem = self.0.public_key_to_pem()?;
+        let pem = self.rsa()?.public_key_to_pem()?;
         // Again, we are just converting the pem to a public key
         Rsa::public_key_from_pem(pem.as_slice()).map(|e| PublicKey(e))
     }
@@ -104,10 +109,7 @@ impl<'a> Deserialize<'a> for PublicKey {
 
 impl PrivateKey {
     /// Generates a new RSA key pair with the key size of RSA_KEY_SIZE.
-    ///
-    /// # Returns
-    ///
-    /// The generated RSA key pair.
+    /// This takes a while and blocks the current thread, use `generate_pair_async` in async code.
     ///
     /// # Returns
     ///
@@ -115,7 +117,17 @@ impl PrivateKey {
     pub fn generate_pair() -> Result<Self> {
         let res = Rsa::generate(*RSA_KEY_SIZE)?;
 
-        Ok(Self(res))
+        Ok(Self::try_from(res)?)
+    }
+
+    /// Parses the key for a single operation. The parsed key should be dropped as soon as possible,
+    /// it is not stored in locked memory.
+    ///
+    /// # Returns
+    ///
+    /// The openssl key, fails if this key has been wiped
+    pub fn rsa(&self) -> Result<Rsa<Private>, ErrorStack> {
+        Rsa::private_key_from_der(self.0.expose())
     }
 
     /// Decrypts the given data with the given private key.
@@ -129,7 +141,7 @@ impl PrivateKey {
     ///
     ///
     pub fn decrypt(&self, encrypted: &[u8]) -> Result<Vec<u8>> {
-        let key = PKey::from_rsa(self.0.clone())?;
+        let key = PKey::from_rsa(self.rsa()?)?;
 
         let mut decrypter = Decrypter::new(&key)?;
         decrypter.set_rsa_padding(*RSA_PADDING)?;
@@ -147,6 +159,18 @@ impl PrivateKey {
 }
 
 impl PublicKey {
+    /// Calculates the fingerprint of this key, so users can compare keys without the whole key
+    ///
+    /// # Returns
+    /// The sha256 hash of the DER encoded key as lowercase hex.
+    ///
+    pub fn fingerprint(&self) -> Result<String> {
+        let der = self.0.public_key_to_der()?;
+        let digest = hash(MessageDigest::sha256(), &der)?;
+
+        Ok(digest.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+
     /// Encrypts the given data with the given public key.
     ///
     /// # Arguments
@@ -0,0 +1,106 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+};
+
+use anyhow::Result;
+use lazy_static::lazy_static;
+use log::{debug, warn};
+use tokio::{runtime::Handle, task::spawn_blocking};
+
+use crate::PrivateKey;
+
+lazy_static! {
+    /// How many rsa keys are generated ahead of time
+    pub static ref KEY_POOL_SIZE: usize = 2;
+    static ref KEY_POOL: KeyPool = KeyPool::default();
+}
+
+/// Keys that have been generated in the background, so creating a new chat
+/// does not have to wait for a rsa key to be generated
+#[derive(Default)]
+struct KeyPool {
+    keys: Mutex<Vec<PrivateKey>>,
+    /// Whether a blocking task is currently filling the pool
+    refilling: AtomicBool,
+}
+
+impl KeyPool {
+    fn len(&self) -> usize {
+        self.keys.lock().unwrap().len()
+    }
+
+    fn pop(&self) -> Option<PrivateKey> {
+        self.keys.lock().unwrap().pop()
+    }
+
+    /// Generates keys until the pool is full. Returns false if generating failed.
+    fn fill(&self) -> bool {
+        while self.len() < *KEY_POOL_SIZE {
+            match PrivateKey::generate_pair() {
+                Ok(key) => self.keys.lock().unwrap().push(key),
+                Err(e) => {
+                    warn!("Could not generate key for the key pool: {:?}", e);
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+}
+
+/// Fills the key pool on a blocking thread of the current tokio runtime.
+/// Does nothing if the pool is already being filled or if there is no runtime.
+pub fn fill_key_pool() {
+    let Ok(handle) = Handle::try_current() else {
+        return;
+    };
+
+    if KEY_POOL.refilling.swap(true, Ordering::AcqRel) {
+        return;
+    }
+
+    handle.spawn_blocking(|| {
+        loop {
+            let filled = KEY_POOL.fill();
+            KEY_POOL.refilling.store(false, Ordering::Release);
+
+            // A key might have been taken after the pool was full, but before
+            // the flag was cleared, so we have to check again
+            if !filled || KEY_POOL.len() >= *KEY_POOL_SIZE || KEY_POOL.refilling.swap(true, Ordering::AcqRel) {
+                break;
+            }
+        }
+
+        debug!("Key pool has been filled");
+    });
+}
+
+/// Drops every pre-generated key, which wipes them
+pub fn clear_key_pool() {
+    KEY_POOL.keys.lock().unwrap().clear();
+}
+
+/// Returns the amount of keys that are ready to be used
+pub fn pooled_keys() -> usize {
+    KEY_POOL.len()
+}
+
+impl PrivateKey {
+    /// Takes a pre-generated key pair from the key pool or, if the pool is empty,
+    /// generates a new one on a blocking thread. Never blocks the async runtime.
+    ///
+    /// # Returns
+    ///
+    /// The generated private key
+    pub async fn generate_pair_async() -> Result<PrivateKey> {
+        let pooled = KEY_POOL.pop();
+        fill_key_pool();
+
+        match pooled {
+            Some(key) => Ok(key),
+            None => spawn_blocking(PrivateKey::generate_pair).await?,
+        }
+    }
+}
@@ -0,0 +1,409 @@
+use std::{
+    alloc::{alloc_zeroed, dealloc, handle_alloc_error, Layout},
+    fmt::{self, Debug},
+    ops::Deref,
+    ptr::NonNull,
+    slice,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, PoisonError,
+    },
+};
+
+use anyhow::Result;
+use lazy_static::lazy_static;
+use log::warn;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Secrets are carved out of chunks of this size, so memory only has to be locked once per chunk
+const CHUNK_SIZE: usize = 64 * 1024;
+/// The smallest block a secret gets, every block is a power of two between this and `CHUNK_SIZE`
+const MIN_BLOCK_SIZE: usize = 16;
+/// How many different block sizes there are
+const BLOCK_SIZES: usize = (CHUNK_SIZE / MIN_BLOCK_SIZE).ilog2() as usize + 1;
+
+lazy_static! {
+    static ref PAGE_SIZE: usize = page_size();
+    /// Every secret up to `CHUNK_SIZE` bytes is stored in here
+    static ref ARENA: Mutex<Arena> = Mutex::new(Arena::default());
+}
+
+/// Set once locking memory has failed, so we don't warn about it over and over again
+static LOCK_FAILED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(target_family = "unix")]
+fn page_size() -> usize {
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    if size > 0 { size as usize } else { 4096 }
+}
+
+#[cfg(not(target_family = "unix"))]
+fn page_size() -> usize {
+    4096
+}
+
+/// Prevents the given pages from being swapped to disk and excludes them from core dumps, if the platform supports it.
+/// Returns false if the memory could not be locked (for example because RLIMIT_MEMLOCK has been reached)
+fn lock_memory(ptr: *const u8, len: usize) -> bool {
+    #[cfg(target_os = "linux")]
+    unsafe {
+        libc::madvise(ptr as *mut libc::c_void, len, libc::MADV_DONTDUMP);
+    }
+
+    #[cfg(target_family = "unix")]
+    let locked = unsafe { libc::mlock(ptr as *const libc::c_void, len) } == 0;
+
+    #[cfg(target_os = "windows")]
+    let locked = unsafe { windows_sys::Win32::System::Memory::VirtualLock(ptr as *const _, len) } != 0;
+
+    #[cfg(not(any(target_family = "unix", target_os = "windows")))]
+    let locked = false;
+
+    locked
+}
+
+fn unlock_memory(ptr: *const u8, len: usize) {
+    #[cfg(target_family = "unix")]
+    unsafe {
+        libc::munlock(ptr as *const libc::c_void, len);
+    }
+
+    #[cfg(target_os = "windows")]
+    unsafe {
+        windows_sys::Win32::System::Memory::VirtualUnlock(ptr as *const _, len);
+    }
+}
+
+/// Disables core dumps of this process, so secrets that are in memory can't end up on the disk when the app crashes.
+/// On linux this also prevents other processes of the same user from attaching a debugger.
+/// Should be called as early as possible when starting.
+pub fn disable_core_dumps() -> Result<()> {
+    #[cfg(target_family = "unix")]
+    {
+        let limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+        if unsafe { libc::setrlimit(libc::RLIMIT_CORE, &limit) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    if unsafe { libc::prctl(libc::PR_SET_DUMPABLE, 0, 0, 0, 0) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    Ok(())
+}
+
+/// A piece of memory secrets are stored in
+#[derive(Clone, Copy)]
+struct Block {
+    ptr: NonNull<u8>,
+    size: usize,
+    /// Whether the memory of the block could be locked
+    locked: bool,
+}
+
+impl Block {
+    /// Allocates page aligned memory and locks it
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The minimum size of the block, rounded up to whole pages
+    fn alloc_locked(size: usize) -> Self {
+        let size = size.div_ceil(*PAGE_SIZE).max(1) * *PAGE_SIZE;
+        let layout = Layout::from_size_align(size, *PAGE_SIZE).expect("Page size should be a valid alignment");
+
+        let ptr = NonNull::new(unsafe { alloc_zeroed(layout) }).unwrap_or_else(|| handle_alloc_error(layout));
+        let locked = lock_memory(ptr.as_ptr(), size);
+        if !locked && !LOCK_FAILED.swap(true, Ordering::Relaxed) {
+            warn!("Could not lock memory of secrets, they might be swapped to disk");
+        }
+
+        Self { ptr, size, locked }
+    }
+
+    /// Unlocks and frees a block allocated by `alloc_locked`
+    fn free_locked(self) {
+        if self.locked {
+            unlock_memory(self.ptr.as_ptr(), self.size);
+        }
+
+        let layout = Layout::from_size_align(self.size, *PAGE_SIZE).expect("Page size should be a valid alignment");
+        unsafe { dealloc(self.ptr.as_ptr(), layout) };
+    }
+
+    /// # Returns
+    ///
+    /// The whole memory of the block
+    fn memory(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.size) }
+    }
+
+    /// Splits the first `size` bytes off this block
+    fn split_off(&mut self, size: usize) -> Block {
+        let front = Block { size, ..*self };
+        self.ptr = unsafe { self.ptr.add(size) };
+        self.size -= size;
+
+        front
+    }
+}
+
+/// The locked memory most secrets are stored in. Chunks of `CHUNK_SIZE` bytes are allocated and locked once and
+/// secrets get a block of the smallest power of two they fit into. Freed blocks are wiped and reused for other secrets,
+/// the chunks themselves are kept until the app exits.
+#[derive(Default)]
+struct Arena {
+    /// Wiped blocks that can be reused, one list per block size
+    free: [Vec<Block>; BLOCK_SIZES],
+    /// The part of the newest chunk no block has been taken from yet
+    rest: Option<Block>,
+}
+
+// The blocks are only handed out to a single secret at a time
+unsafe impl Send for Arena {}
+
+impl Arena {
+    /// # Returns
+    ///
+    /// The index of the free list of the given block size
+    fn index(size: usize) -> usize {
+        (size / MIN_BLOCK_SIZE).ilog2() as usize
+    }
+
+    /// # Arguments
+    ///
+    /// * `size` - The block size, has to be a power of two between `MIN_BLOCK_SIZE` and `CHUNK_SIZE`
+    ///
+    /// # Returns
+    ///
+    /// A zeroed block of the given size
+    fn alloc(&mut self, size: usize) -> Block {
+        if let Some(block) = self.free[Self::index(size)].pop() {
+            return block;
+        }
+
+        let rest = match self.rest.take() {
+            Some(rest) if rest.size >= size => rest,
+            old => {
+                if let Some(old) = old {
+                    self.recycle(old);
+                }
+
+                Block::alloc_locked(CHUNK_SIZE)
+            }
+        };
+
+        let rest = self.rest.insert(rest);
+        rest.split_off(size)
+    }
+
+    /// Wipes the block, so it can be used for other secrets
+    fn free(&mut self, mut block: Block) {
+        block.memory().zeroize();
+        self.free[Self::index(block.size)].push(block);
+    }
+
+    /// Splits what is left of a chunk into blocks, so the memory is not wasted
+    fn recycle(&mut self, mut rest: Block) {
+        while rest.size >= MIN_BLOCK_SIZE {
+            let size = 1 << rest.size.ilog2();
+            let block = rest.split_off(size);
+            self.free[Self::index(size)].push(block);
+        }
+    }
+}
+
+/// A fixed size buffer for secrets. Its memory is locked, so it is never swapped to disk
+/// and excluded from core dumps where the platform supports this. The buffer is wiped when dropped.
+/// Small secrets share the chunks of a locked arena, larger ones get their own pages.
+pub struct SecretBytes {
+    block: Block,
+    len: usize,
+}
+
+// The buffer is owned by this struct and never shared, just like a Box<[u8]>
+unsafe impl Send for SecretBytes {}
+unsafe impl Sync for SecretBytes {}
+
+impl SecretBytes {
+    /// Copies the given data into a new locked buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The secret that should be copied. The caller still has to wipe this one.
+    ///
+    /// # Returns
+    ///
+    /// The buffer containing the secret
+    pub fn from_slice(data: &[u8]) -> Self {
+        let size = data.len().max(MIN_BLOCK_SIZE).next_power_of_two();
+        let block = if size <= CHUNK_SIZE {
+            ARENA.lock().unwrap_or_else(PoisonError::into_inner).alloc(size)
+        } else {
+            Block::alloc_locked(data.len())
+        };
+
+        unsafe { block.ptr.as_ptr().copy_from_nonoverlapping(data.as_ptr(), data.len()) };
+        Self { block, len: data.len() }
+    }
+
+    /// # Returns
+    ///
+    /// The secret itself
+    pub fn expose(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.block.ptr.as_ptr(), self.len) }
+    }
+
+    /// # Returns
+    ///
+    /// Whether the memory of this secret could be locked
+    pub fn is_locked(&self) -> bool {
+        self.block.locked
+    }
+}
+
+/// Moves the vec into a locked buffer and wipes the vec afterwards
+impl From<Vec<u8>> for SecretBytes {
+    fn from(mut value: Vec<u8>) -> Self {
+        let secret = Self::from_slice(&value);
+        value.zeroize();
+
+        secret
+    }
+}
+
+impl Clone for SecretBytes {
+    fn clone(&self) -> Self {
+        Self::from_slice(self.expose())
+    }
+}
+
+/// Wipes the whole buffer. The secret is empty afterwards.
+impl Zeroize for SecretBytes {
+    fn zeroize(&mut self) {
+        self.block.memory().zeroize();
+        self.len = 0;
+    }
+}
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        if self.block.size <= CHUNK_SIZE {
+            ARENA.lock().unwrap_or_else(PoisonError::into_inner).free(self.block);
+        } else {
+            self.zeroize();
+            self.block.free_locked();
+        }
+    }
+}
+impl ZeroizeOnDrop for SecretBytes {}
+
+/// Never prints the secret itself
+impl Debug for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretBytes([REDACTED; {}])", self.len)
+    }
+}
+
+/// A string that is stored in a `SecretBytes` buffer, used for decrypted messages for example
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct SecretString(SecretBytes);
+
+impl SecretString {
+    /// # Returns
+    ///
+    /// The secret itself
+    pub fn expose(&self) -> &str {
+        // Only valid utf8 is ever copied into the buffer and a zeroized buffer is empty
+        unsafe { std::str::from_utf8_unchecked(self.0.expose()) }
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(value: &str) -> Self {
+        Self(SecretBytes::from_slice(value.as_bytes()))
+    }
+}
+
+/// Moves the string into a locked buffer and wipes the string afterwards
+impl From<String> for SecretString {
+    fn from(mut value: String) -> Self {
+        let secret = Self::from(value.as_str());
+        value.zeroize();
+
+        secret
+    }
+}
+
+impl Deref for SecretString {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.expose()
+    }
+}
+
+impl PartialEq for SecretString {
+    fn eq(&self, other: &Self) -> bool {
+        self.expose() == other.expose()
+    }
+}
+
+impl PartialEq<str> for SecretString {
+    fn eq(&self, other: &str) -> bool {
+        self.expose() == other
+    }
+}
+
+impl PartialEq<&str> for SecretString {
+    fn eq(&self, other: &&str) -> bool {
+        self.expose() == *other
+    }
+}
+
+/// Never prints the secret itself
+impl Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretString([REDACTED; {}])", self.0.len)
+    }
+}
+
+/// Serializes the secret as a normal string
+impl Serialize for SecretString {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.expose())
+    }
+}
+
+/// Copies borrowed strings directly into the locked buffer, owned strings are wiped after copying
+impl<'a> Deserialize<'a> for SecretString {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'a>,
+    {
+        struct SecretVisitor;
+
+        impl de::Visitor<'_> for SecretVisitor {
+            type Value = SecretString;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a string")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(SecretString::from(v))
+            }
+
+            fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+                Ok(SecretString::from(v))
+            }
+        }
+
+        deserializer.deserialize_string(SecretVisitor)
+    }
+}
@@ -38,3 +38,83 @@ fn serialize() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn key_pool_refills_in_background() -> Result<()> {
+    use std::time::Duration;
+
+    use crate::{pooled_keys, KEY_POOL_SIZE};
+
+    // The pool is empty at first, so this key is generated on demand
+    let key = PrivateKey::generate_pair_async().await?;
+    let public: PublicKey = key.clone().try_into()?;
+    assert_eq!(key.decrypt(&public.encrypt(b"pooled")?)?, b"pooled");
+
+    tokio::time::timeout(Duration::from_secs(120), async {
+        while pooled_keys() < *KEY_POOL_SIZE {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await?;
+
+    // Taking a key from the full pool triggers a refill again
+    PrivateKey::generate_pair_async().await?;
+    assert!(pooled_keys() < *KEY_POOL_SIZE);
+
+    Ok(())
+}
+
+#[test]
+fn cloned_keys_can_be_wiped_independently() -> Result<()> {
+    use zeroize::Zeroize;
+
+    let mut key = PrivateKey::generate_pair()?;
+    let public: PublicKey = key.clone().try_into()?;
+    let clone = key.clone();
+
+    key.zeroize();
+    assert!(key.rsa().is_err());
+
+    let encrypted = public.encrypt(b"still there")?;
+    assert_eq!(clone.decrypt(&encrypted)?, b"still there");
+    Ok(())
+}
+
+#[test]
+fn secrets_reuse_wiped_blocks_of_the_arena() {
+    use crate::SecretBytes;
+
+    // No other test uses secrets of this size, so the freed block is the next one that is handed out
+    let secret = SecretBytes::from_slice(&[1; 20000]);
+    let ptr = secret.expose().as_ptr();
+    drop(secret);
+
+    let reused = SecretBytes::from_slice(&[2; 17000]);
+    assert_eq!(reused.expose().as_ptr(), ptr);
+    assert!(reused.expose().iter().all(|b| *b == 2));
+
+    // Secrets larger than a chunk get their own pages
+    let large = SecretBytes::from_slice(&vec![3; 100_000]);
+    assert_eq!(large.expose().len(), 100_000);
+    assert!(large.expose().iter().all(|b| *b == 3));
+}
+
+#[test]
+fn secret_strings_are_redacted_and_wiped() -> Result<()> {
+    use zeroize::Zeroize;
+
+    use crate::SecretString;
+
+    let json = serde_json::to_string("A \"quoted\" secret")?;
+    let mut secret: SecretString = serde_json::from_str(&json)?;
+    assert_eq!(secret, "A \"quoted\" secret");
+    assert_eq!(serde_json::to_string(&secret)?, json);
+    assert!(!format!("{:?}", secret).contains("secret\""));
+
+    let clone = secret.clone();
+    secret.zeroize();
+    assert!(secret.is_empty());
+    assert_eq!(clone, "A \"quoted\" secret");
+
+    Ok(())
+}
@@ -1,9 +1,5 @@
 use std::{
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
-    },
-    thread::{self, sleep, JoinHandle},
+    sync::Arc,
     time::{Duration, Instant},
 };
 
@@ -11,8 +7,14 @@ use anyhow::Result;
 use futures_util::SinkExt;
 use lazy_static::lazy_static;
 use log::error;
-use smol::block_on;
-use tokio::sync::{Mutex, RwLock};
+use tokio::{
+    sync::{Mutex, RwLock},
+    task::JoinHandle,
+    time::sleep,
+};
+use tokio_util::sync::CancellationToken;
+use crate::general::QueuedPacket;
+
 use super::WriteStream;
 
 lazy_static! {
@@ -26,89 +28,98 @@ lazy_static! {
 pub(super) struct FlushChecker {
     /// The last time we flushed the websocket
     pub(super) last_update: Arc<RwLock<Instant>>,
-    /// whether the current thread should exit
-    pub(super) should_exit: Arc<AtomicBool>,
 
     #[allow(dead_code)]
     /// This stream is just used to flush the websocket.
-    /// This stream is given to other threads (of which the handle is) so actually we *wouldn't* need it,
+    /// This stream is given to the task (of which the handle is) so actually we *wouldn't* need it,
     /// but keeping it for now
     write: Arc<Mutex<WriteStream>>,
     #[allow(dead_code)]
-    /// The thread handle of the flush checker thread.
+    /// The handle of the flush checker task.
     handle: JoinHandle<()>,
 }
 
 impl FlushChecker {
-    /// Spawns the new thread which will check if the websocket should be flushed
+    /// Spawns the new task which will check if the websocket should be flushed
     /// and returns its handle.
     ///
     /// # Arguments
     ///
-    /// * `receiver` - The onion hostname of the receiver
     /// * `last_update` - The last time the websocket was flushed or a message was sent
-    /// * `should_exit` - Whether the thread should exit
-    /// * `write` - The write stream of the websocket 
+    /// * `cancel` - Stops the task once cancelled
+    /// * `write` - The write stream of the websocket
+    /// * `pending` - The packets that have been fed but not flushed, cleared after every flush
     ///
     /// # Returns
     ///
     /// The handle that has been spawned
-    pub async fn spawn_handle(
-        receiver: &str,
+    fn spawn_handle(
         last_update: Arc<RwLock<Instant>>,
-        should_exit: Arc<AtomicBool>,
+        cancel: CancellationToken,
         write: Arc<Mutex<WriteStream>>,
+        pending: Arc<Mutex<Vec<QueuedPacket>>>,
     ) -> JoinHandle<()> {
-        thread::Builder::new().name(format!("flush-checker-{}", receiver)).spawn(move || {
-            while !should_exit.load(Ordering::Relaxed) {
+        tokio::spawn(async move {
+            loop {
                 // Checking if we should flush and adding 5ms to make sure we don't miss the flush
                 let to_wait = FLUSH_DELAY
-                    .checked_sub(block_on(last_update.read()).elapsed())
+                    .checked_sub(last_update.read().await.elapsed())
                     .unwrap_or(Duration::from_secs(0))
                     + Duration::from_millis(5);
-                sleep(to_wait);
+
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = sleep(to_wait) => {}
+                }
 
                 // Again checking if we should flush, if None we should flush now
-                let to_wait = FLUSH_DELAY.checked_sub(block_on(last_update.read()).elapsed());
+                let to_wait = FLUSH_DELAY.checked_sub(last_update.read().await.elapsed());
                 if to_wait.is_some() {
                     continue;
                 }
 
                 // Actually flushing the websocket
-                let res = block_on(block_on(write.lock()).flush());
+                let mut stream = write.lock().await;
+                let res = stream.flush().await;
+                if res.is_ok() {
+                    pending.lock().await.clear();
+                }
+                drop(stream);
 
                 // Setting the last time the websocket was flushed to now
-                *block_on(last_update.write()) = Instant::now();
+                *last_update.write().await = Instant::now();
 
                 if let Err(e) = res {
                     error!("[CLIENT] Could not flush: {:?}", e);
                 }
             }
-        }).unwrap()
+        })
     }
 
-    /// Constructs this struct and spawns the actual thread to check for  new flushes. Represented in the handle field.
+    /// Constructs this struct and spawns the actual task to check for new flushes. Represented in the handle field.
     ///
     /// # Arguments
     ///
-    /// * `receiver` - The onion hostname of the receiver
     /// * `write` - The write stream of the websocket
+    /// * `pending` - The packets that have been fed but not flushed yet
+    /// * `cancel` - Stops the task once cancelled
     ///
     /// # Returns
     /// The constructed flush checker
-    /// 
-    pub async fn new(receiver: &str, write: Arc<Mutex<WriteStream>>) -> Result<Self> {
+    ///
+    pub fn new(
+        write: Arc<Mutex<WriteStream>>,
+        pending: Arc<Mutex<Vec<QueuedPacket>>>,
+        cancel: CancellationToken,
+    ) -> Result<Self> {
         let last_update = Instant::now() - *FLUSH_DELAY - Duration::from_secs(1);
         let last_update = Arc::new(RwLock::new(last_update));
-        let should_exit = Arc::new(AtomicBool::new(false));
 
         // Spawns the handle of this struct
-        let handle =
-            Self::spawn_handle(receiver, last_update.clone(), should_exit.clone(), write.clone()).await;
+        let handle = Self::spawn_handle(last_update.clone(), cancel, write.clone(), pending);
         let s = Self {
             // Just to make sure we flush on the first message
             last_update,
-            should_exit,
             handle,
             write,
         };
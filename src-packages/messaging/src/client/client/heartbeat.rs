@@ -1,8 +1,8 @@
-use std::{time::{Instant, Duration}, thread, sync::Arc};
+use std::sync::Arc;
 
 use futures_util::SinkExt;
 use log::{warn, debug};
-use smol::block_on;
+use tokio::time::{interval, MissedTickBehavior};
 use tokio_tungstenite::tungstenite::Message;
 
 use crate::general::HEARTBEAT;
@@ -11,16 +11,17 @@ use super::MessagingClient;
 
 /// Used to send a ping to the server every HEARTBEAT ms.
 pub(super) trait HeartbeatClient {
-    /// Spawns the heartbeat thread to send a ping to the server every HEARTBEAT Duration.
-    fn spawn_heartbeat_thread(&mut self);
+    /// Spawns the heartbeat task to send a ping to the server every HEARTBEAT Duration.
+    /// Stops as soon as the client is cancelled
+    fn spawn_heartbeat_task(&mut self);
 }
 
 impl HeartbeatClient for MessagingClient {
-    fn spawn_heartbeat_thread(&mut self) {
-        // We don't want to spawn multiple heartbeat threads
-        if self.heartbeat_thread.is_some() {
+    fn spawn_heartbeat_task(&mut self) {
+        // We don't want to spawn multiple heartbeat tasks
+        if self.heartbeat_task.is_some() {
             warn!(
-                "[CLIENT] Could not spawn heartbeat thread, already exists ({:?})",
+                "[CLIENT] Could not spawn heartbeat task, already exists ({:?})",
                 self
             );
             return;
@@ -28,39 +29,34 @@ impl HeartbeatClient for MessagingClient {
 
         // The mutex which will be used to send the ping
         let sender = self.write.clone();
-        let handle = thread::Builder::new().name(format!("heartbeat-{}", self.receiver)).spawn(move || loop {
-            // Measuring the time it took to send the ping, marking the beginning here
-            let before = Instant::now();
-
-            let mut write = block_on(sender.lock());
+        let cancel = self.cancel.clone();
+        let handle = tokio::spawn(async move {
+            let mut ticker = interval(*HEARTBEAT);
+            // A slow ping should not be followed by a burst of pings
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = ticker.tick() => {}
+                }
 
-            // Feeding the ping to the queue of the websocket
-            let temp = write.feed(Message::Ping(vec![]));
-            let res = block_on(temp);
-            drop(write);
+                // Feeding the ping to the queue of the websocket
+                let res = sender.lock().await.feed(Message::Ping(vec![])).await;
+                if let Err(e) = res {
+                    let err_msg = format!("{:?}", e);
+                    if err_msg.contains("AlreadyClosed") {
+                        break;
+                    }
 
-            if let Err(e) = res {
-                let err_msg = format!("{:?}", e);
-                if err_msg.contains("AlreadyClosed") {
-                    debug!("[CLIENT] Closing heartbeat thread...");
-                    break;
+                    warn!("[CLIENT] Could not send heartbeat: {:?}", e);
                 }
-
-                warn!("[CLIENT] Could not send heartbeat: {:?}", e);
             }
 
-            // The total time it took to send the ping
-            let duration = before.elapsed();
+            debug!("[CLIENT] Closing heartbeat task...");
+        });
 
-            // Waiting for the remaining time
-            let diff = HEARTBEAT.checked_sub(duration);
-            let diff = diff.unwrap_or(Duration::new(0, 0));
-
-            // Sleeping the remaining time
-            thread::sleep(diff)
-        }).unwrap();
-
-        // Setting the heartbeat thread the current thread
-        self.heartbeat_thread = Arc::new(Some(handle));
+        // Setting the heartbeat task of this client
+        self.heartbeat_task = Arc::new(Some(handle));
     }
-}
\ No newline at end of file
+}
@@ -6,31 +6,27 @@ use futures_util::{
 };
 use log::{debug, info, warn, error};
 use payloads::{
-    event::AppHandleExt,
+    event::emit_payload,
     packets::{C2SPacket, S2CPacket},
     payloads::{WsClientStatus, WsClientUpdatePayload, WsMessageStatus},
 };
-use shared::{get_app, name_struct, util::_get_name};
-use std::{
-    sync::{Arc, atomic::{AtomicBool, Ordering}},
-    thread::{self, JoinHandle}
-};
-use tauri::async_runtime::block_on;
-use tokio::{net::TcpStream, sync::Mutex};
-use tokio_socks::tcp::Socks5Stream;
+use shared::{name_struct, util::_get_name};
+use std::sync::Arc;
+use tokio::{sync::{Mutex, RwLock}, task::JoinHandle};
+use tokio_util::sync::CancellationToken;
 use tokio_tungstenite::{tungstenite::Message, WebSocketStream};
 use url::Url;
 
-use crate::{general::{IdentityProvider, IdentityVerify, MESSAGING}, client::{SocksProxy, manager_ext::ManagerExt, client::heartbeat::HeartbeatClient}};
+use crate::{general::{Handshake, QueuedPacket, Session, MESSAGING}, client::{manager_ext::ManagerExt, client::heartbeat::HeartbeatClient}, transport::{get_transport, TransportStream}};
 
 use super::flush::FlushChecker;
 
 /// The write stream of the websocket, just a wrapper
-pub(super) type WriteStream = SplitSink<WebSocketStream<Socks5Stream<TcpStream>>, Message>;
+pub(super) type WriteStream = SplitSink<WebSocketStream<TransportStream>, Message>;
 // The read stream of the websocket
-pub(super) type ReadStream = SplitStream<WebSocketStream<Socks5Stream<TcpStream>>>;
+pub(super) type ReadStream = SplitStream<WebSocketStream<TransportStream>>;
 
-/// The main websocket client used to communicate with the server over the tor network
+/// The main websocket client used to communicate with the server over the current transport (tor by default)
 #[derive(Debug)]
 pub struct MessagingClient {
     /// The stream to send messages to the server
@@ -38,10 +34,18 @@ pub struct MessagingClient {
 
     /// The address the client is connected to
     pub(super) receiver: String,
-    /// The current heartbeat thread handle
-    pub(super) heartbeat_thread: Arc<Option<JoinHandle<()>>>,
-    /// The thread used to read messages from the server
-    read_thread: Arc<Option<JoinHandle<()>>>,
+    /// The current heartbeat task handle
+    pub(super) heartbeat_task: Arc<Option<JoinHandle<()>>>,
+    /// The task used to read messages from the server
+    read_task: Arc<Option<JoinHandle<()>>>,
+    /// Stops the heartbeat and flush tasks, cancelled as soon as the server disconnects
+    pub(super) cancel: CancellationToken,
+    /// The packets that have been fed to the websocket but not flushed yet
+    pending: Arc<Mutex<Vec<QueuedPacket>>>,
+    /// Our side of the handshake with the server
+    handshake: Arc<Mutex<Handshake>>,
+    /// The key of this session, every packet after the handshake is authenticated with it
+    session: Arc<RwLock<Option<Session>>>,
 
     /// A receiver used in the common messaging manager to receive general messages
     pub rx: Receiver<S2CPacket>,
@@ -62,17 +66,16 @@ impl MessagingClient {
 /// 
 pub async fn new(onion_hostname: &str) -> Result<Self> {
     // Sending the status update to the frontend (So the user knows what's going on)
-    let _ = get_app()
-        .await
-        .emit_payload(WsClientUpdatePayload {
-            hostname: onion_hostname.to_string(),
-            status: WsClientStatus::ConnectingProxy,
-        })
-        .map_err(|e| warn!("[CLIENT] Could not emit ws client update: {:?}", e));
-
-    debug!("[CLIENT] Creating verify packet...");
-    // Creating a verify packet to send to the client
-    let verify_packet = C2SPacket::identity(onion_hostname).await?;
+    let _ = emit_payload(WsClientUpdatePayload {
+        hostname: onion_hostname.to_string(),
+        status: WsClientStatus::ConnectingProxy,
+    })
+    .map_err(|e| warn!("[CLIENT] Could not emit ws client update: {:?}", e));
+
+    debug!("[CLIENT] Creating hello packet...");
+    // Starting the handshake, the server answers with its own hello
+    let handshake = Handshake::new(onion_hostname).await?;
+    let hello_packet = C2SPacket::Hello(handshake.hello());
 
     // For developing purposes, one could send himself messages, obviously disabling that for production
     #[cfg(not(feature = "dev"))]
@@ -85,29 +88,23 @@ pub async fn new(onion_hostname: &str) -> Result<Self> {
     // The address which is used to connect to the websocket
     let onion_addr = format!("ws://{}.onion/ws/", connect_host);
 
-    debug!("[CLIENT] Creating proxy...");
-
-    // Creating the Socks5Proxy client which is used to connect to the tor network
-    let proxy = SocksProxy::new()?;
     debug!("[CLIENT] Connecting Proxy...");
     let mut onion_addr = Url::parse(&onion_addr)?;
     onion_addr
         .set_scheme("ws")
         .or(Err(anyhow!("[CLIENT] Could not set scheme")))?;
 
-    // Connecting to the destination host using the proxyy
-    let sock = proxy.connect(&onion_addr).await?;
+    // Connecting to the destination host using the current transport
+    let sock = get_transport().dial(onion_hostname, &onion_addr).await?;
 
     debug!("[CLIENT] Connecting Tungstenite...");
 
     // And notifying the front end again about our progress
-    let _ = get_app()
-        .await
-        .emit_payload(WsClientUpdatePayload {
-            hostname: onion_hostname.to_string(),
-            status: WsClientStatus::ConnectingHost,
-        })
-        .map_err(|e| warn!("[CLIENT] Could not emit ws client update: {:?}", e));
+    let _ = emit_payload(WsClientUpdatePayload {
+        hostname: onion_hostname.to_string(),
+        status: WsClientStatus::ConnectingHost,
+    })
+    .map_err(|e| warn!("[CLIENT] Could not emit ws client update: {:?}", e));
 
 
     // Connecting to the websocket with the client
@@ -118,44 +115,47 @@ pub async fn new(onion_hostname: &str) -> Result<Self> {
     let (mut write, read) = ws_stream.split();
 
     // Sending the status update to the frontend, again
-    let _ = get_app()
-        .await
-        .emit_payload(WsClientUpdatePayload {
-            hostname: onion_hostname.to_string(),
-            status: WsClientStatus::WaitingIdentity,
-        })
-        .map_err(|e| warn!("[CLIENT] Could not emit ws client update: {:?}", e));
-
-    debug!("[CLIENT] Sending verify packet");
-    // And actually sending the verify packet
-    write.send(verify_packet.try_into()?).await?;
+    let _ = emit_payload(WsClientUpdatePayload {
+        hostname: onion_hostname.to_string(),
+        status: WsClientStatus::WaitingIdentity,
+    })
+    .map_err(|e| warn!("[CLIENT] Could not emit ws client update: {:?}", e));
+
+    debug!("[CLIENT] Sending hello packet");
+    // And actually sending the hello packet
+    write.send(hello_packet.try_into()?).await?;
 
     // Establishing the channel used to communicate with the common messaging manager
     let (tx, rx) = async_channel::unbounded();
 
     let arc_write = Arc::new(Mutex::new(write));
 
-    // Spawning the new flush thread
-    let checker = FlushChecker::new(onion_hostname, arc_write.clone()).await?;
-    let flusher_exit = checker.should_exit.clone();
+    // Spawning the new flush task
+    let cancel = CancellationToken::new();
+    let pending = Arc::new(Mutex::new(Vec::new()));
+    let checker = FlushChecker::new(arc_write.clone(), pending.clone(), cancel.clone())?;
 
     // Constructing the client as the connection was successful
     let mut c = Self {
         write: arc_write.clone(),
-        heartbeat_thread: Arc::new(None),
+        heartbeat_task: Arc::new(None),
         receiver: onion_hostname.to_string(),
 
         rx,
-        read_thread: Arc::new(None),
+        read_task: Arc::new(None),
+        cancel,
+        pending,
+        handshake: Arc::new(Mutex::new(handshake)),
+        session: Arc::new(RwLock::new(None)),
         flush_checker: checker
     };
 
-    debug!("[CLIENT] Spawning heartbeat thread");
-    // Spawning the heartbeat thread
-    c.spawn_heartbeat_thread();
+    debug!("[CLIENT] Spawning heartbeat task");
+    // Spawning the heartbeat task
+    c.spawn_heartbeat_task();
 
-    // Spawning the read thread
-    c.spawn_read_thread(tx, (read, arc_write), flusher_exit);
+    // Spawning the read task
+    c.spawn_read_task(tx, (read, arc_write));
 
     Ok(c)
 }
@@ -173,42 +173,102 @@ pub async fn new(onion_hostname: &str) -> Result<Self> {
         debug!("[CLIENT] Locking write mutex...");
         let mut state = self.write.lock().await;
         debug!("[CLIENT] Feeding packet {:?}...", name_struct!(msg));
+        let queued = QueuedPacket::from_c2s(&msg);
+        let msg = Self::seal(&self.session, msg).await?;
         state.feed(msg.try_into()?).await?;
+
+        // The write stream is still locked, so the flush task can't clear the pending packets in between
+        if let Some(queued) = queued {
+            self.pending.lock().await.push(queued);
+        }
         self.flush_checker.mark_dirty().await;
         debug!("[CLIENT] Done feeding packet.");
 
         Ok(())
     }
 
-    /// Spawns a thread to read incoming packets from the server.
+    /// Authenticates the given packet with the session key, packets of the handshake are sent as they are
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The session of the client
+    /// * `packet` - The packet to send
+    ///
+    /// # Returns
+    ///
+    /// The packet to send, fails if the handshake is not done yet
+    async fn seal(session: &RwLock<Option<Session>>, packet: C2SPacket) -> Result<C2SPacket> {
+        if packet.is_handshake() {
+            return Ok(packet);
+        }
+
+        session
+            .read()
+            .await
+            .as_ref()
+            .ok_or(anyhow!("[CLIENT] Can't send {:?} before the handshake", name_struct!(packet)))?
+            .seal_c2s(packet)
+    }
+
+    /// Takes every packet that has been fed but not flushed yet, so it can be sent over another connection
+    ///
+    /// # Returns
+    ///
+    /// The taken packets, in the order they have been fed
+    pub(crate) async fn take_queued(&self) -> Vec<QueuedPacket> {
+        // Locking the write stream first, so nothing is flushed or fed meanwhile
+        let _write = self.write.lock().await;
+        std::mem::take(&mut *self.pending.lock().await)
+    }
+
+    /// # Returns
+    ///
+    /// Whether the websocket has been closed
+    pub fn is_closed(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// Closes the websocket and stops the heartbeat and flush tasks
+    pub(crate) async fn shutdown(&self) -> Result<()> {
+        self.cancel.cancel();
+        self.write.lock().await.close().await?;
+
+        Ok(())
+    }
+
+    /// Spawns a task to read incoming packets from the server.
     /// Handles other misc packages and sends messages to the common messaging manager.
-    /// Sets the handle to the thread in the read_thread field.
+    /// Sets the handle to the task in the read_task field.
+    /// Cancels the other tasks of this client once the server disconnects.
     ///
     /// # Arguments
     ///
     /// * `tx` - The sender used to send messages to the common messaging manager (and then to the server)
     /// * `(tuple)` - Contains read and write streams about the self hosted server to send messages between
-    /// * `flush_exit` - Whether the flush checker should exit
     ///
-    fn spawn_read_thread(
+    fn spawn_read_task(
         &mut self,
         tx: Sender<S2CPacket>,
         (receiver, write): (ReadStream, Arc<Mutex<WriteStream>>),
-        flush_exit: Arc<AtomicBool>
     ) {
-        // Don't spawn a new thread if one already exists
-        if self.read_thread.is_some() {
-            warn!("[CLIENT] Could not thread read thread, already exists ({:?})", self);
+        // Don't spawn a new task if one already exists
+        if self.read_task.is_some() {
+            warn!("[CLIENT] Could not spawn read task, already exists ({:?})", self);
             return;
         }
 
         let tmp = self.receiver.clone();
-        let handle = thread::Builder::new().name(format!("read-{}", self.receiver)).spawn(move || {
+        let cancel = self.cancel.clone();
+        let handshake = self.handshake.clone();
+        let session = self.session.clone();
+        let handle = tokio::spawn(async move {
             // Handling the incoming packets concurrently (more performance)
             let future = receiver.for_each_concurrent(2, |msg| {
                 let receiver = tmp.clone();
                 let write = write.clone();
                 let tx = tx.clone();
+                let handshake = handshake.clone();
+                let session = session.clone();
 
                 // Whole async block is needed to be able to use async/await
                 async move {
@@ -239,7 +299,7 @@ pub async fn new(onion_hostname: &str) -> Result<Self> {
                     // The deserialized packet
                     let packet = packet.unwrap();
                     // And handle the packet
-                    let res = Self::handle_packet(packet, &receiver, write, tx).await;
+                    let res = Self::handle_packet(packet, &receiver, write, tx, (handshake, session)).await;
                     if let Err(e) = res {
                         warn!("[CLIENT] Could not handle packet: {:?}", e);
                         return;
@@ -248,17 +308,16 @@ pub async fn new(onion_hostname: &str) -> Result<Self> {
             });
 
             // Waiting for the stream to end
-            block_on(future);
+            future.await;
 
-            // And closing everything in this thread
+            // And closing everything of this client
             info!("[CLIENT] Client disconnected for {}", tmp);
 
-            flush_exit.store(true, Ordering::Relaxed);
-            let f = block_on(MESSAGING.read());
-            block_on(f.remove_connection(&tmp));
-        }).unwrap();
+            // The read task of the connection notices the closed channel and removes it
+            cancel.cancel();
+        });
 
-        self.read_thread = Arc::new(Some(handle));
+        self.read_task = Arc::new(Some(handle));
     }
 
     /// Handles the given packet with additional variables (such as receiver, write stream, etc.)
@@ -270,6 +329,7 @@ pub async fn new(onion_hostname: &str) -> Result<Self> {
     /// * `receiver` - The receiver onion hostname
     /// * `write` - A stream to send packets to the server
     /// * `tx` - A sender to send packets to the common messaging manager
+    /// * `(tuple)` - Our side of the handshake and the session key, once derived
     ///
     /// # Returns
     /// A result if it was successful
@@ -279,15 +339,31 @@ pub async fn new(onion_hostname: &str) -> Result<Self> {
         receiver: &str,
         write: Arc<Mutex<WriteStream>>,
         tx: Sender<S2CPacket>,
+        (handshake, session): (Arc<Mutex<Handshake>>, Arc<RwLock<Option<Session>>>),
     ) -> Result<()> {
-        // If is Some, we have a packet to which verification is needed
-        let mut process_further = None;
-        match packet {
+        let packet = match packet {
+            // The server answered our hello, so we can derive the session and prove our identity
+            S2CPacket::Hello(hello) => {
+                let mut handshake = handshake.lock().await;
+                handshake.set_remote(hello)?;
+
+                let identity = handshake.identity().await?;
+                *session.write().await = Some(handshake.session()?);
+
+                debug!("[CLIENT] Sending identity...");
+                write
+                    .lock()
+                    .await
+                    .send(C2SPacket::SetIdentity(identity).try_into()?)
+                    .await?;
+
+                return Ok(());
+            }
             // The server sent us a message to verify its identity, so we check and send the IdentityVerified packet back
             S2CPacket::VerifyIdentity(identity) => {
                 info!("[CLIENT] Verifying identity for {:?}...", identity);
                 // Verifying the identity
-                identity.verify().await?;
+                handshake.lock().await.verify(&identity).await?;
 
                 debug!("[CLIENT] Identity verified! Locking messaging...");
 
@@ -299,14 +375,28 @@ pub async fn new(onion_hostname: &str) -> Result<Self> {
                 mgr.check_verified(receiver).await?;
 
                 debug!("[CLIENT] Sending IdentityVerified packet...");
-                write
-                    .lock()
-                    .await
-                    .send(C2SPacket::IdentityVerified.try_into()?)
-                    .await?;
+                // Sealing while the stream is locked, so the packets are sent in the order of their sequence numbers
+                let mut write = write.lock().await;
+                let verified = Self::seal(&session, C2SPacket::IdentityVerified).await?;
+                write.send(verified.try_into()?).await?;
 
-                debug!("[CLIENT] Done sending IdentityVerified packet.")
+                debug!("[CLIENT] Done sending IdentityVerified packet.");
+                return Ok(());
             }
+            S2CPacket::Authenticated(p) => session
+                .read()
+                .await
+                .as_ref()
+                .ok_or(anyhow!("[CLIENT] Got an authenticated packet before the handshake"))?
+                .open_s2c(&p)?,
+            p => return Err(anyhow!("[CLIENT] Unauthenticated packet {:?}", name_struct!(p))),
+        };
+
+        // If is Some, we have a packet to which verification is needed
+        let mut process_further = None;
+        match packet {
+            // Just cover traffic
+            S2CPacket::Cover(_) => {}
             S2CPacket::IdentityVerified => {
                 info!("[CLIENT] Got myself verified!");
 
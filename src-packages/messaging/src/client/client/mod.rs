@@ -5,4 +5,4 @@ mod flush;
 /// This module contains the code to send a heartbeat every x seconds, so connection won't get interrupted.
 pub(super) mod heartbeat;
 
-pub use index::*;
\ No newline at end of file
+pub use index::*;
@@ -13,5 +13,6 @@ pub use client::MessagingClient;
 pub use tls::*;
 
 lazy_static! {
-    pub static ref TOR_CLIENT: WebClient = WebClient::from_config().unwrap();
+    /// The client used for general requests of the app (e.g. checking the tor connection)
+    pub static ref TOR_CLIENT: WebClient = WebClient::from_config("general").unwrap();
 }
\ No newline at end of file
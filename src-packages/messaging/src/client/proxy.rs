@@ -1,6 +1,8 @@
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 
+use lazy_static::lazy_static;
 use log::info;
+use openssl::{hash::{hash, MessageDigest}, rand::rand_bytes};
 use shared::config::CONFIG;
 use tokio::net::TcpStream;
 use tokio_socks::tcp::Socks5Stream;
@@ -8,6 +10,15 @@ use url::Url;
 
 use crate::client::util::get_server_name;
 
+lazy_static! {
+    /// Random for every run, so the socks credentials can't be linked to a contact (e.g. in the tor logs)
+    static ref ISOLATION_SALT: [u8; 16] = {
+        let mut salt = [0u8; 16];
+        rand_bytes(&mut salt).expect("Could not generate isolation salt");
+        salt
+    };
+}
+
 /// A socks proxy which is used to connect to the tor network
 #[derive(Debug)]
 pub struct SocksProxy {
@@ -18,36 +29,59 @@ pub struct SocksProxy {
 }
 
 impl SocksProxy {
-    /// Creates a new socks proxy from the config
+    /// Creates a proxy whose connections only share circuits with other connections to the same contact.
+    /// Tor isolates streams by their socks credentials (`IsolateSOCKSAuth`)
+    ///
+    /// # Arguments
+    ///
+    /// * `onion_hostname` - The contact the connections of this proxy are made to
     ///
     /// # Returns
-    /// 
+    ///
     /// A new socks proxy from the config
-    pub fn new() -> Result<Self> {
-        let addr = format!("socks5://127.0.0.1:{}", CONFIG.socks_port());
+    pub fn for_peer(onion_hostname: &str) -> Result<Self> {
+        Self::isolated("peer", onion_hostname)
+    }
 
-        // Parses the url and checks if the scheme is socks5
+    /// Creates a proxy whose connections only share circuits with other connections of the same purpose
+    ///
+    /// # Arguments
+    ///
+    /// * `purpose` - What the connections of this proxy are used for (e.g. `tor-check`)
+    ///
+    /// # Returns
+    ///
+    /// A new socks proxy from the config
+    pub fn for_purpose(purpose: &str) -> Result<Self> {
+        Self::isolated("purpose", purpose)
+    }
+
+    /// Creates a new socks proxy from the config with credentials unique to the given isolation key
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - The kind of isolation, used as username
+    /// * `key` - What the connections should be isolated by
+    ///
+    /// # Returns
+    ///
+    /// A new socks proxy from the config
+    fn isolated(kind: &str, key: &str) -> Result<Self> {
+        let addr = format!("socks5://127.0.0.1:{}", CONFIG.socks_port());
         let url = Url::parse(&addr)?;
-        let scheme = url.scheme();
-        if scheme != "socks5" {
-            return Err(anyhow!("Scheme is not 'socks5'"));
-        }
 
-        // Checks if the url has a username and password
-        let username = url.username();
-        let password = url.password();
+        // The key itself is not sent to tor, just a salted hash of it
+        let mut to_hash = ISOLATION_SALT.to_vec();
+        to_hash.extend_from_slice(kind.as_bytes());
+        to_hash.push(0);
+        to_hash.extend_from_slice(key.as_bytes());
 
-        // If the password is set, the username must be set too
-        let mut auth = None;
-        if let Some(passwd) = password {
-            if !username.is_empty() {
-                auth = Some((username.to_string(), passwd.to_string()));
-            }
-        }
+        let password = hex::encode(hash(MessageDigest::sha256(), &to_hash)?);
+        let username = format!("enkrypton-{}", kind);
 
         // Returns the proxy
         Ok(SocksProxy {
-            auth,
+            auth: Some((username, password)),
             proxy_url: url,
         })
     }
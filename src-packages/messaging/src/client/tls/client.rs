@@ -1,72 +1,118 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
-use anyhow::{anyhow, Result};
-use tokio::net::TcpStream;
+use log::debug;
+use tokio::io::BufReader;
 use tokio_rustls::{
-    client::TlsStream,
     rustls::{pki_types::ServerName, ClientConfig, RootCertStore},
     TlsConnector,
 };
-use tokio_socks::tcp::Socks5Stream;
 
 use url::Url;
 
 use crate::client::SocksProxy;
 
-use super::request::Request;
+use super::{
+    error::{HttpError, HttpResult},
+    http1::{read_body, read_head, write_request},
+    pool::{is_idle_open, ConnectionPool, PoolKey, PooledConnection},
+    request::Request,
+    response::Response,
+    stream::HttpStream,
+};
 
 const USER_AGENT: &str =
     "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:109.0) Gecko/20100101 Firefox/117.0";
 
+/// How long a request may take by default, circuits over tor can be slow to build
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+/// How many redirects are followed by default
+const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// Whether sending a request with this method twice has the same effect as sending it once.
+/// PUT is left out even though it is idempotent, as a repeated PUT could overwrite changes made in between
+fn is_idempotent(method: &str) -> bool {
+    matches!(method, "GET" | "HEAD" | "OPTIONS" | "TRACE" | "DELETE")
+}
+
 /// A web client which is used to send requests to the server over the tor network
 #[derive(Debug)]
 pub struct WebClient {
     /// The proxy used to connect to the tor network
     proxy: SocksProxy,
+    /// The tls config used for https connections
+    tls_config: Arc<ClientConfig>,
+    /// Connections that are kept open to be reused
+    pool: ConnectionPool,
 }
 
 impl WebClient {
-    /// Gets the underlying SocksProxy of this client
-    /// # Returns
+    /// Creates a new web client from the config with the default tor proxy port.
+    /// Clients with different purposes never share circuits
+    ///
+    /// # Arguments
+    ///
+    /// * `purpose` - What this client is used for, requests are isolated by it
     ///
-    /// The proxy used to connect to the tor network
-    pub(super) fn proxy(&self) -> &SocksProxy {
-        &self.proxy
-    }
-
-    /// Creates a new web client from the config with the default tor proxy port
     /// # Returns
     ///
     /// Creates a new web client from the config with the default tor proxy port
-    pub fn from_config() -> Result<Self> {
-        let tor_proxy = SocksProxy::new()?;
+    pub fn from_config(purpose: &str) -> anyhow::Result<Self> {
+        let tor_proxy = SocksProxy::for_purpose(purpose)?;
 
-        Ok(WebClient { proxy: tor_proxy })
+        Ok(WebClient {
+            proxy: tor_proxy,
+            tls_config: Arc::new(Self::get_tls_config()),
+            pool: ConnectionPool::default(),
+        })
     }
 
-    /// Just just opens a connection to the proxy, uses it and closes it after that.
-    /// Could be optimized but not doing that for literally one connection.
-    /// Oh also: blocking so use in threads thanks
+    /// Creates a request with the given method, can be sent with `Request::send`
     ///
     /// # Arguments
     ///
-    /// * `addr` - The url to send a get request to
+    /// * `method` - The HTTP method to use
+    /// * `addr` - The url to send the request to (http or https)
     ///
     /// # Returns
     ///
     /// The struct that can be used to send the request
-    pub fn get(&self, addr: &str) -> Request<'_> {
-        Request::from_client(self, "GET", addr)
+    pub fn request(&self, method: &str, addr: &str) -> Request<'_> {
+        Request::from_client(self, method, addr, DEFAULT_TIMEOUT, DEFAULT_MAX_REDIRECTS)
             .header("User-Agent", USER_AGENT)
             .header("Accept", "*/*")
     }
 
+    /// Creates a GET request to the given url
+    pub fn get(&self, addr: &str) -> Request<'_> {
+        self.request("GET", addr)
+    }
+
+    /// Creates a HEAD request to the given url
+    pub fn head(&self, addr: &str) -> Request<'_> {
+        self.request("HEAD", addr)
+    }
+
+    /// Creates a POST request to the given url, the body is set with `Request::body` or `Request::json`
+    pub fn post(&self, addr: &str) -> Request<'_> {
+        self.request("POST", addr)
+    }
+
+    /// Creates a PUT request to the given url, the body is set with `Request::body` or `Request::json`
+    pub fn put(&self, addr: &str) -> Request<'_> {
+        self.request("PUT", addr)
+    }
+
+    /// Creates a DELETE request to the given url
+    pub fn delete(&self, addr: &str) -> Request<'_> {
+        self.request("DELETE", addr)
+    }
+
     /// The tls config to use for the client
     ///
     /// # Returns
     ///
     /// The TLS Configuration
-    fn get_tls_config(&self) -> ClientConfig {
+    fn get_tls_config() -> ClientConfig {
         let mut root_cert_store = RootCertStore::empty();
         root_cert_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
 
@@ -76,33 +122,137 @@ impl WebClient {
     }
 
     //noinspection SpellCheckingInspection
-    /// Creates a connection to the given url using the proxy
+    /// Creates a connection to the given url using the proxy, wrapped in tls for https
     ///
     /// # Arguments
     ///
-    /// * `proxy` - Proxy to use when connecting
     /// * `url` - The url to connect to
     ///
     /// # Returns
     /// Returns the connection to the given url
     ///
-    pub(super) async fn create_connection(
-        &self,
-        proxy: Socks5Stream<TcpStream>,
-        url: &Url,
-    ) -> Result<TlsStream<Socks5Stream<TcpStream>>> {
-        let config = self.get_tls_config();
+    async fn open_stream(&self, url: &Url) -> HttpResult<HttpStream> {
+        let scheme = url.scheme();
+        if scheme != "http" && scheme != "https" {
+            return Err(HttpError::UnsupportedScheme(scheme.to_string()));
+        }
+
+        let proxy = self.proxy.connect(url).await.map_err(HttpError::Proxy)?;
+        if scheme == "http" {
+            return Ok(HttpStream::Plain(proxy));
+        }
 
-        let server_name_raw = url
-            .host_str()
-            .ok_or(anyhow!("Url has to have a host."))?
-            .to_string();
-        let server_name: ServerName = server_name_raw.try_into()?;
-        let connector = TlsConnector::try_from(Arc::new(config))?;
+        let server_name_raw = url.host_str().ok_or(HttpError::MissingHost)?.to_string();
+        let server_name: ServerName = server_name_raw
+            .clone()
+            .try_into()
+            .map_err(|_| HttpError::InvalidServerName(server_name_raw))?;
+        let connector = TlsConnector::from(self.tls_config.clone());
 
         // and connecting it to the proxy
         let stream = connector.connect(server_name, proxy).await?;
+        Ok(HttpStream::Tls(Box::new(stream)))
+    }
+
+    /// Sends a single request (without following redirects), reusing an open connection if possible
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - The HTTP method to use
+    /// * `url` - The url to send the request to
+    /// * `headers` - Additional headers to send
+    /// * `body` - The body to send, if any
+    ///
+    /// # Returns
+    ///
+    /// The response of the server
+    pub(super) async fn execute(
+        &self,
+        method: &str,
+        url: &Url,
+        headers: &HashMap<String, String>,
+        body: Option<&[u8]>,
+    ) -> HttpResult<Response> {
+        let key = PoolKey::from_url(url)?;
+
+        while let Some(mut conn) = self.pool.take(&key).await {
+            // Connections the server closed while they were idle are dropped before the request is written
+            if !is_idle_open(&mut conn) {
+                debug!("Dropping closed idle connection to {}", url);
+                continue;
+            }
+
+            match Self::exchange(&mut conn, method, url, headers, body).await {
+                Ok((resp, reusable)) => {
+                    if reusable {
+                        self.pool.put(key, conn).await;
+                    }
+
+                    return Ok(resp);
+                }
+                // The server may still close the connection while the request is sent. It could have handled the request already,
+                // so only requests which can safely be sent twice are tried again with a new connection
+                Err(HttpError::Io(e)) if is_idempotent(method) => {
+                    debug!("Reused connection to {} failed, reconnecting: {}", url, e);
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        let mut conn = BufReader::new(self.open_stream(url).await?);
+        let (resp, reusable) = Self::exchange(&mut conn, method, url, headers, body).await?;
+        if reusable {
+            self.pool.put(key, conn).await;
+        }
+
+        Ok(resp)
+    }
+
+    /// Writes the request to the connection and reads the whole response
+    ///
+    /// # Returns
+    ///
+    /// The response and whether the connection can be used for another request
+    async fn exchange(
+        conn: &mut PooledConnection,
+        method: &str,
+        url: &Url,
+        headers: &HashMap<String, String>,
+        body: Option<&[u8]>,
+    ) -> HttpResult<(Response, bool)> {
+        let host = url.host_str().ok_or(HttpError::MissingHost)?;
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_string(),
+        };
+
+        let mut target = url.path().to_string();
+        if target.is_empty() {
+            target = "/".to_string();
+        }
+        if let Some(query) = url.query() {
+            target = format!("{}?{}", target, query);
+        }
+
+        let mut all_headers = vec![
+            ("Host".to_string(), host),
+            ("Connection".to_string(), "keep-alive".to_string()),
+        ];
+
+        if body.is_some() || method == "POST" || method == "PUT" {
+            all_headers.push(("Content-Length".to_string(), body.unwrap_or_default().len().to_string()));
+        }
+
+        // Headers of the request replace the default ones
+        all_headers.retain(|(k, _)| !headers.keys().any(|e| e.eq_ignore_ascii_case(k)));
+        all_headers.extend(headers.iter().map(|(k, v)| (k.clone(), v.clone())));
+
+        write_request(conn.get_mut(), method, &target, &all_headers, body.unwrap_or_default()).await?;
+
+        let head = read_head(conn).await?;
+        let (body, reusable) = read_body(conn, &head, method == "HEAD").await?;
 
-        Ok(stream)
+        Ok((Response::new(url.clone(), head, body), reusable))
     }
 }
@@ -0,0 +1,64 @@
+use std::time::Duration;
+
+/// Includes all errors that can occur while sending a request with the web client
+#[derive(Debug, thiserror::Error)]
+pub enum HttpError {
+    InvalidUrl(url::ParseError),
+    UnsupportedScheme(String),
+    MissingHost,
+    InvalidServerName(String),
+
+    Proxy(anyhow::Error),
+    Io(std::io::Error),
+    Timeout(Duration),
+
+    InvalidResponse(String),
+    BodyTooLarge(usize),
+    TooManyRedirects(usize),
+    InsecureRedirect(url::Url),
+
+    Json(serde_json::Error),
+}
+
+/// The result of every operation of the web client
+pub type HttpResult<T> = std::result::Result<T, HttpError>;
+
+impl std::fmt::Display for HttpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidUrl(err) => write!(f, "Invalid url: {}", err),
+            Self::UnsupportedScheme(scheme) => write!(f, "Unsupported scheme '{}', only http and https are supported", scheme),
+            Self::MissingHost => write!(f, "Url has to have a host"),
+            Self::InvalidServerName(name) => write!(f, "Invalid server name for tls: {}", name),
+
+            Self::Proxy(err) => write!(f, "Could not connect over the socks proxy: {}", err),
+            Self::Io(err) => write!(f, "Connection error: {}", err),
+            Self::Timeout(after) => write!(f, "Request timed out after {:?}", after),
+
+            Self::InvalidResponse(reason) => write!(f, "Server sent an invalid response: {}", reason),
+            Self::BodyTooLarge(max) => write!(f, "Response body is larger than {} bytes", max),
+            Self::TooManyRedirects(max) => write!(f, "Stopped after following {} redirects", max),
+            Self::InsecureRedirect(to) => write!(f, "Refused to follow the redirect from https to {}", to),
+
+            Self::Json(err) => write!(f, "Could not parse json: {}", err),
+        }
+    }
+}
+
+impl From<url::ParseError> for HttpError {
+    fn from(err: url::ParseError) -> Self {
+        Self::InvalidUrl(err)
+    }
+}
+
+impl From<std::io::Error> for HttpError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for HttpError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Json(err)
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::error::{HttpError, HttpResult};
+
+/// The status line and headers together may not be larger than this
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+/// Response bodies may not be larger than this
+pub(super) const MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+/// The status line and headers of a response
+#[derive(Debug)]
+pub(super) struct ResponseHead {
+    pub status: u16,
+    pub reason: String,
+    /// Whether the server answered with HTTP/1.1 (HTTP/1.0 closes the connection by default)
+    pub http_11: bool,
+    /// The headers with lowercase names
+    pub headers: HashMap<String, String>,
+}
+
+impl ResponseHead {
+    /// Whether the connection may be used for further requests after this response
+    fn keep_alive(&self) -> bool {
+        let connection = self.headers.get("connection").map(|e| e.to_lowercase());
+        match connection.as_deref() {
+            Some("close") => false,
+            Some("keep-alive") => true,
+            _ => self.http_11,
+        }
+    }
+}
+
+/// Writes a request with the given headers and body to the stream
+pub(super) async fn write_request<W: AsyncWrite + Unpin>(
+    stream: &mut W,
+    method: &str,
+    target: &str,
+    headers: &[(String, String)],
+    body: &[u8],
+) -> HttpResult<()> {
+    let mut head = format!("{} {} HTTP/1.1\r\n", method, target);
+    for (key, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", key, value));
+    }
+    head.push_str("\r\n");
+
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body).await?;
+    stream.flush().await?;
+
+    Ok(())
+}
+
+/// Reads a single line without the trailing line break, counting it against `budget`
+async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut R, budget: &mut usize) -> HttpResult<String> {
+    let mut buf = Vec::new();
+    let read = (&mut *reader)
+        .take(*budget as u64 + 1)
+        .read_until(b'\n', &mut buf)
+        .await?;
+
+    if read == 0 {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+
+    if read > *budget {
+        return Err(HttpError::InvalidResponse("Response head is too large".to_string()));
+    }
+
+    *budget -= read;
+    let line = String::from_utf8_lossy(&buf);
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Reads the status line and headers of a response, skipping informational (1xx) responses
+pub(super) async fn read_head<R: AsyncBufRead + Unpin>(reader: &mut R) -> HttpResult<ResponseHead> {
+    let mut budget = MAX_HEAD_SIZE;
+    loop {
+        let status_line = read_line(reader, &mut budget).await?;
+        let mut split = status_line.splitn(3, ' ');
+
+        let protocol = split.next().unwrap_or_default();
+        if !protocol.starts_with("HTTP/1.") {
+            return Err(HttpError::InvalidResponse(format!("Invalid protocol '{}'", protocol)));
+        }
+
+        let status = split
+            .next()
+            .and_then(|e| e.parse::<u16>().ok())
+            .ok_or(HttpError::InvalidResponse(format!("Invalid status line '{}'", status_line)))?;
+        let reason = split.next().unwrap_or_default().to_string();
+
+        let mut headers = HashMap::new();
+        loop {
+            let line = read_line(reader, &mut budget).await?;
+            if line.is_empty() {
+                break;
+            }
+
+            let (key, value) = line
+                .split_once(':')
+                .ok_or(HttpError::InvalidResponse(format!("Invalid header '{}'", line)))?;
+
+            let key = key.trim().to_lowercase();
+            let value = value.trim();
+
+            // Headers which are sent multiple times are combined into one
+            headers
+                .entry(key)
+                .and_modify(|e: &mut String| {
+                    e.push_str(", ");
+                    e.push_str(value);
+                })
+                .or_insert_with(|| value.to_string());
+        }
+
+        if (100..200).contains(&status) {
+            continue;
+        }
+
+        return Ok(ResponseHead {
+            status,
+            reason,
+            http_11: protocol == "HTTP/1.1",
+            headers,
+        });
+    }
+}
+
+/// Reads the body of a response, either chunked, with a content length or until the connection is closed
+///
+/// # Arguments
+///
+/// * `reader` - The connection to read from
+/// * `head` - The head of the response this body belongs to
+/// * `is_head_request` - Whether the request was a `HEAD` request, which never has a body
+///
+/// # Returns
+///
+/// The body and whether the connection can be reused afterwards
+pub(super) async fn read_body<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    head: &ResponseHead,
+    is_head_request: bool,
+) -> HttpResult<(Vec<u8>, bool)> {
+    let keep_alive = head.keep_alive();
+    if is_head_request || head.status == 204 || head.status == 304 {
+        return Ok((Vec::new(), keep_alive));
+    }
+
+    let chunked = head
+        .headers
+        .get("transfer-encoding")
+        .is_some_and(|e| e.to_lowercase().split(',').any(|e| e.trim() == "chunked"));
+
+    if chunked {
+        let body = read_chunked(reader).await?;
+        return Ok((body, keep_alive));
+    }
+
+    if let Some(length) = head.headers.get("content-length") {
+        let length: usize = length
+            .trim()
+            .parse()
+            .map_err(|_| HttpError::InvalidResponse(format!("Invalid content length '{}'", length)))?;
+
+        if length > MAX_BODY_SIZE {
+            return Err(HttpError::BodyTooLarge(MAX_BODY_SIZE));
+        }
+
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).await?;
+        return Ok((body, keep_alive));
+    }
+
+    // Without a length the body ends when the server closes the connection
+    let mut body = Vec::new();
+    read_limited(reader, &mut body, MAX_BODY_SIZE).await?;
+    Ok((body, false))
+}
+
+/// Decodes a body with `Transfer-Encoding: chunked`
+async fn read_chunked<R: AsyncBufRead + Unpin>(reader: &mut R) -> HttpResult<Vec<u8>> {
+    let mut body = Vec::new();
+    let mut budget = MAX_HEAD_SIZE;
+
+    loop {
+        let line = read_line(reader, &mut budget).await?;
+        // Chunk extensions after ';' are ignored
+        let size = line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16)
+            .map_err(|_| HttpError::InvalidResponse(format!("Invalid chunk size '{}'", size)))?;
+
+        if size == 0 {
+            break;
+        }
+
+        if body.len() + size > MAX_BODY_SIZE {
+            return Err(HttpError::BodyTooLarge(MAX_BODY_SIZE));
+        }
+
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..]).await?;
+
+        // Every chunk is followed by a line break
+        let rest = read_line(reader, &mut budget).await?;
+        if !rest.is_empty() {
+            return Err(HttpError::InvalidResponse("Chunk is longer than its size".to_string()));
+        }
+    }
+
+    // Skipping trailers until the empty line which ends the body
+    while !read_line(reader, &mut budget).await?.is_empty() {}
+
+    Ok(body)
+}
+
+/// Reads until the end of the stream, failing if more than `max` bytes are sent
+async fn read_limited<R: AsyncRead + Unpin>(reader: &mut R, buf: &mut Vec<u8>, max: usize) -> HttpResult<()> {
+    let read = reader.take(max as u64 + 1).read_to_end(buf).await?;
+    if read > max {
+        return Err(HttpError::BodyTooLarge(max));
+    }
+
+    Ok(())
+}
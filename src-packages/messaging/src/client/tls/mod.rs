@@ -1,6 +1,15 @@
 mod client;
-mod response;
+mod error;
+mod http1;
+mod pool;
 mod request;
+mod response;
+mod stream;
+#[cfg(test)]
+mod tests;
 
-/// This web client can send https requests across the tor network and parse/return the response.
-pub use client::WebClient;
\ No newline at end of file
+/// This web client can send http and https requests across the tor network and parse/return the response.
+pub use client::WebClient;
+pub use error::*;
+pub use request::Request;
+pub use response::Response;
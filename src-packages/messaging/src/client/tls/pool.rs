@@ -0,0 +1,81 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use futures_util::FutureExt;
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, BufReader},
+    sync::Mutex,
+};
+use url::Url;
+
+use super::{error::{HttpError, HttpResult}, stream::HttpStream};
+
+/// How long an unused connection is kept open to be reused
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+/// How many unused connections are kept per host
+const MAX_IDLE_PER_HOST: usize = 4;
+
+/// A connection to a server, ready to send the next request on
+pub(super) type PooledConnection = BufReader<HttpStream>;
+
+/// Connections can only be reused for requests with the same scheme, host and port
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(super) struct PoolKey {
+    scheme: String,
+    host: String,
+    port: u16,
+}
+
+impl PoolKey {
+    /// Creates the key connections to the given url are stored with
+    pub fn from_url(url: &Url) -> HttpResult<Self> {
+        Ok(Self {
+            scheme: url.scheme().to_string(),
+            host: url.host_str().ok_or(HttpError::MissingHost)?.to_string(),
+            port: url.port_or_known_default().unwrap_or(80),
+        })
+    }
+}
+
+/// Keeps connections open after a request, so the next request to the same host
+/// doesn't have to build a new circuit / tls session
+#[derive(Debug)]
+pub(super) struct ConnectionPool<C = PooledConnection> {
+    idle: Mutex<HashMap<PoolKey, Vec<(C, Instant)>>>,
+}
+
+impl<C> Default for ConnectionPool<C> {
+    fn default() -> Self {
+        Self { idle: Mutex::new(HashMap::new()) }
+    }
+}
+
+impl<C> ConnectionPool<C> {
+    /// Takes the most recently used connection to the host out of the pool, dropping the expired ones
+    pub async fn take(&self, key: &PoolKey) -> Option<C> {
+        let mut idle = self.idle.lock().await;
+        let conns = idle.get_mut(key)?;
+        conns.retain(|(_, since)| since.elapsed() < IDLE_TIMEOUT);
+
+        conns.pop().map(|(conn, _)| conn)
+    }
+
+    /// Puts a connection back so it can be reused
+    pub async fn put(&self, key: PoolKey, conn: C) {
+        let mut idle = self.idle.lock().await;
+        let conns = idle.entry(key).or_default();
+        if conns.len() >= MAX_IDLE_PER_HOST {
+            conns.remove(0);
+        }
+
+        conns.push((conn, Instant::now()));
+    }
+}
+
+/// Checks without waiting whether an idle connection can still be used, before anything is written to it.
+/// The server closing the connection shows up as end of stream, any data it sent unasked makes the connection unusable too.
+pub(super) fn is_idle_open<R: AsyncBufRead + Unpin>(conn: &mut R) -> bool {
+    conn.fill_buf().now_or_never().is_none()
+}
@@ -1,10 +1,15 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
-use anyhow::{anyhow, Result};
-use tokio::io::AsyncWriteExt;
+use log::debug;
+use serde::Serialize;
+use tokio::time::timeout;
 use url::Url;
 
-use super::{response::Response, WebClient};
+use super::{
+    error::{HttpError, HttpResult},
+    response::Response,
+    WebClient,
+};
 
 /// Represents a request to a server which should be made
 pub struct Request<'a> {
@@ -16,6 +21,12 @@ pub struct Request<'a> {
     method: String,
     /// Headers to send with this request
     headers: HashMap<String, String>,
+    /// The body to send with this request
+    body: Option<Vec<u8>>,
+    /// How long the whole request (including redirects) may take
+    timeout: Duration,
+    /// How many redirects are followed before giving up
+    max_redirects: usize,
 }
 
 impl<'a> Request<'a> {
@@ -26,16 +37,27 @@ impl<'a> Request<'a> {
     /// * `client` - The client making the request.
     /// * `method` - The HTTP method of the request.
     /// * `url` - The URL of the request.
+    /// * `timeout` - How long the request may take
+    /// * `max_redirects` - How many redirects are followed
     ///
     /// # Returns
     ///
     /// A new `Request` instance builder.
-    pub(super) fn from_client(client: &'a WebClient, method: &str, url: &str) -> Self {
+    pub(super) fn from_client(
+        client: &'a WebClient,
+        method: &str,
+        url: &str,
+        timeout: Duration,
+        max_redirects: usize,
+    ) -> Self {
         Self {
             client,
             headers: HashMap::new(),
             method: method.to_string(),
             url: url.to_string(),
+            body: None,
+            timeout,
+            max_redirects,
         }
     }
 
@@ -50,51 +72,72 @@ impl<'a> Request<'a> {
     ///
     /// The modified `Request` instance.
     pub fn header(mut self, header: &str, value: &str) -> Self {
+        // Header names are case insensitive, so the old value is removed no matter how it was written
+        self.headers.retain(|k, _| !k.eq_ignore_ascii_case(header));
         self.headers.insert(header.to_string(), value.to_string());
 
         self
     }
 
-    /// Gets the headers of this function ready to be sent to the server.
-    /// Formatted for the HTTP Protocol
+    /// Sets the body of the request
     ///
     /// # Arguments
     ///
-    /// * `host` - The host to send this HTTP request to
+    /// * `body` - The raw body to send
     ///
     /// # Returns
     ///
-    /// The headers of the request as a formatted string.
-    fn get_headers(&self, host: &str) -> String {
-        let mut lines = Vec::<String>::new();
-        lines.push(format!("Host: {}", host));
-        for (key, value) in self.headers.iter() {
-            lines.push(format!("{}: {}", key, value))
-        }
+    /// The modified `Request` instance.
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = Some(body.into());
 
-        lines.join("\r\n")
+        self
     }
 
-    /// Gets the beginning of the HTTP Payload
+    /// Serializes the given value to json and uses it as body
     ///
     /// # Arguments
     ///
-    /// * `url` - The parsed URL that this request is being sent to
-    /// * `path` - The path of the request.
+    /// * `value` - The value to send as json
     ///
     /// # Returns
     ///
-    /// The full headers of the request as a formatted string.
-    fn get_http_payload(&self, url: &Url, path: &str) -> Result<String> {
-        // Using HTTP 1.1 Protocol
-        let start = format!("{} {} HTTP/1.1\r\n", self.method, path);
-        
-        let server_name_raw = url.host_str().ok_or(anyhow!("Url has to have a host."))?;
-        let headers = self.get_headers(server_name_raw);
+    /// The modified `Request` instance.
+    pub fn json<T: Serialize>(self, value: &T) -> HttpResult<Self> {
+        let body = serde_json::to_vec(value)?;
 
-        Ok(format!("{}{}\r\n\r\n", start, headers))
+        Ok(self.header("Content-Type", "application/json").body(body))
     }
 
+    /// Sets how long the request may take, including connecting and following redirects
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The maximum duration of this request
+    ///
+    /// # Returns
+    ///
+    /// The modified `Request` instance.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+
+        self
+    }
+
+    /// Sets how many redirects are followed, `0` returns redirects as they are
+    ///
+    /// # Arguments
+    ///
+    /// * `max_redirects` - How many redirects may be followed
+    ///
+    /// # Returns
+    ///
+    /// The modified `Request` instance.
+    pub fn max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+
+        self
+    }
 
     /// Sends this request and returns the response of the server
     ///
@@ -104,21 +147,95 @@ impl<'a> Request<'a> {
     ///
     /// # Errors
     ///
-    /// Returns an error if there was a problem sending the request or receiving the response.
-    pub async fn send(self) -> Result<Response> {
-        let url = Url::parse(&self.url)?;
-        let mut path = url.path();
-        if path.is_empty() {
-            path = "/";
+    /// Returns an error if there was a problem sending the request or receiving the response,
+    /// or the request took longer than its timeout.
+    pub async fn send(self) -> HttpResult<Response> {
+        let max_wait = self.timeout;
+
+        timeout(max_wait, self.send_following_redirects())
+            .await
+            .map_err(|_| HttpError::Timeout(max_wait))?
+    }
+
+    /// Sends this request and follows the redirects of the server
+    async fn send_following_redirects(self) -> HttpResult<Response> {
+        let mut url = Url::parse(&self.url)?;
+        let mut method = self.method.clone();
+        let mut headers = self.headers.clone();
+        let mut body = self.body.clone();
+
+        let mut redirects = 0;
+        loop {
+            let resp = self
+                .client
+                .execute(&method, &url, &headers, body.as_deref())
+                .await?;
+
+            let Some(location) = resp.redirect_location() else {
+                return Ok(resp);
+            };
+
+            if self.max_redirects == 0 {
+                return Ok(resp);
+            }
+
+            if redirects >= self.max_redirects {
+                return Err(HttpError::TooManyRedirects(self.max_redirects));
+            }
+            redirects += 1;
+
+            let next = url.join(location)?;
+            debug!("Following redirect ({}) from {} to {}", resp.status(), url, next);
+
+            prepare_redirect(resp.status(), &url, &next, &mut method, &mut headers, &mut body)?;
+            url = next;
         }
+    }
+}
 
-        let proxy_conn = self.client.proxy().connect(&url).await?;
-        let mut stream = self.client.create_connection(proxy_conn, &url).await?;
+/// Headers which must not be sent to another origin than the one they were set for
+const CREDENTIAL_HEADERS: [&str; 3] = ["Authorization", "Cookie", "Proxy-Authorization"];
 
-        let prepend = self.get_http_payload(&url, path)?;
-        stream.write_all(prepend.as_bytes()).await?;
+/// Adjusts the request for following a redirect
+///
+/// # Arguments
+///
+/// * `status` - The status code of the redirect
+/// * `from` - The url which was redirected
+/// * `to` - The url the redirect points to
+/// * `method` - The method of the request, changed to GET where the redirect requires it
+/// * `headers` - The headers of the request, credentials are removed when leaving the origin
+/// * `body` - The body of the request, removed together with the method
+///
+/// # Errors
+///
+/// Returns an error if the redirect would downgrade https to http
+pub(super) fn prepare_redirect(
+    status: u16,
+    from: &Url,
+    to: &Url,
+    method: &mut String,
+    headers: &mut HashMap<String, String>,
+    body: &mut Option<Vec<u8>>,
+) -> HttpResult<()> {
+    if from.scheme() == "https" && to.scheme() != "https" {
+        return Err(HttpError::InsecureRedirect(to.clone()));
+    }
+
+    let same_origin = from.scheme() == to.scheme()
+        && from.host_str() == to.host_str()
+        && from.port_or_known_default() == to.port_or_known_default();
 
-        let resp = Response::from_stream(stream).await;
-        Ok(resp)
+    if !same_origin {
+        headers.retain(|k, _| !CREDENTIAL_HEADERS.iter().any(|e| k.eq_ignore_ascii_case(e)));
     }
+
+    // Except for 307 and 308, the redirect has to be requested with GET and without body
+    if matches!(status, 301..=303) && method != "HEAD" {
+        *method = "GET".to_string();
+        *body = None;
+        headers.retain(|k, _| !k.eq_ignore_ascii_case("Content-Type"));
+    }
+
+    Ok(())
 }
@@ -1,192 +1,132 @@
 use std::collections::HashMap;
 
-use anyhow::{anyhow, Result};
-use log::{debug, warn};
 use serde::de::DeserializeOwned;
-use tokio::{
-    io::{AsyncBufReadExt, AsyncReadExt, BufReader},
-    net::TcpStream,
+use url::Url;
+
+use super::{
+    error::{HttpError, HttpResult},
+    http1::ResponseHead,
 };
-use tokio_rustls::client::TlsStream;
-use tokio_socks::tcp::Socks5Stream;
 
+/// Represents a response from the web client, with the body already read completely
+#[derive(Debug, Clone)]
 pub struct Response {
-    reader: BufReader<TlsStream<Socks5Stream<TcpStream>>>,
-    status_code: Option<u32>,
-    headers: Option<HashMap<String, String>>,
+    /// The url this response is from (after following redirects)
+    url: Url,
+    status_code: u16,
+    reason: String,
+    /// The headers of the response with lowercase names
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
 }
 
-/// The seperator between the header key and value
-const HEADER_SEPARATOR: &str = ": ";
-
-/// Represents a response from the web client.
 impl Response {
-    /// Retrieves the headers of the response.
+    /// Creates a response from the head and body read from the server
+    ///
+    /// # Arguments
     ///
-    /// If the headers have already been retrieved, it returns a clone of the cached headers.
-    /// Otherwise, it reads the status code and then reads the headers from the stream.
+    /// * `url` - The url the request was sent to
+    /// * `head` - The status line and headers of the response
+    /// * `body` - The decoded body of the response
     ///
     /// # Returns
     ///
-    /// A `Result` containing a `HashMap` of the headers.
-    pub async fn headers(&mut self) -> Result<HashMap<String, String>> {
-        if let Some(headers) = self.headers.as_ref() {
-            return Ok(headers.clone());
-        }
-
-        if self.status_code.is_none() {
-            self.read_status().await?;
+    /// Returns the created `Response`
+    pub(super) fn new(url: Url, head: ResponseHead, body: Vec<u8>) -> Self {
+        Response {
+            url,
+            status_code: head.status,
+            reason: head.reason,
+            headers: head.headers,
+            body,
         }
-
-        let res = self.read_headers().await?;
-        Ok(res)
     }
 
-    /// Reads the status code from the stream and stores it in `self`.
-    /// Reads from the incoming buffer
-    ///
     /// # Returns
     ///
     /// The status code of this response
-    async fn read_status(&mut self) -> Result<u32> {
-        let mut buf = String::new();
-
-        self.reader.read_line(&mut buf).await?;
-        let mut split = buf.split_ascii_whitespace();
-
-        let protocol = split.next().ok_or(anyhow!("Not starting with protocol"))?;
-        let status_code = split.next().ok_or(anyhow!("No status code"))?;
-        let status_code: u32 = status_code.parse()?;
-
-        if !protocol.contains("HTTP") {
-            return Err(anyhow!("Invalid protocol {}", protocol));
-        }
-
-        self.status_code = Some(status_code);
-        Ok(status_code)
+    pub fn status(&self) -> u16 {
+        self.status_code
     }
 
-    /// Reads the status code and stores it in `self`
-    /// of the response or returns the cached status code.
-    ///
     /// # Returns
     ///
-    /// A `Result` containing the status code as a `u32`
-    pub async fn status(&mut self) -> Result<u32> {
-        if let Some(code) = self.status_code {
-            return Ok(code);
-        }
+    /// The reason phrase of the status line (e.g. `Not Found`)
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
 
-        let res = self.read_status().await?;
-        self.status_code = Some(res);
+    /// # Returns
+    ///
+    /// Whether the status code is in the 2xx range
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status_code)
+    }
 
-        Ok(res)
+    /// # Returns
+    ///
+    /// The url this response is from, differs from the requested url if redirects were followed
+    pub fn url(&self) -> &Url {
+        &self.url
     }
 
-    /// Reads the headers from the incoming stream, parses them and stores them in a hash map.
+    /// Retrieves the headers of the response, every name is lowercase
     ///
     /// # Returns
     ///
-    /// A hashmap of the headers
-    async fn read_headers(&mut self) -> Result<HashMap<String, String>> {
-        let mut buf = String::new();
-
-        // The hashmap used to store the headers by its key
-        let mut headers = HashMap::<String, String>::new();
-        loop {
-            // Clearing the string buffer
-            buf.clear();
-
-            // Reads a line from the stream
-            let res = self.reader.read_line(&mut buf).await;
-            if res.is_err() || res.unwrap() == 0 {
-                break;
-            }
-
-            // Replace weird windows line endings
-            let buf = buf.replace("\r", "");
-            debug!("Buf: {}", buf);
-
-            // If the line is empty, we are done
-            let only_whitespace = buf.chars().all(|e| e.is_whitespace() || e == '\n');
-
-            if only_whitespace {
-                break;
-            }
-
-            // Split the line at the first occurrence of the separator
-            let mut pair = Vec::from_iter(buf.split(HEADER_SEPARATOR));
-            if pair.len() < 2 {
-                let char_vec: Vec<String> =
-                    buf.chars().map(|e| e.escape_debug().to_string()).collect();
-
-                warn!(
-                    "Server returned invalid header '{}' (chars: {:?})",
-                    buf, char_vec
-                );
-                continue;
-            }
-
-            // And extracting the key and value
-            let key = pair.remove(0).to_string();
-            let value = pair
-                .join(HEADER_SEPARATOR)
-                .trim_end_matches(|e| e == '\n' || e == '\r')
-                .to_string();
-
-            headers.insert(key, value);
-        }
-
-        debug!("Headers are {:#?}", headers);
-
-        // Done, storing the headers in self
-        self.headers.replace(headers.clone());
-        Ok(headers)
+    /// A `HashMap` of the headers
+    pub fn headers(&self) -> &HashMap<String, String> {
+        &self.headers
     }
 
-    /// Creates a `Response` from the given TLS stream.
+    /// Gets a single header of the response
     ///
     /// # Arguments
     ///
-    /// * `stream` - The TLS stream to read from.
+    /// * `name` - The name of the header, case insensitive
     ///
     /// # Returns
     ///
-    /// Returns the created `Response`
-    pub(super) async fn from_stream(stream: TlsStream<Socks5Stream<TcpStream>>) -> Self {
-        let reader = BufReader::new(stream);
+    /// The value of the header if it was sent
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_lowercase()).map(|e| e.as_str())
+    }
 
-        Response {
-            reader,
-            status_code: None,
-            headers: None,
+    /// # Returns
+    ///
+    /// Where the server redirects to, if this response is a redirect
+    pub(super) fn redirect_location(&self) -> Option<&str> {
+        match self.status_code {
+            301 | 302 | 303 | 307 | 308 => self.header("location"),
+            _ => None,
         }
     }
 
+    /// # Returns
+    ///
+    /// The raw body of the response
+    pub fn bytes(self) -> Vec<u8> {
+        self.body
+    }
+
     /// Reads the body of the response and deserializing it to json
     ///
     /// # Returns
     ///
     /// The deserialized json object
-    pub async fn json<'a, T>(self) -> Result<T>
+    pub fn json<T>(&self) -> HttpResult<T>
     where
         T: DeserializeOwned,
     {
-        let txt = self.text().await?;
-
-        let json = serde_json::from_str::<T>(&txt)?;
-        Ok(json)
+        serde_json::from_slice::<T>(&self.body).map_err(HttpError::Json)
     }
 
     /// Reads the body of the response and returns it as a string
     ///
     /// # Returns
     ///
-    /// The response body as string
-    pub async fn text(mut self) -> Result<String> {
-        let mut buf = String::new();
-        self.reader.read_to_string(&mut buf).await?;
-
-        Ok(buf)
+    /// The response body as string, invalid utf-8 is replaced
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).to_string()
     }
 }
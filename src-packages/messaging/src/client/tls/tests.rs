@@ -0,0 +1,290 @@
+//! Tests of the http/1.1 parsing, the connection pool and redirects over in-memory streams
+
+use std::collections::HashMap;
+
+use tokio::io::{duplex, AsyncWriteExt, BufReader};
+use url::Url;
+
+use super::{
+    error::{HttpError, HttpResult},
+    http1::{read_body, read_head, ResponseHead, MAX_BODY_SIZE},
+    pool::{is_idle_open, ConnectionPool, PoolKey},
+    request::prepare_redirect,
+};
+
+/// Reads the head and body of a raw response
+///
+/// # Returns
+///
+/// The head, the body with whether the connection is reusable, and whatever is left of the stream
+async fn parse(raw: &[u8], is_head_request: bool) -> (ResponseHead, HttpResult<(Vec<u8>, bool)>, Vec<u8>) {
+    let mut reader = raw;
+    let head = read_head(&mut reader).await.unwrap();
+    let body = read_body(&mut reader, &head, is_head_request).await;
+
+    (head, body, reader.to_vec())
+}
+
+async fn parse_body(raw: &[u8]) -> HttpResult<(Vec<u8>, bool)> {
+    parse(raw, false).await.1
+}
+
+fn url(url: &str) -> Url {
+    Url::parse(url).unwrap()
+}
+
+#[tokio::test]
+async fn content_length_body() {
+    let raw = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nX-Test: a\r\nx-test: b\r\n\r\nhelloHTTP/1.1";
+    let (head, body, rest) = parse(raw, false).await;
+
+    assert_eq!(head.status, 200);
+    assert_eq!(head.reason, "OK");
+    assert_eq!(head.headers["x-test"], "a, b");
+    assert_eq!(body.unwrap(), (b"hello".to_vec(), true));
+    // The next response on the connection is left untouched
+    assert_eq!(rest, b"HTTP/1.1");
+}
+
+#[tokio::test]
+async fn body_until_eof() {
+    let (body, reusable) = parse_body(b"HTTP/1.1 200 OK\r\n\r\nuntil the end").await.unwrap();
+    assert_eq!(body, b"until the end");
+    assert!(!reusable);
+
+    assert!(parse_body(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nshort").await.is_err());
+}
+
+#[tokio::test]
+async fn connection_reuse() {
+    let (_, reusable) = parse_body(b"HTTP/1.0 200 OK\r\nContent-Length: 0\r\n\r\n").await.unwrap();
+    assert!(!reusable);
+
+    let (_, reusable) = parse_body(b"HTTP/1.0 200 OK\r\nConnection: keep-alive\r\nContent-Length: 0\r\n\r\n").await.unwrap();
+    assert!(reusable);
+
+    let (_, reusable) = parse_body(b"HTTP/1.1 200 OK\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n").await.unwrap();
+    assert!(!reusable);
+}
+
+#[tokio::test]
+async fn responses_without_body() {
+    let (_, body, rest) = parse(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nnext", true).await;
+    assert_eq!(body.unwrap(), (Vec::new(), true));
+    assert_eq!(rest, b"next");
+
+    let (_, body, rest) = parse(b"HTTP/1.1 204 No Content\r\n\r\nnext", false).await;
+    assert_eq!(body.unwrap(), (Vec::new(), true));
+    assert_eq!(rest, b"next");
+}
+
+#[tokio::test]
+async fn chunked_body() {
+    let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: gzip, Chunked\r\n\r\n5;ext=1\r\nhello\r\nA\r\n, chunked!\r\n0\r\nX-Trailer: value\r\nX-Other: value\r\n\r\nnext";
+    let (_, body, rest) = parse(raw, false).await;
+
+    assert_eq!(body.unwrap(), (b"hello, chunked!".to_vec(), true));
+    assert_eq!(rest, b"next");
+}
+
+#[tokio::test]
+async fn invalid_chunks() {
+    let invalid_size = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nxyz\r\nhello\r\n0\r\n\r\n";
+    assert!(matches!(parse_body(invalid_size).await, Err(HttpError::InvalidResponse(_))));
+
+    let negative_size = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n-5\r\nhello\r\n0\r\n\r\n";
+    assert!(matches!(parse_body(negative_size).await, Err(HttpError::InvalidResponse(_))));
+
+    let too_long = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nhello\r\n0\r\n\r\n";
+    assert!(matches!(parse_body(too_long).await, Err(HttpError::InvalidResponse(_))));
+
+    let unterminated = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n";
+    assert!(matches!(parse_body(unterminated).await, Err(HttpError::Io(_))));
+}
+
+#[tokio::test]
+async fn informational_responses_are_skipped() {
+    let raw = b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 103 Early Hints\r\nLink: </style.css>\r\n\r\nHTTP/1.1 201 Created\r\nContent-Length: 2\r\n\r\nok";
+    let (head, body, _) = parse(raw, false).await;
+
+    assert_eq!(head.status, 201);
+    assert!(!head.headers.contains_key("link"));
+    assert_eq!(body.unwrap().0, b"ok");
+}
+
+#[tokio::test]
+async fn invalid_heads() {
+    assert!(read_head(&mut &b"SSH-2.0-OpenSSH\r\n\r\n"[..]).await.is_err());
+    assert!(read_head(&mut &b"HTTP/1.1 abc OK\r\n\r\n"[..]).await.is_err());
+    assert!(read_head(&mut &b"HTTP/1.1 200 OK\r\nno colon\r\n\r\n"[..]).await.is_err());
+
+    let huge_header = format!("HTTP/1.1 200 OK\r\nX-Big: {}\r\n\r\n", "a".repeat(64 * 1024));
+    assert!(matches!(
+        read_head(&mut huge_header.as_bytes()).await,
+        Err(HttpError::InvalidResponse(_))
+    ));
+}
+
+#[tokio::test]
+async fn body_size_limit() {
+    let declared = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", MAX_BODY_SIZE + 1);
+    assert!(matches!(parse_body(declared.as_bytes()).await, Err(HttpError::BodyTooLarge(_))));
+
+    let chunk = format!("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n", MAX_BODY_SIZE + 1);
+    assert!(matches!(parse_body(chunk.as_bytes()).await, Err(HttpError::BodyTooLarge(_))));
+
+    let mut until_eof = b"HTTP/1.1 200 OK\r\n\r\n".to_vec();
+    until_eof.resize(until_eof.len() + MAX_BODY_SIZE, b'a');
+    assert_eq!(parse_body(&until_eof).await.unwrap().0.len(), MAX_BODY_SIZE);
+
+    until_eof.push(b'a');
+    assert!(matches!(parse_body(&until_eof).await, Err(HttpError::BodyTooLarge(_))));
+}
+
+#[test]
+fn redirects_change_method() {
+    let from = url("https://example.com/form");
+    let to = url("https://example.com/done");
+
+    for status in [301, 302, 303] {
+        let mut method = "POST".to_string();
+        let mut headers = HashMap::from([("content-type".to_string(), "application/json".to_string())]);
+        let mut body = Some(b"{}".to_vec());
+
+        prepare_redirect(status, &from, &to, &mut method, &mut headers, &mut body).unwrap();
+        assert_eq!(method, "GET");
+        assert!(body.is_none());
+        assert!(headers.is_empty());
+    }
+
+    for status in [307, 308] {
+        let mut method = "POST".to_string();
+        let mut headers = HashMap::from([("Content-Type".to_string(), "application/json".to_string())]);
+        let mut body = Some(b"{}".to_vec());
+
+        prepare_redirect(status, &from, &to, &mut method, &mut headers, &mut body).unwrap();
+        assert_eq!(method, "POST");
+        assert_eq!(body.as_deref(), Some(&b"{}"[..]));
+        assert_eq!(headers.len(), 1);
+    }
+
+    let mut method = "HEAD".to_string();
+    prepare_redirect(302, &from, &to, &mut method, &mut HashMap::new(), &mut None).unwrap();
+    assert_eq!(method, "HEAD");
+}
+
+#[test]
+fn redirects_strip_credentials() {
+    let credentials = || {
+        HashMap::from([
+            ("authorization".to_string(), "Bearer token".to_string()),
+            ("Cookie".to_string(), "session=1".to_string()),
+            ("Proxy-Authorization".to_string(), "Basic abc".to_string()),
+            ("Accept".to_string(), "*/*".to_string()),
+        ])
+    };
+
+    let same_origin = [
+        ("https://example.com/a", "https://example.com/b"),
+        ("https://example.com/a", "https://example.com:443/b"),
+        ("http://example.onion/a", "http://example.onion:80/b"),
+    ];
+    for (from, to) in same_origin {
+        let mut headers = credentials();
+        prepare_redirect(307, &url(from), &url(to), &mut "GET".to_string(), &mut headers, &mut None).unwrap();
+        assert_eq!(headers.len(), 4, "{} -> {}", from, to);
+    }
+
+    let other_origin = [
+        ("https://example.com/a", "https://other.com/a"),
+        ("https://example.com/a", "https://example.com:8443/a"),
+        ("http://example.com/a", "https://example.com/a"),
+    ];
+    for (from, to) in other_origin {
+        let mut headers = credentials();
+        prepare_redirect(307, &url(from), &url(to), &mut "GET".to_string(), &mut headers, &mut None).unwrap();
+        assert_eq!(headers.keys().collect::<Vec<_>>(), ["Accept"], "{} -> {}", from, to);
+    }
+}
+
+#[test]
+fn redirects_refuse_downgrade() {
+    let res = prepare_redirect(
+        302,
+        &url("https://example.com/"),
+        &url("http://example.com/"),
+        &mut "GET".to_string(),
+        &mut HashMap::new(),
+        &mut None,
+    );
+    assert!(matches!(res, Err(HttpError::InsecureRedirect(_))));
+
+    let res = prepare_redirect(
+        302,
+        &url("http://example.onion/"),
+        &url("https://example.com/"),
+        &mut "GET".to_string(),
+        &mut HashMap::new(),
+        &mut None,
+    );
+    assert!(res.is_ok());
+}
+
+#[test]
+fn pool_keys() {
+    let key = |u: &str| PoolKey::from_url(&url(u)).unwrap();
+
+    assert_eq!(key("http://example.onion/a"), key("http://example.onion:80/b?c"));
+    assert_eq!(key("https://example.com/"), key("https://example.com:443/"));
+    assert_ne!(key("http://example.com/"), key("https://example.com/"));
+    assert_ne!(key("https://example.com/"), key("https://example.com:8443/"));
+    assert_ne!(key("https://example.com/"), key("https://www.example.com/"));
+}
+
+#[tokio::test]
+async fn pool_reuses_by_key() {
+    let key = |u: &str| PoolKey::from_url(&url(u)).unwrap();
+    let pool = ConnectionPool::<u32>::default();
+
+    pool.put(key("https://example.com/"), 1).await;
+    pool.put(key("https://example.com/"), 2).await;
+    pool.put(key("http://example.com/"), 3).await;
+
+    assert_eq!(pool.take(&key("https://example.com:8443/")).await, None);
+    // The most recently used connection is taken first
+    assert_eq!(pool.take(&key("https://example.com/other")).await, Some(2));
+    assert_eq!(pool.take(&key("https://example.com/")).await, Some(1));
+    assert_eq!(pool.take(&key("https://example.com/")).await, None);
+    assert_eq!(pool.take(&key("http://example.com/")).await, Some(3));
+}
+
+#[tokio::test]
+async fn pool_keeps_few_idle_connections() {
+    let key = PoolKey::from_url(&url("https://example.com/")).unwrap();
+    let pool = ConnectionPool::<u32>::default();
+
+    for conn in 0..10 {
+        pool.put(key.clone(), conn).await;
+    }
+
+    let mut kept = Vec::new();
+    while let Some(conn) = pool.take(&key).await {
+        kept.push(conn);
+    }
+    assert_eq!(kept, [9, 8, 7, 6]);
+}
+
+#[tokio::test]
+async fn closed_idle_connections_are_detected() {
+    let (client, mut server) = duplex(64);
+    let mut conn = BufReader::new(client);
+    assert!(is_idle_open(&mut conn));
+
+    server.write_all(b"unexpected").await.unwrap();
+    assert!(!is_idle_open(&mut conn));
+
+    let (client, server) = duplex(64);
+    let mut conn = BufReader::new(client);
+    drop(server);
+    assert!(!is_idle_open(&mut conn));
+}
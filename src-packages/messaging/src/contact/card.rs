@@ -0,0 +1,248 @@
+use anyhow::{anyhow, Result};
+use data_encoding::BASE64URL_NOPAD;
+use encryption::PublicKey;
+use log::{info, warn};
+use shared::onion::{onion_public_key, parse_onion_hostname};
+use storage_internal::{helpers::{GetPrivateKey, ModifyChat}, StorageManager};
+use tor_proxy::service::{
+    decode_client_auth_key, encode_client_auth_key, get_service_hostname, sign_as_service, verify_service_signature,
+};
+use url::{form_urlencoded, Url};
+
+use crate::general::{issue_client_auth, set_remote_client_auth};
+
+/// The scheme of contact card URIs
+pub const CARD_SCHEME: &str = "enkrypton";
+/// The version of the contact card format, cards of other versions are refused
+pub const CARD_VERSION: u8 = 1;
+/// The length of the hex encoded fingerprint of a public key
+const FINGERPRINT_LENGTH: usize = 64;
+
+/// Everything another user needs to add us as a contact, e.g. `enkrypton:<onion>?v=1&to=...&fp=...&sig=...`.
+/// Signed with the key of our onion service, so nobody can hand out cards in our name.
+/// Cards are issued to a single recipient, as we use another rsa key for every contact
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContactCard {
+    /// The onion hostname of the user that issued the card
+    pub hostname: String,
+    /// The onion hostname of the contact this card has been issued to
+    pub recipient: String,
+    /// The fingerprint of the rsa key the issuer uses for the recipient
+    pub fingerprint: String,
+    /// The client authorization key the recipient needs to discover the onion service of the issuer
+    pub auth_key: Option<Vec<u8>>,
+    /// The name the issuer wants to be shown as
+    pub name: Option<String>,
+    /// The signature of the onion service of the issuer over every other field
+    pub signature: Vec<u8>,
+}
+
+impl ContactCard {
+    /// Creates and signs a new card of ourselves for the given contact
+    ///
+    /// # Arguments
+    ///
+    /// * `recipient` - The onion hostname of the contact to invite
+    /// * `name` - The name we want to be shown as
+    /// * `with_auth` - Whether to issue a client authorization key to the contact and put it in the card
+    ///
+    /// # Returns
+    ///
+    /// The signed card
+    pub async fn create(recipient: &str, name: Option<String>, with_auth: bool) -> Result<Self> {
+        onion_public_key(recipient)?;
+
+        // The contact dials our server, so this is the hostname it knows us by
+        let hostname = get_service_hostname(false)
+            .await?
+            .ok_or(anyhow!("Could not get own hostname"))?;
+
+        let priv_key = StorageManager::get_or_create_private_key(recipient).await?;
+        let pub_key: PublicKey = priv_key.try_into()?;
+
+        let auth_key = match with_auth {
+            true => Some(issue_client_auth(recipient).await?),
+            false => None,
+        };
+
+        let mut card = Self {
+            hostname,
+            recipient: recipient.to_string(),
+            fingerprint: pub_key.fingerprint()?,
+            auth_key,
+            name: name.filter(|n| !n.is_empty()),
+            signature: Vec::new(),
+        };
+
+        card.signature = sign_as_service(&card.signed_data()).await?;
+        Ok(card)
+    }
+
+    /// Parses a card from its URI, does not verify the signature
+    ///
+    /// # Arguments
+    ///
+    /// * `uri` - The URI created by `to_uri`
+    ///
+    /// # Returns
+    ///
+    /// The parsed card, fails if the URI is not a valid contact card
+    pub fn parse(uri: &str) -> Result<Self> {
+        let url = Url::parse(uri.trim())?;
+        if url.scheme() != CARD_SCHEME {
+            return Err(anyhow!("Contact cards have to start with {}:", CARD_SCHEME));
+        }
+
+        let mut version = None;
+        let mut recipient = None;
+        let mut fingerprint = None;
+        let mut auth_key = None;
+        let mut name = None;
+        let mut signature = None;
+
+        for (key, value) in url.query_pairs() {
+            let value = value.to_string();
+            match key.as_ref() {
+                "v" => version = Some(value.parse::<u8>()?),
+                "to" => recipient = Some(value),
+                "fp" => fingerprint = Some(value.to_lowercase()),
+                "auth" => auth_key = Some(decode_client_auth_key(&value)?),
+                "name" => name = Some(value).filter(|n| !n.is_empty()),
+                "sig" => signature = Some(
+                    BASE64URL_NOPAD
+                        .decode(value.as_bytes())
+                        .map_err(|e| anyhow!("Invalid signature of contact card: {}", e))?,
+                ),
+                _ => return Err(anyhow!("Unknown field {} in contact card", key)),
+            }
+        }
+
+        if version != Some(CARD_VERSION) {
+            return Err(anyhow!("Unsupported contact card version {:?}", version));
+        }
+
+        let fingerprint = fingerprint.ok_or(anyhow!("Contact card is missing the key fingerprint"))?;
+        if fingerprint.len() != FINGERPRINT_LENGTH || !fingerprint.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(anyhow!("Invalid key fingerprint {}", fingerprint));
+        }
+
+        let hostname = parse_onion_hostname(url.path())?;
+        let recipient = recipient.ok_or(anyhow!("Contact card is missing the recipient"))?;
+        onion_public_key(&recipient)?;
+
+        Ok(Self {
+            hostname,
+            recipient,
+            fingerprint,
+            auth_key,
+            name,
+            signature: signature.ok_or(anyhow!("Contact card is not signed"))?,
+        })
+    }
+
+    /// # Returns
+    ///
+    /// The URI of this card, can be shared as text or QR code
+    pub fn to_uri(&self) -> String {
+        let mut query = form_urlencoded::Serializer::new(String::new());
+        query.append_pair("v", &CARD_VERSION.to_string());
+        query.append_pair("to", &self.recipient);
+        query.append_pair("fp", &self.fingerprint);
+
+        if let Some(key) = &self.auth_key {
+            query.append_pair("auth", &encode_client_auth_key(key));
+        }
+
+        if let Some(name) = &self.name {
+            query.append_pair("name", name);
+        }
+
+        query.append_pair("sig", &BASE64URL_NOPAD.encode(&self.signature));
+        format!("{}:{}?{}", CARD_SCHEME, self.hostname, query.finish())
+    }
+
+    /// The data the issuer signs, every field length prefixed so they can't be moved around
+    ///
+    /// # Returns
+    ///
+    /// The bytes to sign
+    fn signed_data(&self) -> Vec<u8> {
+        let auth_key = self.auth_key.as_deref();
+        let name = self.name.as_ref().map(|n| n.as_bytes());
+
+        let mut data = b"enkrypton-contact-card".to_vec();
+        data.push(CARD_VERSION);
+        for field in [
+            Some(self.hostname.as_bytes()),
+            Some(self.recipient.as_bytes()),
+            Some(self.fingerprint.as_bytes()),
+            auth_key,
+            name,
+        ] {
+            data.push(field.is_some() as u8);
+
+            let field = field.unwrap_or_default();
+            data.extend((field.len() as u64).to_le_bytes());
+            data.extend(field);
+        }
+
+        data
+    }
+
+    /// Verifies that this card has been signed by the owner of its onion address and has been issued to us
+    ///
+    /// # Arguments
+    ///
+    /// * `own_hostname` - Our own onion hostname
+    ///
+    /// # Returns
+    ///
+    /// Fails if the card is not valid for us
+    pub fn verify(&self, own_hostname: &str) -> Result<()> {
+        if self.recipient != own_hostname {
+            return Err(anyhow!("This contact card has been issued to {}", self.recipient));
+        }
+
+        verify_service_signature(&self.hostname, &self.signed_data(), &self.signature)
+    }
+
+    /// Verifies this card and adds its issuer as a contact.
+    /// The key of the issuer is pinned, so the first connection only succeeds with the key of the card
+    ///
+    /// # Returns
+    ///
+    /// Fails if the card is invalid or we already know the issuer by another key
+    pub async fn import(&self) -> Result<()> {
+        // We dial the issuer, so this is the hostname it knows us by
+        let own_hostname = get_service_hostname(true)
+            .await?
+            .ok_or(anyhow!("Could not get own hostname"))?;
+
+        if let Err(e) = self.verify(&own_hostname) {
+            warn!("[INVALID_CONTACT_CARD] {:?}", e);
+            return Err(e);
+        }
+
+        StorageManager::modify_chat(&self.hostname, |chat| {
+            if let Some(known) = &chat.rec_pub_key
+                && known.fingerprint()? != self.fingerprint
+            {
+                return Err(anyhow!("{} is already known with another key", self.hostname));
+            }
+
+            chat.rec_key_fingerprint = Some(self.fingerprint.clone());
+            if let Some(name) = &self.name {
+                chat.nickname = Some(name.clone());
+            }
+
+            Ok(())
+        }).await?;
+
+        if let Some(key) = &self.auth_key {
+            set_remote_client_auth(&self.hostname, Some(key.clone())).await?;
+        }
+
+        info!("Imported contact card of {}", self.hostname);
+        Ok(())
+    }
+}
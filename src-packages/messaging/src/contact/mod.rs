@@ -0,0 +1,5 @@
+mod card;
+mod qr;
+
+pub use card::*;
+pub use qr::*;
@@ -0,0 +1,84 @@
+use anyhow::Result;
+use png::{BitDepth, ColorType, Encoder};
+use qrcode::{render::svg, Color, QrCode};
+
+use super::ContactCard;
+
+/// How many pixels a single module of rendered PNG codes is wide
+pub const QR_MODULE_SIZE: usize = 8;
+/// The modules of white space around the code, scanners need it to find the code
+const QR_QUIET_ZONE: usize = 4;
+/// The smallest width and height of rendered SVG codes
+const QR_SVG_MIN_SIZE: u32 = 256;
+
+/// Renders the given data as QR code to an SVG image
+///
+/// # Arguments
+///
+/// * `data` - The data to encode
+///
+/// # Returns
+///
+/// The SVG document
+pub fn render_qr_svg(data: &str) -> Result<String> {
+    let code = QrCode::new(data.as_bytes())?;
+    let svg = code
+        .render::<svg::Color>()
+        .min_dimensions(QR_SVG_MIN_SIZE, QR_SVG_MIN_SIZE)
+        .build();
+
+    Ok(svg)
+}
+
+/// Renders the given data as black and white QR code to a PNG image
+///
+/// # Arguments
+///
+/// * `data` - The data to encode
+///
+/// # Returns
+///
+/// The encoded PNG file
+pub fn render_qr_png(data: &str) -> Result<Vec<u8>> {
+    let code = QrCode::new(data.as_bytes())?;
+    let modules = code.width();
+    let size = (modules + 2 * QR_QUIET_ZONE) * QR_MODULE_SIZE;
+
+    // One byte per pixel, white by default
+    let mut pixels = vec![u8::MAX; size * size];
+    for (i, color) in code.to_colors().iter().enumerate() {
+        if *color != Color::Dark {
+            continue;
+        }
+
+        let x = (i % modules + QR_QUIET_ZONE) * QR_MODULE_SIZE;
+        let y = (i / modules + QR_QUIET_ZONE) * QR_MODULE_SIZE;
+        for row in y..y + QR_MODULE_SIZE {
+            pixels[row * size + x..row * size + x + QR_MODULE_SIZE].fill(0);
+        }
+    }
+
+    let mut png = Vec::new();
+    let mut encoder = Encoder::new(&mut png, size as u32, size as u32);
+    encoder.set_color(ColorType::Grayscale);
+    encoder.set_depth(BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&pixels)?;
+
+    Ok(png)
+}
+
+impl ContactCard {
+    /// # Returns
+    ///
+    /// The URI of this card as QR code in an SVG image
+    pub fn to_qr_svg(&self) -> Result<String> {
+        render_qr_svg(&self.to_uri())
+    }
+
+    /// # Returns
+    ///
+    /// The URI of this card as QR code in a PNG image
+    pub fn to_qr_png(&self) -> Result<Vec<u8>> {
+        render_qr_png(&self.to_uri())
+    }
+}
@@ -0,0 +1,100 @@
+use anyhow::Result;
+use log::{debug, info};
+use storage_internal::{helpers::ModifyChat, StorageManager, STORAGE};
+use tor_proxy::{
+    manager::reload_tor,
+    service::{client_auth_public_key, generate_client_auth_key, write_authorized_clients, write_client_auth},
+};
+
+/// Issues a client authorization key to the given contact, so it can discover our onion service.
+/// Our service is hidden from everyone without such a key once the first one has been issued,
+/// contacts that only know our onion address can't connect anymore.
+///
+/// # Arguments
+///
+/// * `onion_host` - The contact to authorize
+///
+/// # Returns
+///
+/// The raw private key that should be given to the contact, the same key if it has been issued before
+pub async fn issue_client_auth(onion_host: &str) -> Result<Vec<u8>> {
+    let key = StorageManager::modify_chat(onion_host, |chat| {
+        if chat.auth_key.is_none() {
+            chat.auth_key = Some(generate_client_auth_key()?);
+        }
+
+        Ok(chat.auth_key.clone().unwrap())
+    }).await?;
+
+    info!("Issued client authorization key to {}", onion_host);
+    sync_client_auth().await?;
+
+    Ok(key)
+}
+
+/// Revokes the client authorization key of the given contact, it can't discover our onion service anymore
+///
+/// # Arguments
+///
+/// * `onion_host` - The contact to revoke the key of
+///
+pub async fn revoke_client_auth(onion_host: &str) -> Result<()> {
+    STORAGE.read().await.modify_storage_data(|e| {
+        if let Some(chat) = e.chats.get_mut(onion_host) {
+            chat.auth_key = None;
+        }
+
+        Ok(())
+    }).await?;
+
+    info!("Revoked client authorization key of {}", onion_host);
+    sync_client_auth().await
+}
+
+/// Stores the client authorization key the given contact issued to us, so we can dial its onion service
+///
+/// # Arguments
+///
+/// * `onion_host` - The contact that issued the key
+/// * `key` - The raw private key, None if the contact does not restrict its service
+///
+pub async fn set_remote_client_auth(onion_host: &str, key: Option<Vec<u8>>) -> Result<()> {
+    // Just making sure this is a valid x25519 key
+    if let Some(key) = &key {
+        client_auth_public_key(key)?;
+    }
+
+    StorageManager::modify_chat(onion_host, |chat| {
+        chat.rec_auth_key = key;
+        Ok(())
+    }).await?;
+
+    sync_client_auth().await
+}
+
+/// Writes the client authorization keys of every contact to the files tor reads and reloads tor.
+/// Should be called once the storage is unlocked, as tor can't read the keys before
+pub async fn sync_client_auth() -> Result<()> {
+    let (clients, services) = STORAGE.read().await.get_data(|e| {
+        let mut clients = Vec::new();
+        let mut services = Vec::new();
+
+        for (hostname, chat) in e.chats.iter() {
+            if let Some(key) = &chat.auth_key {
+                clients.push((hostname.clone(), client_auth_public_key(key)?));
+            }
+
+            if let Some(key) = &chat.rec_auth_key {
+                services.push((hostname.clone(), key.clone()));
+            }
+        }
+
+        Ok((clients, services))
+    }).await?;
+
+    debug!("Writing {} authorized clients and {} client authorization keys", clients.len(), services.len());
+    write_authorized_clients(&clients).await?;
+    write_client_auth(&services).await?;
+
+    reload_tor().await
+}
@@ -6,18 +6,17 @@ use actix_web::Either;
 use anyhow::{anyhow, Result};
 use async_channel::{Receiver, Sender};
 use log::{debug, error, info};
+use serde::Serialize;
 use payloads::{
-    event::AppHandleExt,
+    event::emit_payload,
     packets::{C2SPacket, S2CPacket},
     payloads::{WsClientStatus, WsClientUpdatePayload, WsMessageStatus},
 };
-use shared::{APP_HANDLE, util::now_millis};
-#[cfg(feature="dev")]
-use smol::block_on;
+use shared::util::now_millis;
 use storage_internal::{helpers::ChatStorageHelper, STORAGE};
-use tokio::sync::RwLock;
+use tokio::{sync::{Mutex, RwLock}, task::JoinHandle};
 
-use super::{MESSAGING, ConnectionReadThread};
+use super::{MESSAGING, ConnectionReadTask, QueuedPacket};
 
 /// This enum is used to store the connection info either from the client or the server
 #[derive(Debug)]
@@ -33,19 +32,36 @@ impl ConnInfo {
     pub fn get_receiver(&self) -> Either<Receiver<S2CPacket>, Receiver<C2SPacket>> {
         match self {
             ConnInfo::Client(c) => Either::Left(c.rx.clone()),
-            ConnInfo::Server((rx, _)) => Either::Right(rx.clone()),
+            ConnInfo::Server((rx, _, _)) => Either::Right(rx.clone()),
         }
     }
 }
 
+/// A snapshot of the state of a connection, used for diagnostics
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionState {
+    /// The host this connection is with
+    pub hostname: String,
+    /// Whether we connected to the receiver (or the receiver connected to us)
+    pub is_client: bool,
+    /// Whether the receiver has verified itself
+    pub verified: bool,
+    /// Whether we have verified ourselves to the receiver
+    pub self_verified: bool,
+}
+
 /// A generalized connection struct that can be used for both the client and the server
 #[derive(Debug, Clone)]
 pub struct Connection {
     pub(super) info: Arc<RwLock<ConnInfo>>,
-    read_thread: Arc<Option<ConnectionReadThread>>,
+    read_task: Arc<Option<ConnectionReadTask>>,
+    cover_task: Arc<Option<JoinHandle<()>>>,
     pub(crate) self_verified: Arc<RwLock<bool>>,
     pub(crate) verified: Arc<RwLock<bool>>,
     pub(super) receiver_host: String,
+    /// Packets moved over from another connection, held back until the peer knows its identity has been verified.
+    /// It would reject them before that. Always `None` for client connections and once released.
+    held_back: Arc<Mutex<Option<Vec<QueuedPacket>>>>,
 
     notifier_ready_tx: Sender<()>,
     notifier_ready_rx: Receiver<()>,
@@ -60,26 +76,18 @@ impl Connection {
         );
 
         // Sending the client update
-        let res = APP_HANDLE
-            .read()
-            .await
-            .as_ref()
-            .ok_or(anyhow!(
-                "Could not send client update, app handle not there"
-            ))
-            .and_then(|handle| {
-                #[cfg(feature="dev")]
-                block_on(block_on(STORAGE.read()).get_data(|e| {
-                    debug!("Current chats are: {:?}", e.chats);
-                    Ok(())
-                }))
-                .unwrap();
-                handle.emit_payload(WsClientUpdatePayload {
-                    hostname: self.receiver_host.clone(),
-                    status: WsClientStatus::Connected,
-                })?;
-                Ok(())
-            });
+        #[cfg(feature="dev")]
+        STORAGE.read().await.get_data(|e| {
+            debug!("Current chats are: {:?}", e.chats);
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        let res = emit_payload(WsClientUpdatePayload {
+            hostname: self.receiver_host.clone(),
+            status: WsClientStatus::Connected,
+        });
 
         if let Err(e) = res {
             error!("Could not send client update: {:?}", e);
@@ -102,6 +110,20 @@ impl Connection {
         Ok(())
     }
 
+    /// # Returns
+    ///
+    /// A snapshot of the current state of this connection
+    pub async fn get_state(&self) -> ConnectionState {
+        let is_client = matches!(*self.info.read().await, ConnInfo::Client(_));
+
+        ConnectionState {
+            hostname: self.receiver_host.clone(),
+            is_client,
+            verified: *self.verified.read().await,
+            self_verified: *self.self_verified.read().await,
+        }
+    }
+
     /// Creates a new generic connection
     ///
     /// # Arguments
@@ -114,20 +136,27 @@ impl Connection {
     /// The newly constructed connection
     async fn new_general(receiver_host: &str, info: ConnInfo) -> Self {
         let (tx, rx) = async_channel::unbounded();
+        let held_back = match info {
+            ConnInfo::Client(_) => None,
+            ConnInfo::Server(_) => Some(Vec::new()),
+        };
 
         let mut s = Self {
             info: Arc::new(RwLock::new(info)),
-            read_thread: Arc::new(None),
+            read_task: Arc::new(None),
+            cover_task: Arc::new(None),
             verified: Arc::new(RwLock::new(false)),
             self_verified: Arc::new(RwLock::new(false)),
             receiver_host: receiver_host.to_string(),
+            held_back: Arc::new(Mutex::new(held_back)),
 
             notifier_ready_tx: tx,
             notifier_ready_rx: rx,
         };
 
-        let read_thread = ConnectionReadThread::new(&s).await;
-        s.read_thread = Arc::new(Some(read_thread));
+        let read_task = ConnectionReadTask::new(&s).await;
+        s.read_task = Arc::new(Some(read_task));
+        s.cover_task = Arc::new(Some(s.spawn_cover_task()));
         s
     }
 
@@ -162,6 +191,121 @@ impl Connection {
         Self::new_general(receiver_host, ConnInfo::Server(c)).await
     }
 
+    /// Closes the connection. Both sides notice it and remove the connection afterwards.
+    /// As it is closed on purpose, the peer is not reconnected
+    pub async fn close(&self) -> Result<()> {
+        MESSAGING.read().await.set_active(&self.receiver_host, false).await;
+        self.shutdown().await
+    }
+
+    /// Closes the connection without changing whether the peer should be reconnected
+    pub(crate) async fn shutdown(&self) -> Result<()> {
+        match &*self.info.read().await {
+            ConnInfo::Client(c) => c.shutdown().await?,
+            // The ws actor stops as soon as its send channel is closed
+            ConnInfo::Server((_, s, _)) => {
+                s.close();
+            }
+        };
+
+        Ok(())
+    }
+
+    /// # Returns
+    ///
+    /// Whether the connection has been closed by either side
+    pub async fn is_closed(&self) -> bool {
+        match &*self.info.read().await {
+            ConnInfo::Client(c) => c.is_closed(),
+            ConnInfo::Server((_, s, _)) => s.is_closed(),
+        }
+    }
+
+    /// # Returns
+    ///
+    /// Whether both are handles to the same connection
+    pub(crate) fn is_same(&self, other: &Connection) -> bool {
+        Arc::ptr_eq(&self.info, &other.info)
+    }
+
+    /// # Arguments
+    ///
+    /// * `channels` - The channels of a ws actor
+    ///
+    /// # Returns
+    ///
+    /// Whether this is the connection of the ws actor with the given channels
+    pub(crate) async fn has_channels(&self, channels: &ServerChannels) -> bool {
+        match &*self.info.read().await {
+            ConnInfo::Server((_, s, _)) => s.same_channel(&channels.1),
+            ConnInfo::Client(_) => false,
+        }
+    }
+
+    /// Takes every packet that has been queued but not sent yet
+    ///
+    /// # Returns
+    ///
+    /// The taken packets, in the order they have been queued
+    pub(crate) async fn take_queued(&self) -> Vec<QueuedPacket> {
+        match &*self.info.read().await {
+            ConnInfo::Client(c) => c.take_queued().await,
+            ConnInfo::Server((_, _, s_rx)) => {
+                // Packets that are held back have been queued before the ones in the channel
+                let mut queued = self.held_back.lock().await.as_mut().map(std::mem::take).unwrap_or_default();
+                while let Ok(p) = s_rx.try_recv() {
+                    queued.extend(QueuedPacket::from_s2c(p));
+                }
+
+                queued
+            }
+        }
+    }
+
+    /// Queues the given packets to be sent over this connection
+    ///
+    /// # Arguments
+    ///
+    /// * `packets` - The packets taken from another connection to the same peer
+    ///
+    pub(crate) async fn send_queued(&self, packets: Vec<QueuedPacket>) -> Result<()> {
+        // The identity handshake of the accepted connection is not done yet, see `release_queued`
+        if let Some(held_back) = self.held_back.lock().await.as_mut() {
+            held_back.extend(packets);
+            return Ok(());
+        }
+
+        let info = self.info.read().await;
+        for p in packets {
+            match &*info {
+                ConnInfo::Client(c) => c.feed_packet(p.into_c2s()).await?,
+                ConnInfo::Server((_, s, _)) => s.send(p.into_s2c()).await?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sends the packets `send_queued` held back. Called by the ws actor once it told the peer that its identity is verified,
+    /// so the peer accepts them. They are sent in the background, the actor has to keep running to drain its send queue.
+    pub(crate) async fn release_queued(&self) {
+        let Some(packets) = self.held_back.lock().await.take() else {
+            return;
+        };
+
+        if packets.is_empty() {
+            return;
+        }
+
+        debug!("Sending {} packets held back for {}", packets.len(), self.receiver_host);
+        let conn = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = conn.send_queued(packets).await {
+                error!("Could not send held back packets to {}: {:?}", conn.receiver_host, e);
+            }
+        });
+    }
+
     /// Sends a message to the receiver
     ///
     /// # Arguments
@@ -206,7 +350,6 @@ impl Connection {
     /// Sends a message to the receiver with the given date and msg, internal function
     async fn inner_send(&self, msg: &str, date: u128) -> Result<()> {
         let raw = msg.as_bytes().to_vec();
-
         let tmp = self.receiver_host.clone();
         debug!("Reading public key for {}...", tmp);
 
@@ -233,7 +376,7 @@ impl Connection {
                 let packet = C2SPacket::Message((date, bin));
                 c.feed_packet(packet).await?;
             }
-            ConnInfo::Server((_, s)) => {
+            ConnInfo::Server((_, s, _)) => {
                 debug!("Server msg");
                 let packet = S2CPacket::Message((date, bin));
 
@@ -0,0 +1,155 @@
+use std::{fs, sync::RwLock, time::Duration};
+
+use anyhow::Result;
+use encryption::consts::RSA_KEY_SIZE;
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
+use openssl::rand::rand_bytes;
+use payloads::packets::{C2SPacket, S2CPacket};
+use serde::{Deserialize, Serialize};
+use shared::get_cover_traffic_path;
+use tokio::{task::JoinHandle, time::sleep};
+
+use super::{ConnInfo, Connection};
+
+lazy_static! {
+    /// The average interval dummy packets are sent in, cover traffic is disabled if None.
+    /// Read from the settings file once
+    static ref COVER_TRAFFIC: RwLock<Option<Duration>> = RwLock::new(read_cover_traffic());
+
+    /// How often to check whether cover traffic has been enabled
+    static ref COVER_TRAFFIC_POLL: Duration = Duration::from_secs(1);
+}
+
+/// The cover traffic setting as it is stored in `cover_traffic.json`
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CoverTrafficSettings {
+    /// The average interval in milliseconds, cover traffic is disabled if None
+    interval_ms: Option<u64>,
+}
+
+/// Reads the cover traffic setting from the settings file, cover traffic is disabled if there is none
+///
+/// # Returns
+///
+/// The stored interval
+fn read_cover_traffic() -> Option<Duration> {
+    let path = get_cover_traffic_path();
+    if !path.is_file() {
+        return None;
+    }
+
+    fs::read_to_string(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|raw| Ok(serde_json::from_str::<CoverTrafficSettings>(&raw)?))
+        .unwrap_or_else(|e| {
+            warn!("Could not read {:?}, cover traffic is disabled: {:?}", path, e);
+            CoverTrafficSettings::default()
+        })
+        .interval_ms
+        .filter(|ms| *ms > 0)
+        .map(Duration::from_millis)
+}
+
+/// Enables or disables cover traffic for every connection and stores the setting, so it is kept after restarting
+///
+/// # Arguments
+///
+/// * `interval` - The average interval to send dummy packets in, None to disable cover traffic
+///
+/// # Returns
+///
+/// Fails if the setting could not be stored
+pub fn set_cover_traffic(interval: Option<Duration>) -> Result<()> {
+    let interval = interval.filter(|i| !i.is_zero());
+    let settings = CoverTrafficSettings {
+        interval_ms: interval.map(|i| i.as_millis().try_into().unwrap_or(u64::MAX)),
+    };
+
+    fs::write(get_cover_traffic_path(), serde_json::to_string_pretty(&settings)?)?;
+    *COVER_TRAFFIC.write().unwrap() = interval;
+    info!("Updated cover traffic interval: {:?}", interval);
+
+    Ok(())
+}
+
+/// # Returns
+///
+/// The average interval dummy packets are sent in, None if cover traffic is disabled
+pub fn get_cover_traffic() -> Option<Duration> {
+    *COVER_TRAFFIC.read().unwrap()
+}
+
+/// # Returns
+///
+/// Random bytes, so the dummy packet is just as long as a message packet (an rsa block and its date)
+pub fn cover_payload() -> Vec<u8> {
+    let mut payload = vec![0; (*RSA_KEY_SIZE / 8) as usize + size_of::<u128>()];
+    if let Err(e) = rand_bytes(&mut payload) {
+        warn!("Could not create cover payload: {:?}", e);
+    }
+
+    payload
+}
+
+/// # Arguments
+///
+/// * `interval` - The average interval
+///
+/// # Returns
+///
+/// A random delay between half and one and a half of the interval, so dummy packets don't arrive periodically
+fn jittered(interval: Duration) -> Duration {
+    let mut rand = [0u8; 4];
+    let factor = match rand_bytes(&mut rand) {
+        Ok(_) => u32::from_le_bytes(rand) as f64 / u32::MAX as f64,
+        Err(_) => 0.5,
+    };
+
+    interval.div_f64(2.0) + interval.mul_f64(factor)
+}
+
+impl Connection {
+    /// Spawns the task sending dummy packets over this connection while cover traffic is enabled.
+    /// Stops once the connection has been closed
+    ///
+    /// # Returns
+    ///
+    /// The task handle
+    pub(super) fn spawn_cover_task(&self) -> JoinHandle<()> {
+        let conn = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let delay = get_cover_traffic().map(jittered).unwrap_or(*COVER_TRAFFIC_POLL);
+                sleep(delay).await;
+
+                if conn.is_closed().await {
+                    break;
+                }
+
+                // Dummy packets are authenticated with the session key, so we have to wait for the handshake
+                let verified = *conn.verified.read().await && *conn.self_verified.read().await;
+                if get_cover_traffic().is_none() || !verified {
+                    continue;
+                }
+
+                if let Err(e) = conn.send_cover().await {
+                    debug!("Could not send cover packet to {}: {:?}", conn.receiver_host, e);
+                }
+            }
+
+            debug!("Stopping cover traffic for {}", conn.receiver_host);
+        })
+    }
+
+    /// Sends a dummy packet, which the other side drops
+    async fn send_cover(&self) -> anyhow::Result<()> {
+        match &*self.info.read().await {
+            ConnInfo::Client(c) => c.feed_packet(C2SPacket::Cover(cover_payload())).await?,
+            ConnInfo::Server((_, s, _)) => s.send(S2CPacket::Cover(cover_payload())).await?,
+        };
+
+        Ok(())
+    }
+}
@@ -0,0 +1,311 @@
+use anyhow::{anyhow, Result};
+use encryption::{PrivateKey, PublicKey};
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
+use openssl::{
+    derive::Deriver,
+    hash::MessageDigest,
+    pkey::{Id, PKey, Private},
+    rand::rand_bytes,
+    sign::{Signer, Verifier},
+};
+use payloads::{
+    packets::{Hello, Identity},
+};
+use storage_internal::{helpers::{GetPrivateKey, ModifyChat}, StorageManager, STORAGE};
+use shared::onion::onion_public_key;
+use tor_proxy::service::{get_service_hostname, sign_as_service, verify_service_signature};
+
+use super::Session;
+
+lazy_static! {
+    /// The digest used to sign the handshake with the rsa keys
+    static ref HANDSHAKE_DIGEST: MessageDigest = MessageDigest::sha256();
+}
+
+/// The length of the nonces both sides challenge each other with
+const NONCE_LENGTH: usize = 32;
+/// The length of a raw x25519 public key
+const EPHEMERAL_KEY_LENGTH: usize = 32;
+
+/// One side of the handshake between client and server.
+/// Both sides send a hello with a fresh nonce and an ephemeral key, then each side signs both hellos.
+/// So an identity is only valid for a single session and can't be replayed.
+/// Once both hellos are known, the session key is derived from the ephemeral keys.
+#[derive(Debug)]
+pub struct Handshake {
+    /// Whether we dialed the other side
+    is_client: bool,
+    /// Our own onion hostname
+    own_hostname: String,
+    /// The onion hostname of the other side
+    remote_hostname: String,
+    /// Our hello, contains the challenge for the other side
+    own_hello: Hello,
+    /// The private part of the ephemeral key in our hello
+    ephemeral: PKey<Private>,
+    /// The hello of the other side, once received
+    remote_hello: Option<Hello>,
+}
+
+impl Handshake {
+    /// Starts the handshake with a server we dialed
+    ///
+    /// # Arguments
+    ///
+    /// * `remote_hostname` - The onion hostname of the server
+    ///
+    /// # Returns
+    ///
+    /// The handshake, its hello should be sent to the server
+    pub async fn new(remote_hostname: &str) -> Result<Self> {
+        Self::create(remote_hostname, true).await
+    }
+
+    /// Answers the hello of a client that dialed us
+    ///
+    /// # Arguments
+    ///
+    /// * `hello` - The hello the client sent
+    ///
+    /// # Returns
+    ///
+    /// The handshake, its hello should be sent back to the client
+    pub async fn accept(hello: Hello) -> Result<Self> {
+        let mut handshake = Self::create(&hello.hostname, false).await?;
+        handshake.set_remote(hello)?;
+
+        Ok(handshake)
+    }
+
+    /// Creates our side of the handshake with a fresh nonce and ephemeral key
+    async fn create(remote_hostname: &str, is_client: bool) -> Result<Self> {
+        // Nothing is signed for hosts that can't own an onion service
+        onion_public_key(remote_hostname)?;
+
+        let own_hostname = get_service_hostname(is_client)
+            .await?
+            .ok_or(anyhow!("Could not get own hostname"))?;
+
+        let mut nonce = vec![0; NONCE_LENGTH];
+        rand_bytes(&mut nonce)?;
+
+        let ephemeral = PKey::generate_x25519()?;
+        let own_hello = Hello {
+            hostname: own_hostname.clone(),
+            nonce,
+            ephemeral_key: ephemeral.raw_public_key()?,
+        };
+
+        Ok(Self {
+            is_client,
+            own_hostname,
+            remote_hostname: remote_hostname.to_string(),
+            own_hello,
+            ephemeral,
+            remote_hello: None,
+        })
+    }
+
+    /// # Returns
+    ///
+    /// Our hello, containing the challenge for the other side
+    pub fn hello(&self) -> Hello {
+        self.own_hello.clone()
+    }
+
+    /// Sets the hello the other side answered with
+    ///
+    /// # Arguments
+    ///
+    /// * `hello` - The hello of the other side
+    ///
+    /// # Returns
+    ///
+    /// Fails if there already is a hello or it is not the one of the expected side
+    pub fn set_remote(&mut self, hello: Hello) -> Result<()> {
+        if self.remote_hello.is_some() {
+            return Err(anyhow!("Already got the hello of {}", self.remote_hostname));
+        }
+
+        if hello.hostname != self.remote_hostname {
+            return Err(anyhow!(
+                "Expected the hello of {} but got the one of {}",
+                self.remote_hostname,
+                hello.hostname
+            ));
+        }
+
+        if hello.nonce.len() != NONCE_LENGTH || hello.ephemeral_key.len() != EPHEMERAL_KEY_LENGTH {
+            return Err(anyhow!("Invalid hello of {}", hello.hostname));
+        }
+
+        self.remote_hello = Some(hello);
+        Ok(())
+    }
+
+    /// # Returns
+    ///
+    /// The hello of the other side, fails if it has not been received yet
+    fn remote_hello(&self) -> Result<&Hello> {
+        self.remote_hello
+            .as_ref()
+            .ok_or(anyhow!("No hello of {} yet", self.remote_hostname))
+    }
+
+    /// # Returns
+    ///
+    /// Both hellos, the one of the client first so both sides get the same
+    fn transcript(&self) -> Result<Vec<u8>> {
+        let remote = self.remote_hello()?;
+        let (client, server) = if self.is_client {
+            (&self.own_hello, remote)
+        } else {
+            (remote, &self.own_hello)
+        };
+
+        let mut data = b"enkrypton-handshake".to_vec();
+        for hello in [client, server] {
+            data.extend((hello.hostname.len() as u64).to_le_bytes());
+            data.extend(hello.hostname.as_bytes());
+            data.extend(&hello.nonce);
+            data.extend(&hello.ephemeral_key);
+        }
+
+        Ok(data)
+    }
+
+    /// The data an identity signs: the transcript, the signing side and the public key it sends along
+    ///
+    /// # Arguments
+    ///
+    /// * `signer` - The onion hostname of the signing side
+    /// * `pub_key` - The public key of the signing side
+    ///
+    /// # Returns
+    ///
+    /// The data to sign
+    fn signed_data(&self, signer: &str, pub_key: &PublicKey) -> Result<Vec<u8>> {
+        let mut data = self.transcript()?;
+        data.extend(signer.as_bytes());
+        data.push(0);
+        data.extend(pub_key.0.public_key_to_der()?);
+
+        Ok(data)
+    }
+
+    /// Creates our identity for this handshake, signed with the rsa key for the other side and our onion service key
+    ///
+    /// # Returns
+    ///
+    /// The identity to send to the other side
+    pub async fn identity(&self) -> Result<Identity> {
+        // Get the private key for the receiver (used to decrypt messages)
+        let priv_key: PrivateKey = StorageManager::get_or_create_private_key(&self.remote_hostname).await?;
+        let pub_key: PublicKey = priv_key.clone().try_into()?;
+
+        let data = self.signed_data(&self.own_hostname, &pub_key)?;
+
+        // Signing the handshake with the key for the receiver
+        let keypair = PKey::from_rsa(priv_key.rsa()?)?;
+        let mut signer = Signer::new(*HANDSHAKE_DIGEST, &keypair)?;
+        signer.update(&data)?;
+        let signature = signer.sign_to_vec()?;
+
+        // Proving that we own the onion address
+        let onion_signature = sign_as_service(&data).await?;
+
+        Ok(Identity {
+            hostname: self.own_hostname.clone(),
+            signature,
+            pub_key,
+            onion_signature,
+        })
+    }
+
+    /// Verifies the identity of the other side.
+    /// If we don't know the other side yet, its public key is stored with a new chat
+    ///
+    /// # Arguments
+    ///
+    /// * `identity` - The identity the other side sent
+    ///
+    /// # Returns
+    ///
+    /// Fails if the identity is not valid for this handshake
+    pub async fn verify(&self, identity: &Identity) -> Result<()> {
+        let Identity { hostname: remote_host, pub_key, signature, onion_signature } = identity;
+        if *remote_host != self.remote_hostname {
+            return Err(anyhow!("Expected the identity of {} but got the one of {}", self.remote_hostname, remote_host));
+        }
+
+        let data = self.signed_data(remote_host, pub_key)?;
+
+        // The rsa key may be new, so checking that the remote side actually owns its onion address
+        if let Err(e) = verify_service_signature(remote_host, &data, onion_signature) {
+            warn!("[INVALID_ONION_SIGNATURE] {:?}. This may be an attack!", e);
+            return Err(anyhow!("{} could not prove that it owns its onion address", remote_host));
+        }
+
+        debug!("Reading to verify...");
+        // Check if there is a public key for the given receiver
+        let (local_pub_key, pinned) = STORAGE.read().await.get_data(|e| {
+            let chat = e.chats.get(remote_host);
+            let key = chat.and_then(|e| e.rec_pub_key.clone());
+            let pinned = chat.and_then(|e| e.rec_key_fingerprint.clone());
+
+            Ok((key, pinned))
+        }).await?;
+
+        // The key we know the receiver by, or the new one it just sent us
+        let is_new = local_pub_key.is_none();
+        if is_new
+            && let Some(pinned) = pinned
+            && pub_key.fingerprint()? != pinned
+        {
+            warn!("[PINNED_KEY_MISMATCH] {} did not use the key of its contact card. This may be an attack!", remote_host);
+            return Err(anyhow!("{} did not use the key of its contact card", remote_host));
+        }
+
+        let verify_key = local_pub_key.unwrap_or_else(|| pub_key.clone());
+
+        info!("Verifying for hostname: {:?}", remote_host);
+        let keypair = PKey::from_rsa(verify_key.0)?;
+        let mut verifier = Verifier::new(*HANDSHAKE_DIGEST, &keypair)?;
+
+        verifier.update(&data)?;
+        if !verifier.verify(signature)? {
+            warn!("[INVALID_SIGNATURE] Wrong signature was given! This may be an attack!");
+            return Err(anyhow!("Wrong signature was given! This may be an attack!"));
+        }
+
+        if is_new {
+            // Adding public key to storage because it does not exist
+            StorageManager::modify_chat(remote_host, |res| {
+                res.rec_pub_key = Some(pub_key.clone());
+
+                Ok(())
+            }).await?;
+            debug!("Done.");
+        }
+
+        Ok(())
+    }
+
+    /// Derives the key of this session from both ephemeral keys
+    ///
+    /// # Returns
+    ///
+    /// The session, fails if the hello of the other side is missing
+    pub fn session(&self) -> Result<Session> {
+        let remote = self.remote_hello()?;
+        let remote_key = PKey::public_key_from_raw_bytes(&remote.ephemeral_key, Id::X25519)?;
+
+        let mut deriver = Deriver::new(&self.ephemeral)?;
+        deriver.set_peer(&remote_key)?;
+        let shared = deriver.derive_to_vec()?;
+
+        // Binding the key to both hellos
+        Session::derive(&shared, &self.transcript()?)
+    }
+}
@@ -2,23 +2,25 @@ use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use anyhow::{anyhow, Result};
 use lazy_static::lazy_static;
-use log::{info, debug};
+use log::{info, debug, warn};
 use payloads::{
-    event::AppHandleExt,
-    payloads::{WsMessageStatus, WsMessageStatusPayload},
+    event::emit_payload,
+    payloads::{PeerState, WsClientStatus, WsClientUpdatePayload, WsMessageStatus, WsMessageStatusPayload},
 };
-use shared::get_app;
 use storage_internal::STORAGE;
 use tokio::sync::RwLock;
+use tor_proxy::supervisor::wait_until_ready;
 
-use crate::client::MessagingClient;
+use crate::{client::MessagingClient, transport::get_transport};
 
-use super::Connection;
+use super::{Connection, ConnectionState, Peer};
 
 /// A Manager which holds the connections by receiver name
 pub struct MessagingManager {
     /// The connections by receiver name
     pub(crate) connections: Arc<RwLock<HashMap<String, Connection>>>,
+    /// The state of every peer we have been connected to (or tried to), by receiver name
+    pub(crate) peers: Arc<RwLock<HashMap<String, Peer>>>,
 }
 
 lazy_static! {
@@ -29,6 +31,8 @@ lazy_static! {
     pub static ref HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(25);
     // The interval for the heartbeat
     pub static ref HEARTBEAT: Duration = HEARTBEAT_TIMEOUT.div_f32(2.0);
+    // How long to wait for tor to be ready (e.g. while it is restarting) before a connection fails
+    pub static ref TOR_READY_TIMEOUT: Duration = Duration::from_secs(30);
 }
 
 impl MessagingManager {
@@ -41,10 +45,12 @@ impl MessagingManager {
     fn new() -> Self {
         MessagingManager {
             connections: Arc::new(RwLock::new(HashMap::new())),
+            peers: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
-    /// Connects to the given onion host name and adds the connection to the connections map
+    /// Connects to the given onion host name and adds the connection to the connections map.
+    /// Schedules a reconnect if the peer is active and connecting failed
     ///
     /// # Arguments
     ///
@@ -53,21 +59,50 @@ impl MessagingManager {
     /// # Returns
     ///
     /// The result of the connection
-    async fn connect(&self, onion_hostname: &str) -> Result<()> {
+    pub(crate) async fn connect(&self, onion_hostname: &str) -> Result<()> {
+        self.set_peer_state(onion_hostname, PeerState::Connecting).await;
+
+        let res = self.dial(onion_hostname).await;
+        if res.is_err() {
+            self.schedule_reconnect(onion_hostname).await;
+        }
+
+        res
+    }
+
+    /// Dials the given onion host name and adds the connection to the connections map
+    ///
+    /// # Arguments
+    ///
+    /// * `onion_hostname` - The hostname to dial
+    ///
+    /// # Returns
+    ///
+    /// The result of the connection
+    async fn dial(&self, onion_hostname: &str) -> Result<()> {
+        // Connections are paused while tor is restarting
+        if get_transport().uses_tor(onion_hostname) {
+            wait_until_ready(*TOR_READY_TIMEOUT).await?;
+        }
         let client = MessagingClient::new(&onion_hostname).await?;
 
         info!("[CLIENT]: New Connection for {}", onion_hostname);
+        self.set_peer_state(onion_hostname, PeerState::Handshaking).await;
 
         let conn = Connection::new_client(onion_hostname, client).await;
-        self.connections
-            .write()
-            .await
-            .insert(onion_hostname.to_string(), conn);
+        let kept = self.insert_connection(onion_hostname, conn.clone()).await?;
+
+        // The peer dialed us at the same time and its connection is kept
+        let state = kept.get_state().await;
+        if !kept.is_same(&conn) && state.verified && state.self_verified {
+            self.set_peer_state(onion_hostname, PeerState::Connected).await;
+        }
 
         Ok(())
     }
 
-    /// Gets a connection by receiver name or connects if it doesn't exist yet
+    /// Gets a connection by receiver name or connects if it doesn't exist yet.
+    /// The peer is marked as active, so it is reconnected if the connection drops
     ///
     /// # Arguments
     ///
@@ -77,6 +112,7 @@ impl MessagingManager {
     ///
     /// The connection
     pub async fn get_or_connect(&self, onion_host: &str) -> Result<Connection> {
+        self.set_active(onion_host, true).await;
         if !self.connections.read().await.contains_key(onion_host) {
             self.connect(&onion_host).await?;
         }
@@ -124,6 +160,7 @@ impl MessagingManager {
         let verified = self.assert_verified(onion_host).await;
 
         if let Ok(c) = verified {
+            self.set_peer_state(onion_host, PeerState::Connected).await;
             c.notify_verified().await?;
         }
 
@@ -177,14 +214,85 @@ impl MessagingManager {
         self.connections.read().await.contains_key(onion_host)
     }
 
-    /// Removes the connection with the given host name
+    /// # Returns
+    ///
+    /// The state of every connection that is currently open
+    pub async fn get_connection_states(&self) -> Vec<ConnectionState> {
+        let connections: Vec<Connection> = self.connections.read().await.values().cloned().collect();
+
+        let mut states = Vec::with_capacity(connections.len());
+        for conn in connections {
+            states.push(conn.get_state().await);
+        }
+
+        states
+    }
+
+    /// Removes the connection with the given host name, active peers are reconnected afterwards
     ///
     /// # Arguments
     ///
     /// * `onion_host` - The receiver to remove the connection from
     ///
     pub async fn remove_connection(&self, onion_host: &str) {
-        self.connections.write().await.remove(onion_host);
+        let removed = self.connections.write().await.remove(onion_host);
+        if removed.is_some() {
+            self.schedule_reconnect(onion_host).await;
+        }
+    }
+
+    /// Closes every connection without scheduling reconnects, used before the storage is locked
+    /// as messages could neither be decrypted nor stored afterwards.
+    /// Active peers stay active, so `reconnect_active` can dial them again once the storage is unlocked
+    pub async fn close_all(&self) {
+        // Removing them first, so closing them does not schedule a reconnect
+        let connections: Vec<Connection> = self.connections.write().await.drain().map(|(_, c)| c).collect();
+
+        for conn in connections {
+            if let Err(e) = conn.shutdown().await {
+                warn!("Could not close connection to {}: {:?}", conn.receiver_host, e);
+            }
+
+            // The read task does not emit this, as the connection has been removed already
+            let _ = emit_payload(WsClientUpdatePayload {
+                hostname: conn.receiver_host.clone(),
+                status: WsClientStatus::Disconnected,
+            })
+            .map_err(|e| warn!("Could not emit ws client update: {:?}", e));
+        }
+
+        // Pending reconnects are cancelled as well
+        let hosts: Vec<String> = self.peers.read().await.keys().cloned().collect();
+        for host in hosts {
+            self.set_peer_state(&host, PeerState::Idle).await;
+        }
+    }
+
+    /// Removes the given connection, if it has not been replaced by another one to the same peer already.
+    /// Active peers are reconnected afterwards
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - The connection that has been closed
+    ///
+    /// # Returns
+    ///
+    /// Whether the connection has been removed
+    pub(crate) async fn remove_if_current(&self, conn: &Connection) -> bool {
+        let mut connections = self.connections.write().await;
+        let is_current = connections
+            .get(&conn.receiver_host)
+            .is_some_and(|c| c.is_same(conn));
+
+        if !is_current {
+            return false;
+        }
+
+        connections.remove(&conn.receiver_host);
+        drop(connections);
+
+        self.schedule_reconnect(&conn.receiver_host).await;
+        true
     }
 
     /// Setting the new msg status and updates the storage/frontend
@@ -225,7 +333,7 @@ impl MessagingManager {
             .await?;
 
         debug!("Sending message status update to client Hostname: {}, Date: {}, Status: {:?}", onion_host, date, status);
-        get_app().await.emit_payload(WsMessageStatusPayload {
+        emit_payload(WsMessageStatusPayload {
             hostname: onion_host.to_string(),
             date,
             status,
@@ -1,9 +1,26 @@
-mod traits;
 mod manager;
 mod connection;
-mod receive_thread;
+mod receive_task;
+mod peers;
+mod queued;
+mod tie_break;
+mod handshake;
+mod session;
+mod replay;
+mod padding;
+mod cover;
+mod client_auth;
 
 pub use connection::*;
 pub use manager::*;
-pub use traits::*;
-pub use receive_thread::*;
\ No newline at end of file
+pub use handshake::Handshake;
+pub use session::Session;
+pub use replay::*;
+pub use padding::*;
+pub use cover::{cover_payload, get_cover_traffic, set_cover_traffic};
+pub use receive_task::*;
+pub use client_auth::*;
+pub use peers::{backoff_delay, RECONNECT_BASE_DELAY, RECONNECT_MAX_DELAY};
+pub(crate) use peers::Peer;
+pub(crate) use queued::QueuedPacket;
+pub use tie_break::keeps_dialed;
\ No newline at end of file
@@ -0,0 +1,49 @@
+use anyhow::{anyhow, Result};
+
+/// The bytes in front of every padded packet, containing its length
+const LENGTH_PREFIX: usize = size_of::<u32>();
+
+/// Every authenticated packet is padded to a multiple of this size before it is sealed.
+/// Messages, acknowledgements and cover packets all fit into one block, so they can't be told apart by their size on the wire
+pub const PADDED_PACKET_SIZE: usize = 1024;
+
+/// Pads the serialized packet to the next multiple of `PADDED_PACKET_SIZE`
+///
+/// # Arguments
+///
+/// * `packet` - The serialized packet to pad
+///
+/// # Returns
+///
+/// The length of the packet, the packet and zeros up to the padded size. Fails if the packet is too long
+pub fn pad_packet(packet: &[u8]) -> Result<Vec<u8>> {
+    let len = u32::try_from(packet.len()).map_err(|_| anyhow!("Packet is too long ({} bytes)", packet.len()))?;
+    let size = (packet.len() + LENGTH_PREFIX).div_ceil(PADDED_PACKET_SIZE) * PADDED_PACKET_SIZE;
+
+    let mut padded = Vec::with_capacity(size);
+    padded.extend(len.to_le_bytes());
+    padded.extend(packet);
+    padded.resize(size, 0);
+
+    Ok(padded)
+}
+
+/// Removes the padding of a packet
+///
+/// # Arguments
+///
+/// * `padded` - The packet, padded by `pad_packet`
+///
+/// # Returns
+///
+/// The serialized packet, fails if the padding is invalid
+pub fn unpad_packet(padded: &[u8]) -> Result<&[u8]> {
+    if padded.is_empty() || !padded.len().is_multiple_of(PADDED_PACKET_SIZE) {
+        return Err(anyhow!("Packet has not been padded ({} bytes)", padded.len()));
+    }
+
+    let len = u32::from_le_bytes([padded[0], padded[1], padded[2], padded[3]]) as usize;
+    padded
+        .get(LENGTH_PREFIX..LENGTH_PREFIX + len)
+        .ok_or(anyhow!("Invalid length of padded packet"))
+}
@@ -0,0 +1,220 @@
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
+use openssl::rand::rand_bytes;
+use payloads::{
+    event::emit_payload,
+    payloads::{PeerState, PeerStatePayload},
+};
+use tokio::time::sleep;
+
+use super::{MessagingManager, MESSAGING};
+
+lazy_static! {
+    /// The delay before the first reconnect attempt, doubled with every failed attempt
+    pub static ref RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+    /// The longest delay between two reconnect attempts
+    pub static ref RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+}
+
+/// What the manager knows about a single peer, whether connected or not
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Peer {
+    /// The current state of the connection
+    pub state: PeerState,
+    /// Whether we want to be connected to this peer (so we dialed it), only active peers are reconnected
+    pub active: bool,
+    /// How many connection attempts failed in a row
+    pub attempts: u32,
+}
+
+/// Calculates how long to wait before the given attempt.
+/// Half of the delay is random, so peers that dropped at the same time don't all retry at once
+///
+/// # Arguments
+///
+/// * `attempt` - The number of the attempt, starting at 1
+///
+/// # Returns
+///
+/// The delay before the attempt
+pub fn backoff_delay(attempt: u32) -> Duration {
+    let exp = RECONNECT_BASE_DELAY.saturating_mul(1 << attempt.saturating_sub(1).min(16));
+    let delay = exp.min(*RECONNECT_MAX_DELAY);
+
+    let mut rand = [0u8; 4];
+    let jitter = match rand_bytes(&mut rand) {
+        Ok(_) => u32::from_le_bytes(rand) as f64 / u32::MAX as f64,
+        Err(_) => 0.5,
+    };
+
+    delay.div_f64(2.0) + delay.div_f64(2.0).mul_f64(jitter)
+}
+
+impl MessagingManager {
+    /// # Arguments
+    ///
+    /// * `onion_host` - The peer to get the state of
+    ///
+    /// # Returns
+    ///
+    /// The current state of the connection to the given peer
+    pub async fn get_peer_state(&self, onion_host: &str) -> PeerState {
+        self.peers
+            .read()
+            .await
+            .get(onion_host)
+            .map(|p| p.state.clone())
+            .unwrap_or_default()
+    }
+
+    /// Sets whether we want to stay connected to the given peer.
+    /// Active peers are reconnected automatically once their connection drops
+    ///
+    /// # Arguments
+    ///
+    /// * `onion_host` - The peer to (de)activate
+    /// * `active` - Whether the peer should be reconnected
+    ///
+    pub async fn set_active(&self, onion_host: &str, active: bool) {
+        let mut peers = self.peers.write().await;
+        let peer = peers.entry(onion_host.to_string()).or_default();
+        peer.active = active;
+
+        // A pending reconnect is not needed anymore
+        let stop_backoff = !active && matches!(peer.state, PeerState::Backoff { .. });
+        drop(peers);
+
+        if stop_backoff {
+            self.set_peer_state(onion_host, PeerState::Idle).await;
+        }
+    }
+
+    /// Moves the given peer to a new state and tells the listeners about it
+    ///
+    /// # Arguments
+    ///
+    /// * `onion_host` - The peer whose state changed
+    /// * `state` - The new state
+    ///
+    pub(crate) async fn set_peer_state(&self, onion_host: &str, state: PeerState) {
+        let mut peers = self.peers.write().await;
+        let peer = peers.entry(onion_host.to_string()).or_default();
+        if peer.state == state {
+            return;
+        }
+
+        if state == PeerState::Connected {
+            peer.attempts = 0;
+        }
+
+        let previous = std::mem::replace(&mut peer.state, state.clone());
+        drop(peers);
+
+        debug!("Peer {} changed from {:?} to {:?}", onion_host, previous, state);
+        let _ = emit_payload(PeerStatePayload {
+            hostname: onion_host.to_string(),
+            previous,
+            state,
+        })
+        .map_err(|e| warn!("Could not emit peer state update: {:?}", e));
+    }
+
+    /// Called once the connection to a peer failed or dropped.
+    /// Active peers are dialed again after a jittered backoff, every other peer goes idle
+    ///
+    /// # Arguments
+    ///
+    /// * `onion_host` - The peer that has been disconnected
+    ///
+    pub(crate) async fn schedule_reconnect(&self, onion_host: &str) {
+        let mut peers = self.peers.write().await;
+        let peer = peers.entry(onion_host.to_string()).or_default();
+        if !peer.active {
+            drop(peers);
+            self.set_peer_state(onion_host, PeerState::Idle).await;
+            return;
+        }
+
+        peer.attempts += 1;
+        let attempt = peer.attempts;
+        drop(peers);
+
+        let delay = backoff_delay(attempt);
+        info!("Reconnecting to {} in {:?} (attempt {})", onion_host, delay, attempt);
+        self.set_peer_state(
+            onion_host,
+            PeerState::Backoff {
+                attempt,
+                retry_in_ms: delay.as_millis() as u64,
+            },
+        )
+        .await;
+
+        let onion_host = onion_host.to_string();
+        tokio::spawn(async move {
+            sleep(delay).await;
+            MESSAGING.read().await.reconnect(&onion_host, attempt).await;
+        });
+    }
+
+    /// Dials every active peer that is not connected in the background, used after the storage has been unlocked
+    pub async fn reconnect_active(&self) {
+        let hosts: Vec<String> = self
+            .peers
+            .read()
+            .await
+            .iter()
+            .filter(|(_, p)| p.active && p.state == PeerState::Idle)
+            .map(|(host, _)| host.clone())
+            .collect();
+
+        for host in hosts {
+            info!("Reconnecting to {} after unlocking", host);
+            tokio::spawn(async move {
+                // Failing schedules the next attempt
+                if let Err(e) = MESSAGING.read().await.connect(&host).await {
+                    warn!("Could not reconnect to {}: {:?}", host, e);
+                }
+            });
+        }
+    }
+
+    /// Dials the given peer again, if nothing happened since the reconnect has been scheduled
+    ///
+    /// # Arguments
+    ///
+    /// * `onion_host` - The peer to reconnect to
+    /// * `attempt` - The attempt this reconnect has been scheduled for
+    ///
+    /// # Returns
+    ///
+    /// The boxed future, as connecting may schedule another reconnect
+    fn reconnect<'a>(&'a self, onion_host: &'a str, attempt: u32) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            {
+                let peers = self.peers.read().await;
+                let Some(peer) = peers.get(onion_host) else {
+                    return;
+                };
+
+                // Someone else connected meanwhile or the peer has been deactivated
+                let scheduled = matches!(peer.state, PeerState::Backoff { attempt: a, .. } if a == attempt);
+                if !scheduled || !peer.active {
+                    return;
+                }
+            }
+
+            if self.is_connected(onion_host).await {
+                return;
+            }
+
+            // Failing schedules the next attempt
+            if let Err(e) = self.connect(onion_host).await {
+                warn!("Could not reconnect to {}: {:?}", onion_host, e);
+            }
+        })
+    }
+}
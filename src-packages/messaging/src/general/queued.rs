@@ -0,0 +1,57 @@
+use payloads::packets::{C2SPacket, S2CPacket};
+
+/// A packet that has been queued but not sent yet.
+/// Not tied to a side of the connection, so it can be moved to another connection to the same peer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum QueuedPacket {
+    /// An encrypted message with its date
+    Message((u128, Vec<u8>)),
+    /// The message with the given date has been received
+    MessageReceived(u128),
+    /// The message with the given date could not be received
+    MessageFailed(u128),
+}
+
+impl QueuedPacket {
+    /// # Returns
+    ///
+    /// The packet, if it has to be moved to another connection (handshake packets do not)
+    pub fn from_c2s(packet: &C2SPacket) -> Option<Self> {
+        match packet {
+            C2SPacket::Message(msg) => Some(QueuedPacket::Message(msg.clone())),
+            C2SPacket::MessageReceived(date) => Some(QueuedPacket::MessageReceived(*date)),
+            C2SPacket::MessageFailed(date) => Some(QueuedPacket::MessageFailed(*date)),
+            _ => None,
+        }
+    }
+
+    /// # Returns
+    ///
+    /// The packet, if it has to be moved to another connection (handshake packets do not)
+    pub fn from_s2c(packet: S2CPacket) -> Option<Self> {
+        match packet {
+            S2CPacket::Message(msg) => Some(QueuedPacket::Message(msg)),
+            S2CPacket::MessageReceived(date) => Some(QueuedPacket::MessageReceived(date)),
+            S2CPacket::MessageFailed(date) => Some(QueuedPacket::MessageFailed(date)),
+            _ => None,
+        }
+    }
+
+    /// Converts this packet to be sent by a client
+    pub fn into_c2s(self) -> C2SPacket {
+        match self {
+            QueuedPacket::Message(msg) => C2SPacket::Message(msg),
+            QueuedPacket::MessageReceived(date) => C2SPacket::MessageReceived(date),
+            QueuedPacket::MessageFailed(date) => C2SPacket::MessageFailed(date),
+        }
+    }
+
+    /// Converts this packet to be sent by the server
+    pub fn into_s2c(self) -> S2CPacket {
+        match self {
+            QueuedPacket::Message(msg) => S2CPacket::Message(msg),
+            QueuedPacket::MessageReceived(date) => S2CPacket::MessageReceived(date),
+            QueuedPacket::MessageFailed(date) => S2CPacket::MessageFailed(date),
+        }
+    }
+}
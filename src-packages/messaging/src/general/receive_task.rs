@@ -1,33 +1,30 @@
-use std::{
-    sync::Arc,
-    thread::{self, JoinHandle},
-};
+use std::sync::Arc;
 
 use actix_web::Either;
 use anyhow::{anyhow, Result};
+use encryption::SecretString;
 use log::{debug, error, warn};
 use payloads::{
-    event::AppHandleExt,
+    event::emit_payload,
     packets::{C2SPacket, S2CPacket},
     payloads::{WsMessagePayload, WsMessageStatus, WsClientUpdatePayload, WsClientStatus},
 };
-use shared::get_app;
-use smol::block_on;
 use storage_internal::{helpers::ChatStorageHelper, STORAGE};
-use tokio::sync::RwLock;
+use tokio::{sync::RwLock, task::JoinHandle};
+use zeroize::Zeroizing;
 
 use super::{ConnInfo, Connection, MESSAGING};
 
-/// A thread which reads messages incoming from the specific handlers such as `ws_manager` and `MessagingClient`
+/// A task which reads messages incoming from the specific handlers such as `ws_manager` and `MessagingClient`
 #[derive(Debug)]
-pub struct ConnectionReadThread {
-    /// The thread handle that was spawned
-    pub read_thread: JoinHandle<()>,
+pub struct ConnectionReadTask {
+    /// The task handle that was spawned
+    pub read_task: JoinHandle<()>,
 }
 
-impl ConnectionReadThread {
-    /// Creates a new read thread for the given connection.
-    /// This will spawn a new thread and read from the given connection.
+impl ConnectionReadTask {
+    /// Creates a new read task for the given connection.
+    /// This will spawn a new task and read from the given connection.
     /// Incoming messages are received and handled by the `handle` function.
     ///
     /// # Arguments
@@ -36,11 +33,11 @@ impl ConnectionReadThread {
     ///
     /// # Returns
     ///
-    /// The constructed read thread
+    /// The constructed read task
     pub async fn new(conn: &Connection) -> Self {
         let handle = Self::spawn(conn).await;
         Self {
-            read_thread: handle,
+            read_task: handle,
         }
     }
 
@@ -55,30 +52,29 @@ impl ConnectionReadThread {
     /// # Returns
     ///
     /// The decrypted message, fails if we cannot decrypt it
-    pub async fn handle_inner(date: u128, msg: Vec<u8>, receiver_host: &str) -> Result<String> {
+    pub async fn handle_inner(date: u128, msg: Vec<u8>, receiver_host: &str) -> Result<SecretString> {
         debug!("Reading conn for {}...", receiver_host);
-        let storage = block_on(STORAGE.read());
-
-        let priv_key = storage.get_data(|e| {
-            e.chats
-                .get(receiver_host)
-                .and_then(|e| Some(e.priv_key.clone()))
-                .ok_or(anyhow!("The private key was empty (should never happen)"))
-        });
-
-        debug!("Getting res future");
-
-        let priv_key = block_on(priv_key)?;
+        let priv_key = STORAGE
+            .read()
+            .await
+            .get_data(|e| {
+                e.chats
+                    .get(receiver_host)
+                    .map(|e| e.priv_key.clone())
+                    .ok_or(anyhow!("The private key was empty (should never happen)"))
+            })
+            .await?;
         debug!("Done");
-        drop(storage);
 
-        let msg = priv_key.decrypt(&msg)?;
-        let msg = String::from_utf8(msg)?;
+        // The plaintext is only kept in locked memory, the decrypted buffer is wiped right away
+        let decrypted = Zeroizing::new(priv_key.decrypt(&msg)?);
+        let msg = SecretString::from(std::str::from_utf8(&decrypted)?);
+        drop(decrypted);
 
         #[cfg(feature="dev")]
         debug!(
             "Received message: {}, Sending payload with receiver {}",
-            msg, receiver_host
+            msg.expose(), receiver_host
         );
 
         STORAGE
@@ -134,7 +130,7 @@ impl ConnectionReadThread {
                     let packet = C2SPacket::MessageReceived(date);
                     c.feed_packet(packet).await?;
                 }
-                ConnInfo::Server((_, s)) => {
+                ConnInfo::Server((_, s, _)) => {
                     debug!("Server msg");
                     let packet = S2CPacket::MessageReceived(date);
 
@@ -143,10 +139,9 @@ impl ConnectionReadThread {
             };
 
             // Emitting payload to update frontend
-            let handle = get_app().await;
-            handle.emit_payload(WsMessagePayload {
+            emit_payload(WsMessagePayload {
                 receiver: receiver_host.to_string(),
-                message: msg.to_string(),
+                message: msg.expose().to_string(),
             })?;
         }
 
@@ -154,11 +149,17 @@ impl ConnectionReadThread {
             // Setting the status to failed and sending a failed status to the other side
 
             error!("Could not handle message: {:?}", e);
-            MESSAGING
+
+            // The message is usually not stored if it could not be decrypted, so there might be nothing to update.
+            // The other side still has to be told about it though
+            let res = MESSAGING
                 .read()
                 .await
                 .set_msg_status(&receiver_host, date, WsMessageStatus::Failed)
-                .await?;
+                .await;
+            if let Err(e) = res {
+                debug!("Could not set status of failed message: {:?}", e);
+            }
 
             // Notifies the other side that receiving the message failed
             match &*info.read().await {
@@ -167,7 +168,7 @@ impl ConnectionReadThread {
                     let packet = C2SPacket::MessageFailed(date);
                     c.feed_packet(packet).await?;
                 }
-                ConnInfo::Server((_, s)) => {
+                ConnInfo::Server((_, s, _)) => {
                     debug!("Server msg");
                     let packet = S2CPacket::MessageFailed(date);
 
@@ -179,7 +180,8 @@ impl ConnectionReadThread {
         Ok(())
     }
 
-    /// Spawns a new thread which reads from the given connection
+    /// Spawns a new task which reads from the given connection
+    /// until the handler (client or ws actor) closes its channel
     ///
     /// # Arguments
     ///
@@ -187,78 +189,71 @@ impl ConnectionReadThread {
     ///
     /// # Returns
     ///
-    /// The thread handle
+    /// The task handle
     pub async fn spawn(conn: &Connection) -> JoinHandle<()> {
         let info_read = conn.info.clone();
         let receiver = info_read.read().await.get_receiver();
 
         let receiver_host = conn.receiver_host.clone();
-        // Spawns the new read thread
-        thread::Builder::new()
-            .name(format!("conn-reader-{}", receiver_host))
-            .spawn(move || {
-                loop {
-                    let msg = match &receiver {
-                        Either::Left(r) => {
-                            // Reads the message from the receiver
-                            let msg = block_on(r.recv());
-                            if let Err(e) = msg {
-                                error!("Could not read packet: {:?}", e);
-                                break;
-                            }
+        let conn = conn.clone();
+        // Spawns the new read task
+        tokio::spawn(async move {
+            loop {
+                let msg = match &receiver {
+                    Either::Left(r) => {
+                        // Reads the message from the receiver, fails once the channel is closed
+                        let msg = r.recv().await;
+                        if msg.is_err() {
+                            debug!("Channel of {} closed, stopping", receiver_host);
+                            break;
+                        }
 
-                            // Handle the message and store it
-                            match msg.unwrap() {
-                                S2CPacket::Message(msg) => Some(msg),
-                                _ => {
-                                    warn!("Main Manager received message it could not handle");
-                                    None
-                                }
+                        // Handle the message and store it
+                        match msg.unwrap() {
+                            S2CPacket::Message(msg) => Some(msg),
+                            _ => {
+                                warn!("Main Manager received message it could not handle");
+                                None
                             }
                         }
+                    }
 
-                        Either::Right(r) => {
-                            // Reads the message from the receiver
-                            let msg = block_on(r.recv());
-                            if let Err(e) = msg {
-                                error!("Could not read packet: {:?}", e);
-                                break;
-                            }
+                    Either::Right(r) => {
+                        // Reads the message from the receiver, fails once the channel is closed
+                        let msg = r.recv().await;
+                        if msg.is_err() {
+                            debug!("Channel of {} closed, stopping", receiver_host);
+                            break;
+                        }
 
-                            // Handle the message and store it
-                            match msg.unwrap() {
-                                C2SPacket::Message(msg) => Some(msg),
-                                _ => {
-                                    warn!("Main Manager received message it could not handle");
-                                    None
-                                }
+                        // Handle the message and store it
+                        match msg.unwrap() {
+                            C2SPacket::Message(msg) => Some(msg),
+                            _ => {
+                                warn!("Main Manager received message it could not handle");
+                                None
                             }
                         }
-                    };
-
-                    // Handles the message
-                    let h = Self::handle(msg, info_read.clone(), &receiver_host);
-                    // And wait for it to be handled
-                    //TODO do not block here
-                    let h = block_on(h);
-
-                    if let Err(e) = h {
-                        error!("{:?}", e);
                     }
-                }
-                let msg = MESSAGING.read();
-                let msg = block_on(msg);
-                let f = msg.remove_connection(&receiver_host);
-                block_on(f);
-
-                let _ = block_on(get_app())
-                .emit_payload(WsClientUpdatePayload {
-                    hostname: receiver_host.to_string(),
-                    status: WsClientStatus::Disconnected,
-                })
-                .map_err(|e| warn!("[SERVER] Could not emit ws client update: {:?}", e));
+                };
 
+                // Handles the message
+                let h = Self::handle(msg, info_read.clone(), &receiver_host).await;
+                if let Err(e) = h {
+                    error!("{:?}", e);
+                }
+            }
+            // Another connection to this peer might have replaced this one, which is still connected
+            if !MESSAGING.read().await.remove_if_current(&conn).await {
+                debug!("Connection to {} has been replaced, not removing it", receiver_host);
+                return;
+            }
+
+            let _ = emit_payload(WsClientUpdatePayload {
+                hostname: receiver_host.to_string(),
+                status: WsClientStatus::Disconnected,
             })
-            .unwrap()
+            .map_err(|e| warn!("[SERVER] Could not emit ws client update: {:?}", e));
+        })
     }
 }
@@ -0,0 +1,34 @@
+use anyhow::{anyhow, Result};
+
+/// Keeps track of the sequence numbers received in a session.
+/// Websockets deliver packets in the order they have been sent, so every packet has to have a higher
+/// sequence number than the one before. Anything else has been replayed or reordered and is rejected.
+#[derive(Debug, Default)]
+pub struct ReplayGuard {
+    /// The highest sequence number received so far, 0 if none
+    highest: u64,
+}
+
+impl ReplayGuard {
+    /// Marks the given sequence number as received
+    ///
+    /// # Arguments
+    ///
+    /// * `sequence` - The sequence number of the packet, starting at 1
+    ///
+    /// # Returns
+    ///
+    /// Fails if the packet is not newer than the last one
+    pub fn check(&mut self, sequence: u64) -> Result<()> {
+        if sequence <= self.highest {
+            return Err(anyhow!(
+                "Packet {} has been replayed or is out of order (latest is {})",
+                sequence,
+                self.highest
+            ));
+        }
+
+        self.highest = sequence;
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+
+use anyhow::{anyhow, Result};
+use openssl::{
+    hash::{hash, MessageDigest},
+    memcmp,
+    pkey::PKey,
+    sign::Signer,
+};
+use payloads::packets::{AuthenticatedPacket, C2SPacket, S2CPacket};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use super::{pad_packet, unpad_packet, ReplayGuard};
+
+/// The key of a single connection, derived during the handshake.
+/// Every packet after the handshake is authenticated with it, so nobody else can inject packets.
+/// Each packet gets the next sequence number, so packets can't be replayed either
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct Session {
+    /// The shared secret both sides derived
+    key: Vec<u8>,
+    /// The sequence number of the last packet we sent
+    #[zeroize(skip)]
+    sent: Arc<AtomicU64>,
+    /// The sequence numbers we received
+    #[zeroize(skip)]
+    received: Arc<Mutex<ReplayGuard>>,
+}
+
+impl std::fmt::Debug for Session {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Session").finish_non_exhaustive()
+    }
+}
+
+impl Session {
+    /// Derives the session key
+    ///
+    /// # Arguments
+    ///
+    /// * `shared_secret` - The result of the key exchange
+    /// * `transcript` - The handshake this session belongs to
+    ///
+    /// # Returns
+    ///
+    /// The new session
+    pub(crate) fn derive(shared_secret: &[u8], transcript: &[u8]) -> Result<Self> {
+        let mut data = b"enkrypton-session".to_vec();
+        data.extend(shared_secret);
+        data.extend(transcript);
+
+        let key = hash(MessageDigest::sha256(), &data)?.to_vec();
+        data.zeroize();
+
+        Ok(Self {
+            key,
+            sent: Arc::default(),
+            received: Arc::default(),
+        })
+    }
+
+    /// # Arguments
+    ///
+    /// * `sequence` - The sequence number of the packet
+    /// * `packet` - The serialized packet
+    /// * `from_client` - Whether the client sent the packet, so packets can't be reflected to their sender
+    ///
+    /// # Returns
+    ///
+    /// The HMAC of the packet
+    fn mac(&self, sequence: u64, packet: &[u8], from_client: bool) -> Result<Vec<u8>> {
+        let key = PKey::hmac(&self.key)?;
+        let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+
+        signer.update(if from_client { b"c2s" } else { b"s2c" })?;
+        signer.update(&sequence.to_le_bytes())?;
+        signer.update(packet)?;
+
+        Ok(signer.sign_to_vec()?)
+    }
+
+    /// Pads the serialized packet, so its size on the wire hides what kind of packet it is,
+    /// and authenticates it with the next sequence number
+    fn seal(&self, packet: Vec<u8>, from_client: bool) -> Result<AuthenticatedPacket> {
+        let packet = pad_packet(&packet)?;
+        let sequence = self.sent.fetch_add(1, Ordering::SeqCst) + 1;
+        let mac = self.mac(sequence, &packet, from_client)?;
+
+        Ok(AuthenticatedPacket { sequence, packet, mac })
+    }
+
+    /// Checks the mac and the sequence number of the packet
+    ///
+    /// # Returns
+    ///
+    /// The serialized packet without its padding, fails if it has not been authenticated with this session or has been received already
+    fn open<'a>(&self, packet: &'a AuthenticatedPacket, from_client: bool) -> Result<&'a [u8]> {
+        let expected = self.mac(packet.sequence, &packet.packet, from_client)?;
+        if expected.len() != packet.mac.len() || !memcmp::eq(&expected, &packet.mac) {
+            return Err(anyhow!("Packet has not been authenticated with the session key"));
+        }
+
+        // Only authenticated packets may advance the sequence number
+        self.received
+            .lock()
+            .map_err(|_| anyhow!("Replay guard has been poisoned"))?
+            .check(packet.sequence)?;
+
+        unpad_packet(&packet.packet)
+    }
+
+    /// # Arguments
+    ///
+    /// * `packet` - The packet to send to the server
+    ///
+    /// # Returns
+    ///
+    /// The authenticated packet
+    pub fn seal_c2s(&self, packet: C2SPacket) -> Result<C2SPacket> {
+        Ok(C2SPacket::Authenticated(self.seal(packet.try_into()?, true)?))
+    }
+
+    /// # Arguments
+    ///
+    /// * `packet` - The packet to send to the client
+    ///
+    /// # Returns
+    ///
+    /// The authenticated packet
+    pub fn seal_s2c(&self, packet: S2CPacket) -> Result<S2CPacket> {
+        Ok(S2CPacket::Authenticated(self.seal(packet.try_into()?, false)?))
+    }
+
+    /// # Arguments
+    ///
+    /// * `packet` - The authenticated packet the client sent
+    ///
+    /// # Returns
+    ///
+    /// The inner packet, fails if it is not authenticated or a packet that may not be wrapped
+    pub fn open_c2s(&self, packet: &AuthenticatedPacket) -> Result<C2SPacket> {
+        let inner = C2SPacket::try_from(&self.open(packet, true)?.to_vec())?;
+        if inner.is_handshake() || matches!(inner, C2SPacket::Authenticated(_)) {
+            return Err(anyhow!("Invalid authenticated packet"));
+        }
+
+        Ok(inner)
+    }
+
+    /// # Arguments
+    ///
+    /// * `packet` - The authenticated packet the server sent
+    ///
+    /// # Returns
+    ///
+    /// The inner packet, fails if it is not authenticated or a packet that may not be wrapped
+    pub fn open_s2c(&self, packet: &AuthenticatedPacket) -> Result<S2CPacket> {
+        let inner = S2CPacket::try_from(&self.open(packet, false)?.to_vec())?;
+        if inner.is_handshake() || matches!(inner, S2CPacket::Authenticated(_)) {
+            return Err(anyhow!("Invalid authenticated packet"));
+        }
+
+        Ok(inner)
+    }
+}
@@ -0,0 +1,76 @@
+use anyhow::{anyhow, Result};
+use log::info;
+use tor_proxy::service::get_service_hostname;
+
+use super::{Connection, MessagingManager};
+
+/// Decides which connection is kept if both peers dialed each other at the same time.
+/// The connection dialed by the peer with the smaller onion hostname wins, so both sides keep the same one
+///
+/// # Arguments
+///
+/// * `own_hostname` - Our own onion hostname
+/// * `peer_hostname` - The onion hostname of the peer
+///
+/// # Returns
+///
+/// Whether the connection we dialed is kept
+pub fn keeps_dialed(own_hostname: &str, peer_hostname: &str) -> bool {
+    own_hostname < peer_hostname
+}
+
+impl MessagingManager {
+    /// Adds a connection to the connections map.
+    /// If there already is one to this peer that has been dialed from the other side, just one of them is kept
+    /// (see `keeps_dialed`). The packets queued on the other one are moved over and it is shut down.
+    /// An accepted connection holds them back until its identity handshake is done.
+    ///
+    /// # Arguments
+    ///
+    /// * `onion_host` - The peer of the connection
+    /// * `conn` - The new connection
+    ///
+    /// # Returns
+    ///
+    /// The connection that is kept, which might not be the given one
+    pub(crate) async fn insert_connection(&self, onion_host: &str, conn: Connection) -> Result<Connection> {
+        // The same hostname the peer signed our identity for
+        let own_hostname = get_service_hostname(!onion_host.ends_with("-dev-client"))
+            .await?
+            .ok_or(anyhow!("Could not get own hostname"))?;
+
+        let mut connections = self.connections.write().await;
+        let existing = match connections.get(onion_host) {
+            Some(e) if !e.is_same(&conn) => e.clone(),
+            _ => {
+                connections.insert(onion_host.to_string(), conn.clone());
+                return Ok(conn);
+            }
+        };
+
+        let new_is_client = conn.get_state().await.is_client;
+        // The old connection has not noticed yet that it dropped, so the new one replaces it
+        let keep_new = if new_is_client == existing.get_state().await.is_client {
+            true
+        } else {
+            keeps_dialed(&own_hostname, onion_host) == new_is_client
+        };
+
+        let (kept, dropped) = if keep_new { (conn, existing) } else { (existing, conn) };
+        connections.insert(onion_host.to_string(), kept.clone());
+        drop(connections);
+
+        info!(
+            "Two connections to {}, keeping the one {}",
+            onion_host,
+            if kept.get_state().await.is_client { "we dialed" } else { "it dialed" }
+        );
+
+        // Nothing queued should get lost, the peer just receives it over the kept connection
+        let queued = dropped.take_queued().await;
+        dropped.shutdown().await?;
+        kept.send_queued(queued).await?;
+
+        Ok(kept)
+    }
+}
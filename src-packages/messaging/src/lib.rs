@@ -5,5 +5,10 @@ pub mod client;
 pub mod general;
 /// General structs and traits for the websocket server hosted by the enkrypton binary
 pub mod server;
+/// Signed contact cards to add other users as contacts
+pub mod contact;
+/// The transports connections can run over (tor, plain tcp or unix sockets)
+pub mod transport;
 
-//TODO - Write some tests here, don't know how though (it relies basically on the whole client)
\ No newline at end of file
+#[cfg(test)]
+mod tests;
\ No newline at end of file
@@ -1,9 +1,11 @@
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use log::info;
+use payloads::payloads::PeerState;
 
 use crate::general::{Connection, MessagingManager};
 
-use super::ws_manager::WsActor;
+use super::ws_manager::ServerChannels;
 
 /// Extends the manager with some helper functions for the server
 #[async_trait]
@@ -14,57 +16,71 @@ pub trait ManagerExt {
     /// # Arguments
     ///
     /// * `onion_host` - The onion host of the connection.
-    /// * `a` - The WsActor representing the connection.
-    async fn set_remote_verified(&self, onion_host: &str, a: &WsActor);
+    /// * `channels` - The channels of the WsActor representing the connection.
+    ///
+    /// # Returns
+    ///
+    /// Fails if the connection has been replaced by one we dialed
+    async fn set_remote_verified(&self, onion_host: &str, channels: &ServerChannels) -> Result<()>;
 
     /// Sets the self verified flag for a connection.
     ///
     /// # Arguments
     ///
     /// * `onion_host` - The onion host of the connection.
-    /// * `a` - The WsActor representing the ws connection to the client.
-    async fn set_self_verified(&self, onion_host: &str, a: &WsActor);
+    /// * `channels` - The channels of the WsActor representing the ws connection to the client.
+    ///
+    /// # Returns
+    ///
+    /// Fails if the connection has been replaced by one we dialed
+    async fn set_self_verified(&self, onion_host: &str, channels: &ServerChannels) -> Result<()>;
 
     /// Inserts or adds a connection to the manager and returns it.
+    /// If we dialed the peer at the same time, just one of both connections is kept (see `insert_connection`)
     ///
     /// # Arguments
     ///
     /// * `onion_host` - The onion host of the connection.
-    /// * `a` - The WsActor representing the connection.
+    /// * `channels` - The channels of the WsActor representing the connection.
     ///
     /// # Returns
     ///
-    /// The Connection object representing the added connection.
-    async fn get_or_insert(&self, onion_host: &str, a: &WsActor) -> Connection;
+    /// The Connection object representing the added connection, fails if the connection we dialed is kept instead
+    async fn get_or_insert(&self, onion_host: &str, channels: &ServerChannels) -> Result<Connection>;
 }
 
 #[async_trait]
 impl ManagerExt for MessagingManager {
-    async fn set_remote_verified(&self, onion_host: &str, a: &WsActor) {
-        let conn = self.get_or_insert(onion_host, a).await;
+    async fn set_remote_verified(&self, onion_host: &str, channels: &ServerChannels) -> Result<()> {
+        let conn = self.get_or_insert(onion_host, channels).await?;
         *conn.verified.write().await = true;
 
         info!("[SERVER]: New Connection for {}", onion_host);
+        Ok(())
     }
 
-    async fn set_self_verified(&self, onion_host: &str, a: &WsActor) {
-        let conn = self.get_or_insert(onion_host, a).await;
+    async fn set_self_verified(&self, onion_host: &str, channels: &ServerChannels) -> Result<()> {
+        let conn = self.get_or_insert(onion_host, channels).await?;
         *conn.self_verified.write().await = true;
+        Ok(())
     }
 
-    async fn get_or_insert(&self, onion_host: &str, a: &WsActor) -> Connection {
+    async fn get_or_insert(&self, onion_host: &str, channels: &ServerChannels) -> Result<Connection> {
         let r = self.connections.read().await.get(onion_host).cloned();
-        if let Some(r) = r {
-            return r;
+        if let Some(r) = r
+            && r.has_channels(channels).await
+        {
+            return Ok(r);
         }
 
         // Creates a new connection
-        let c = Connection::new_server(onion_host, (*a.c_rx.clone(), *a.s_tx.clone())).await;
-        self.connections
-            .write()
-            .await
-            .insert(onion_host.to_string(), c.clone());
+        let c = Connection::new_server(onion_host, channels.clone()).await;
+        let kept = self.insert_connection(onion_host, c.clone()).await?;
+        if !kept.is_same(&c) {
+            return Err(anyhow!("The connection we dialed to {} is kept instead", onion_host));
+        }
 
-        c
+        self.set_peer_state(onion_host, PeerState::Handshaking).await;
+        Ok(c)
     }
 }
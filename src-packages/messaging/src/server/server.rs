@@ -3,16 +3,17 @@ use std::thread;
 use actix_web::{web, App, HttpServer};
 use anyhow::Result;
 use log::{error, info};
-use shared::config::CONFIG;
-use tauri::async_runtime::block_on;
+
+use crate::transport::{get_transport, ListenAddr};
 
 use super::routes::{hello, ws_index};
 
-/// Starts the local webserver in a new thread which runs the server_mainloop.
-/// Listens to CONFIG.service_port() and only to localhost
+/// Starts the local webserver in a new thread which runs the server_mainloop in its own actix system.
+/// Listens to the address of the current transport (for tor CONFIG.service_port() on localhost)
 pub fn start_webserver() {
     thread::Builder::new().name("webserver".to_string()).spawn(move || {
-        let res = block_on(server_mainloop());
+        let listen = get_transport().listen_addr();
+        let res = actix_web::rt::System::new().block_on(server_mainloop(listen));
 
         if res.is_err() {
             error!("{}", res.unwrap_err());
@@ -22,20 +23,37 @@ pub fn start_webserver() {
     }).unwrap();
 }
 
-/// Just initializes a new async webserver and listens just to local connections and to the specified port.
+/// Just initializes a new async webserver and listens just to the given address.
 /// Used to listen for new websocket connections
-async fn server_mainloop() -> Result<()> {
-    HttpServer::new(|| {
+///
+/// # Arguments
+///
+/// * `listen` - Where to listen for other peers
+///
+async fn server_mainloop(listen: ListenAddr) -> Result<()> {
+    let server = HttpServer::new(|| {
         return App::new()
             // Return the default message to tell other clients that this server is actually alive
             .service(hello)
             // The websocket endpoint
             .route("/ws/", web::get().to(ws_index));
-    })
-    // Bind just to localhost and run
-    .bind(("127.0.0.1", CONFIG.service_port()))?
-    .run()
-    .await?;
+    });
+
+    info!("Webserver listening on {:?}", listen);
+    let server = match listen {
+        ListenAddr::Tcp(addr) => server.bind(addr)?,
+        #[cfg(unix)]
+        ListenAddr::Unix(path) => {
+            // A socket left over from an earlier run would make binding fail
+            if path.exists() {
+                std::fs::remove_file(&path)?;
+            }
+
+            server.bind_uds(path)?
+        }
+    };
+
+    server.run().await?;
 
     Ok(())
 }
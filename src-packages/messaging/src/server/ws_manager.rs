@@ -1,24 +1,34 @@
-use std::time::{Duration, Instant};
+use std::{
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
 
-use actix::{Actor, ActorContext, AsyncContext, StreamHandler};
+use actix::{fut::wrap_future, Actor, ActorContext, ActorFutureExt, AsyncContext, StreamHandler};
 use actix_web::web::Bytes;
 use actix_web_actors::ws::{self, Message, ProtocolError};
-use anyhow::Result;
-use async_channel::{Receiver, Sender, TryRecvError};
+use anyhow::{anyhow, Result};
+use async_channel::{Receiver, Sender};
 use log::{debug, error, info, warn};
 use payloads::{
-    event::AppHandleExt,
     packets::{C2SPacket, S2CPacket},
-    payloads::{WsClientStatus, WsClientUpdatePayload, WsMessageStatus},
+    payloads::WsMessageStatus,
 };
-use shared::get_app;
-use smol::future::block_on;
 
-use crate::general::{IdentityProvider, IdentityVerify, HEARTBEAT_TIMEOUT, MESSAGING};
+use tokio::sync::Mutex;
+
+use crate::general::{Handshake, Session, HEARTBEAT_TIMEOUT, MESSAGING};
 
 use super::manager_ext::ManagerExt;
 
-pub type ServerChannels = (Receiver<C2SPacket>, Sender<S2CPacket>);
+/// The packets of the client, the queue to send packets to the client and its receiving end
+/// (used to move queued packets to another connection)
+pub type ServerChannels = (Receiver<C2SPacket>, Sender<S2CPacket>, Receiver<S2CPacket>);
+
+/// Our side of the handshake and the key of the session, once the client sent its hello
+type ServerAuth = (Arc<Mutex<Option<Handshake>>>, Arc<RwLock<Option<Session>>>);
+
+/// How many packets may wait to be sent to the client, senders wait once it is full
+const OUTGOING_CAPACITY: usize = 64;
 
 pub struct WsActor {
     s_rx: Box<Receiver<S2CPacket>>,
@@ -33,38 +43,19 @@ pub struct WsActor {
     receiver: Option<String>,
     // The time the last heartbeat was sent
     last_heartbeat: Instant,
+
+    // Our side of the handshake, once the client sent its hello
+    handshake: Arc<Mutex<Option<Handshake>>>,
+    // The key of this session, every packet after the handshake is authenticated with it
+    session: Arc<RwLock<Option<Session>>>,
 }
 
 impl Actor for WsActor {
     type Context = ws::WebsocketContext<Self>;
 
     fn started(&mut self, ctx: &mut Self::Context) {
-        let rx = self.s_rx.clone();
-
-        // Constantly checking for new messages to send to the client
-        ctx.run_interval(Duration::from_millis(100), move |_a, ctx| {
-            let p = rx.try_recv();
-            if let Err(e) = p {
-                if e == TryRecvError::Closed {
-                    warn!("[SERVER] Channel has been closed. Stopping...");
-                    ctx.stop();
-                }
-
-                return;
-            }
-            let p = p.unwrap();
-            debug!("[SERVER] Sending packet to client {:?}", p);
-
-            let res = p.try_into();
-            if let Err(e) = res {
-                error!("[SERVER] Could not parse packet: {:?}", e);
-                return;
-            }
-
-            // Actually sending the packet to the client
-            let packet: Bytes = res.unwrap();
-            ctx.binary(packet);
-        });
+        // Every packet that is queued is sent to the client as soon as it arrives
+        ctx.add_stream(*self.s_rx.clone());
 
         // Checking for a heartbeat timeout
         ctx.run_interval(Duration::from_secs(1), |a, ctx| {
@@ -89,19 +80,9 @@ impl Actor for WsActor {
         self.c_tx.close();
         self.s_tx.close();
 
+        // The read task of the connection notices the closed channels and removes it
         if let Some(onion_host) = &self.receiver {
-            debug!("[SERVER] Connection was stopped. Removing connection for {}", onion_host);
-
-            // Sending a disconnect update to the frontend
-            let _ = block_on(get_app())
-                .emit_payload(WsClientUpdatePayload {
-                    hostname: onion_host.to_string(),
-                    status: WsClientStatus::Disconnected,
-                })
-                .map_err(|e| warn!("[SERVER] Could not emit ws client update: {:?}", e));
-
-            // And remove the connection from the messaging manager
-            block_on(block_on(MESSAGING.read()).remove_connection(onion_host));
+            debug!("[SERVER] Connection was stopped for {}", onion_host);
         }
     }
 }
@@ -113,7 +94,7 @@ impl WsActor {
     ///
     /// The constructed `WsActor` 
     pub fn new() -> Self {
-        let (s_tx, s_rx) = async_channel::unbounded();
+        let (s_tx, s_rx) = async_channel::bounded(OUTGOING_CAPACITY);
         let (c_tx, c_rx) = async_channel::unbounded();
 
         Self {
@@ -124,81 +105,163 @@ impl WsActor {
 
             s_tx: Box::new(s_tx),
             s_rx: Box::new(s_rx),
+
+            handshake: Arc::new(Mutex::new(None)),
+            session: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Authenticates the given packet with the session key, packets of the handshake are sent as they are
+    ///
+    /// # Arguments
+    ///
+    /// * `packet` - The packet to send to the client
+    ///
+    /// # Returns
+    ///
+    /// The packet to send, fails if the handshake is not done yet
+    fn seal(&self, packet: S2CPacket) -> Result<S2CPacket> {
+        if packet.is_handshake() {
+            return Ok(packet);
         }
+
+        self.session
+            .read()
+            .unwrap()
+            .as_ref()
+            .ok_or(anyhow!("Can't send {:?} before the handshake", packet))?
+            .seal_s2c(packet)
     }
 
-    /// Handles a packet from the client
+    /// # Returns
+    ///
+    /// The channels the connection of this actor communicates with
+    fn channels(&self) -> ServerChannels {
+        (*self.c_rx.clone(), *self.s_tx.clone(), *self.s_rx.clone())
+    }
+
+    /// Handles a packet from the client.
+    /// Does not borrow the actor, so it can run without blocking the arbiter
     ///
     /// # Arguments
     ///
     /// * `packet` - The packet that was received from the client
-    /// * `ctx` - The context of the actor for storing stuff
-    pub async fn inner_handle(
-        &mut self,
+    /// * `receiver` - The verified receiver of this actor, if any
+    /// * `channels` - The channels of this actor
+    /// * `c_tx` - Used to redirect messages of the client to the main handler
+    /// * `(tuple)` - Our side of the handshake and the session key of this actor
+    ///
+    /// # Returns
+    ///
+    /// The receiver, if it has just been verified
+    async fn inner_handle(
         packet: C2SPacket,
-        ctx: &mut <Self as Actor>::Context,
-    ) -> Result<()> {
-        let mut packet_auth = None;
-        match packet {
-            // Again, same as for the client, setting that this connection has been verified on our end
-            C2SPacket::IdentityVerified => {
-                if let Some(onion_host) = &self.receiver {
-                    let messaging = MESSAGING.read().await;
-                    messaging.set_self_verified(&onion_host, &self).await;
-                    messaging.check_verified(&onion_host).await?;
-                } else {
-                    error!("[SERVER] Received IdentityVerified packet but no onion host was set");
+        receiver: Option<String>,
+        channels: ServerChannels,
+        c_tx: Sender<C2SPacket>,
+        (handshake, session): ServerAuth,
+    ) -> Result<Option<String>> {
+        let packet = match packet {
+            // The client started the handshake, answering with our own challenge
+            C2SPacket::Hello(hello) => {
+                let mut handshake = handshake.lock().await;
+                if handshake.is_some() {
+                    return Err(anyhow!("[SERVER] The handshake has already been started"));
                 }
+
+                let accepted = Handshake::accept(hello).await?;
+                *session.write().unwrap() = Some(accepted.session()?);
+
+                let (_, s_tx, _) = &channels;
+                s_tx.send(S2CPacket::Hello(accepted.hello())).await?;
+
+                *handshake = Some(accepted);
+                return Ok(None);
             }
             // Verifying the connection of the client and sending a `IdentityVerified` back. Oh and we send our own identity back
             C2SPacket::SetIdentity(identity) => {
+                let handshake = handshake.lock().await;
+                let handshake = handshake
+                    .as_ref()
+                    .ok_or(anyhow!("[SERVER] Got an identity before the hello"))?;
+
                 info!("[SERVER] Verifying identity for {:?}...", identity);
-                identity.verify().await?;
+                handshake.verify(&identity).await?;
 
                 let messaging = MESSAGING.read().await;
-                self.receiver = Some(identity.hostname.clone());
 
                 // Setting the remote verified status
                 messaging
-                    .set_remote_verified(&identity.hostname, &self)
-                    .await;
+                    .set_remote_verified(&identity.hostname, &channels)
+                    .await?;
                 messaging.check_verified(&identity.hostname).await?;
 
-                let b: Bytes = S2CPacket::IdentityVerified.try_into()?;
-
                 // Creating a new identity packet
-                let verify_p: Bytes = S2CPacket::identity(&identity.hostname).await?.try_into()?;
+                let verify_p = S2CPacket::VerifyIdentity(handshake.identity().await?);
                 info!("[SERVER] Identity verified. Sending packet.");
 
-                ctx.binary(verify_p);
-                ctx.binary(b);
+                let (_, s_tx, _) = &channels;
+                s_tx.send(verify_p).await?;
+                s_tx.send(S2CPacket::IdentityVerified).await?;
+
+                // Packets moved over from another connection are accepted by the peer from now on
+                messaging
+                    .get_or_insert(&identity.hostname, &channels)
+                    .await?
+                    .release_queued()
+                    .await;
+
+                return Ok(Some(identity.hostname));
+            }
+            C2SPacket::Authenticated(p) => session
+                .read()
+                .unwrap()
+                .as_ref()
+                .ok_or(anyhow!("[SERVER] Got an authenticated packet before the handshake"))?
+                .open_c2s(&p)?,
+            p => return Err(anyhow!("[SERVER] Unauthenticated packet {:?}", p)),
+        };
+
+        let mut packet_auth = None;
+        match packet {
+            // Just cover traffic
+            C2SPacket::Cover(_) => {}
+            // Again, same as for the client, setting that this connection has been verified on our end
+            C2SPacket::IdentityVerified => {
+                if let Some(onion_host) = &receiver {
+                    let messaging = MESSAGING.read().await;
+                    messaging.set_self_verified(onion_host, &channels).await?;
+                    messaging.check_verified(onion_host).await?;
+                } else {
+                    error!("[SERVER] Received IdentityVerified packet but no onion host was set");
+                }
             }
             other => packet_auth = Some(other),
         }
 
         // We don't need to process this packet any further as it was already handled
         if packet_auth.is_none() {
-            return Ok(());
+            return Ok(None);
         }
 
         let packet_auth = packet_auth.unwrap();
-        if self.receiver.is_none() {
+        if receiver.is_none() {
             warn!(
                 "[SERVER] Ignoring packet {:?}. No Receiver yet.",
                 packet_auth
             );
-            return Ok(());
+            return Ok(None);
         }
 
         // The receiver of this connection
-        let rec = self.receiver.as_ref().unwrap();
+        let rec = receiver.as_ref().unwrap();
 
         // Return an error here if we are not verified yet.
         MESSAGING.read().await.assert_verified(rec).await?;
         match packet_auth {
             C2SPacket::Message(msg) => {
                 // Sending the message to main handler
-                self.c_tx.send(C2SPacket::Message(msg)).await?;
+                c_tx.send(C2SPacket::Message(msg)).await?;
             }
             C2SPacket::MessageFailed(date) => {
                 // Updating the message status to failed
@@ -219,7 +282,38 @@ impl WsActor {
             }
             _ => warn!("[SERVER] Could not process packet {:?}", packet_auth),
         }
-        Ok(())
+        Ok(None)
+    }
+}
+
+/// Sends the queued packets to the client
+impl StreamHandler<S2CPacket> for WsActor {
+    fn handle(&mut self, p: S2CPacket, ctx: &mut Self::Context) {
+        debug!("[SERVER] Sending packet to client {:?}", p);
+
+        // Everything after the handshake is authenticated with the session key
+        let p = match self.seal(p) {
+            Ok(p) => p,
+            Err(e) => {
+                error!("[SERVER] Could not authenticate packet: {:?}", e);
+                return;
+            }
+        };
+
+        let res = p.try_into();
+        if let Err(e) = res {
+            error!("[SERVER] Could not parse packet: {:?}", e);
+            return;
+        }
+
+        // Actually sending the packet to the client
+        let packet: Bytes = res.unwrap();
+        ctx.binary(packet);
+    }
+
+    fn finished(&mut self, ctx: &mut Self::Context) {
+        warn!("[SERVER] Channel has been closed. Stopping...");
+        ctx.stop();
     }
 }
 
@@ -233,6 +327,11 @@ impl StreamHandler<Result<Message, ProtocolError>> for WsActor {
                 self.last_heartbeat = Instant::now();
             }
             Ok(Message::Text(_)) => {}
+            // The client closed the connection, so answer the close and stop right away
+            Ok(Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
             Ok(Message::Binary(bin)) => {
                 // Deserialize the packet and process it further
                 let res = C2SPacket::try_from(&bin.to_vec());
@@ -249,13 +348,15 @@ impl StreamHandler<Result<Message, ProtocolError>> for WsActor {
 
                 let packet = res.unwrap();
                 // Processing more in that function
-                let res = self.inner_handle(packet, ctx);
-                let res = block_on(res);
-
-                if res.is_err() {
-                    error!("[SERVER] Could not handle packet: {:?}", res.unwrap_err());
-                    return;
-                }
+                let auth = (self.handshake.clone(), self.session.clone());
+                let fut = Self::inner_handle(packet, self.receiver.clone(), self.channels(), *self.c_tx.clone(), auth);
+
+                // Packets are handled one after another, but the arbiter keeps running other actors meanwhile
+                ctx.wait(wrap_future::<_, Self>(fut).map(|res, act, _ctx| match res {
+                    Ok(Some(receiver)) => act.receiver = Some(receiver),
+                    Ok(None) => (),
+                    Err(e) => error!("[SERVER] Could not handle packet: {:?}", e),
+                }));
             }
             _ => (),
         }
@@ -0,0 +1,829 @@
+//! Loopback tests running both peers in this process.
+//! Just like the `dev` feature does for the app, our own hostname gets a `-dev-client` or `-dev-server`
+//! suffix, so the connection we dial and the one our server accepts are two different contacts.
+//! Both sides use the plain tcp transport instead of tor.
+
+use std::{
+    env, fs,
+    future::Future,
+    sync::{Mutex, MutexGuard},
+    time::Duration,
+};
+
+use async_channel::Receiver;
+use ed25519_dalek::{hazmat::ExpandedSecretKey, VerifyingKey};
+use encryption::{PrivateKey, PublicKey};
+use lazy_static::lazy_static;
+use payloads::{
+    event::{set_event_sink, ChannelEventSink, EmittedEvent, SendablePayload},
+    packets::{C2SPacket, S2CPacket},
+    payloads::{
+        PeerState, PeerStatePayload, WsClientStatus, WsClientUpdatePayload, WsMessagePayload, WsMessageStatus,
+        WsMessageStatusPayload,
+    },
+};
+use sha2::{Digest, Sha512};
+use shared::{config::CONFIG, get_cover_traffic_path, onion::onion_hostname, util::now_millis, ROOT_DIR_ENV};
+use storage_internal::STORAGE;
+use tokio::{
+    net::TcpStream,
+    runtime::{Builder, Runtime},
+    time::{sleep, timeout},
+};
+use tor_proxy::service::{
+    client_auth_public_key, decode_client_auth_key, encode_client_auth_key, get_authorized_clients_dir,
+};
+
+use crate::{
+    contact::{render_qr_png, render_qr_svg, ContactCard},
+    general::{
+        backoff_delay, cover_payload, issue_client_auth, keeps_dialed, revoke_client_auth, set_remote_client_auth, pad_packet, set_cover_traffic, unpad_packet, Connection,
+        Handshake, ReplayGuard, Session, MESSAGING, PADDED_PACKET_SIZE, RECONNECT_BASE_DELAY, RECONNECT_MAX_DELAY,
+    },
+    server::{manager_ext::ManagerExt, server::start_webserver},
+    transport::{set_transport, TcpTransport, Transport},
+};
+
+/// How long to wait for an event before failing
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+lazy_static! {
+    /// The expanded secret key of our onion service, stored the same way tor does
+    static ref SERVICE_KEY: [u8; 64] = expand_secret_key(&[7; 32]);
+    /// The onion hostname both peers share
+    static ref HOSTNAME: String = onion_hostname(&VerifyingKey::from(&ExpandedSecretKey::from_bytes(&SERVICE_KEY)));
+    /// Our own server, as seen by the dialing side
+    static ref SERVER: String = format!("{}-dev-server", *HOSTNAME);
+    /// The dialing side, as seen by our own server
+    static ref CLIENT: String = format!("{}-dev-client", *HOSTNAME);
+
+    static ref HARNESS: Harness = Harness::new();
+}
+
+/// Everything the loopback tests share: the storage, the webserver and the messaging manager are global
+struct Harness {
+    /// Drives every test, the read threads keep running on it in between tests
+    runtime: Runtime,
+    /// Every event the peers emit
+    events: Receiver<EmittedEvent>,
+    /// Events that have been skipped while waiting for another one
+    pending: Mutex<Vec<EmittedEvent>>,
+    /// The tests modify the same global state, so just one may run at a time
+    lock: Mutex<()>,
+}
+
+impl Harness {
+    fn new() -> Self {
+        // Set RUST_LOG to see what the peers are doing
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let root = env::temp_dir().join(format!("enkrypton-loopback-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+
+        // SAFETY: nothing else has read the environment yet, this runs before any other harness setup
+        unsafe { env::set_var(ROOT_DIR_ENV, &root) };
+
+        // Tor would usually write the key and hostname of our service
+        let service_dir = std::path::Path::new(CONFIG.service_dir());
+        let mut secret = b"== ed25519v1-secret: type0 ==\0\0\0".to_vec();
+        secret.extend(SERVICE_KEY.iter());
+        fs::write(service_dir.join("hs_ed25519_secret_key"), secret).unwrap();
+        fs::write(service_dir.join("hostname"), format!("{}.onion\n", *HOSTNAME)).unwrap();
+
+        let (sink, events) = ChannelEventSink::new();
+        set_event_sink(std::sync::Arc::new(sink));
+
+        let addr = CONFIG.get_hidden_service_host();
+        let transport = TcpTransport::new(addr.parse().unwrap());
+        transport.add_peer(&SERVER, addr.parse().unwrap());
+        set_transport(std::sync::Arc::new(transport));
+
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(4)
+            .enable_all()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            STORAGE.write().await.read_or_generate("loopback").await.unwrap();
+            assert!(STORAGE.read().await.is_unlocked().unwrap());
+
+            start_webserver();
+            while TcpStream::connect(&addr).await.is_err() {
+                sleep(Duration::from_millis(50)).await;
+            }
+        });
+
+        Self {
+            runtime,
+            events,
+            pending: Mutex::default(),
+            lock: Mutex::new(()),
+        }
+    }
+}
+
+/// Expands the seed of an ed25519 key the same way tor does before storing it
+///
+/// # Arguments
+///
+/// * `seed` - The seed of the key
+///
+/// # Returns
+///
+/// The clamped scalar followed by the hash prefix
+fn expand_secret_key(seed: &[u8; 32]) -> [u8; 64] {
+    let mut expanded: [u8; 64] = Sha512::digest(seed).into();
+    expanded[0] &= 248;
+    expanded[31] &= 127;
+    expanded[31] |= 64;
+
+    expanded
+}
+
+/// Runs a single loopback test, making sure no other one runs at the same time
+fn run_test<F: Future<Output = ()>>(test: F) {
+    let _lock: MutexGuard<()> = HARNESS.lock.lock().unwrap_or_else(|e| e.into_inner());
+
+    // Events of earlier tests are of no interest
+    while HARNESS.events.try_recv().is_ok() {}
+    HARNESS.pending.lock().unwrap().clear();
+    HARNESS.runtime.block_on(test);
+}
+
+/// Waits for the first payload of type `T` matching `filter`.
+/// Other events are kept, so they can still be waited for afterwards
+async fn wait_for<T: SendablePayload>(filter: impl Fn(&T) -> bool) -> T {
+    let matches = |event: &EmittedEvent| {
+        event
+            .parse::<T>()
+            .ok()
+            .filter(|p| p.get_name() == event.name && filter(p))
+    };
+
+    {
+        let mut pending = HARNESS.pending.lock().unwrap();
+        if let Some(i) = pending.iter().position(|e| matches(e).is_some()) {
+            return matches(&pending.remove(i)).unwrap();
+        }
+    }
+
+    let res = timeout(TIMEOUT, async {
+        loop {
+            let event = HARNESS.events.recv().await.unwrap();
+            if let Some(payload) = matches(&event) {
+                return payload;
+            }
+
+            HARNESS.pending.lock().unwrap().push(event);
+        }
+    })
+    .await;
+
+    res.expect("Timed out waiting for event")
+}
+
+async fn wait_for_status(hostname: &str, status: WsClientStatus) {
+    wait_for::<WsClientUpdatePayload>(|p| {
+        p.hostname == hostname && std::mem::discriminant(&p.status) == std::mem::discriminant(&status)
+    })
+    .await;
+}
+
+async fn wait_for_msg_status(hostname: &str, status: WsMessageStatus) -> u128 {
+    let payload = wait_for::<WsMessageStatusPayload>(|p| {
+        p.hostname == hostname && std::mem::discriminant(&p.status) == std::mem::discriminant(&status)
+    })
+    .await;
+
+    payload.date
+}
+
+/// Dials our own server and waits until both sides have verified each other
+///
+/// # Returns
+///
+/// The dialing and the accepted connection
+async fn connect() -> (Connection, Connection) {
+    let client = MESSAGING.read().await.get_or_connect(&SERVER).await.unwrap();
+
+    wait_for_status(&SERVER, WsClientStatus::Connected).await;
+    wait_for_status(&CLIENT, WsClientStatus::Connected).await;
+
+    // Not dialing it, we just never want to reconnect to the accepting side
+    let server = MESSAGING.read().await.connections.read().await[CLIENT.as_str()].clone();
+    (client, server)
+}
+
+async fn wait_for_peer_state(hostname: &str, filter: impl Fn(&PeerState) -> bool) -> PeerState {
+    let payload = wait_for::<PeerStatePayload>(|p| p.hostname == hostname && filter(&p.state)).await;
+    payload.state
+}
+
+/// Exchanges the hellos of a new handshake, without connecting
+///
+/// # Returns
+///
+/// The dialing and the accepting side of the handshake
+async fn handshake() -> (Handshake, Handshake) {
+    let mut client = Handshake::new(&SERVER).await.unwrap();
+    let server = Handshake::accept(client.hello()).await.unwrap();
+    client.set_remote(server.hello()).unwrap();
+
+    (client, server)
+}
+
+/// Closes the given connection and waits until both sides have removed theirs
+async fn disconnect(conn: &Connection) {
+    // The dialing side would reconnect otherwise if the accepting side closes the connection
+    MESSAGING.read().await.set_active(&SERVER, false).await;
+    conn.close().await.unwrap();
+
+    wait_for_status(&SERVER, WsClientStatus::Disconnected).await;
+    wait_for_status(&CLIENT, WsClientStatus::Disconnected).await;
+
+    let res = timeout(TIMEOUT, async {
+        let mgr = MESSAGING.read().await;
+        while mgr.is_connected(&SERVER).await || mgr.is_connected(&CLIENT).await {
+            sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await;
+    res.expect("Connections have not been removed");
+}
+
+async fn get_pub_key(hostname: &str) -> Option<PublicKey> {
+    STORAGE
+        .read()
+        .await
+        .get_data(|e| Ok(e.chats.get(hostname).and_then(|c| c.rec_pub_key.clone())))
+        .await
+        .unwrap()
+}
+
+async fn set_pub_key(hostname: &str, key: PublicKey) {
+    STORAGE
+        .read()
+        .await
+        .modify_storage_data(|e| {
+            e.chats.get_mut(hostname).unwrap().rec_pub_key = Some(key);
+            Ok(())
+        })
+        .await
+        .unwrap();
+}
+
+#[test]
+fn identity_exchange_verifies_both_sides() {
+    run_test(async {
+        let (client, server) = connect().await;
+
+        // Both sides have stored the public key of the other one
+        assert!(get_pub_key(&SERVER).await.is_some());
+        assert!(get_pub_key(&CLIENT).await.is_some());
+
+        let client_state = client.get_state().await;
+        assert!(client_state.is_client);
+        assert!(client_state.verified && client_state.self_verified);
+
+        let server_state = server.get_state().await;
+        assert!(!server_state.is_client);
+        assert!(server_state.verified && server_state.self_verified);
+
+        disconnect(&client).await;
+    });
+}
+
+#[test]
+fn identities_need_the_key_of_the_claimed_onion_address() {
+    run_test(async {
+        let foreign = ExpandedSecretKey::from_bytes(&expand_secret_key(&[8; 32]));
+        let foreign = format!("{}-dev-client", onion_hostname(&VerifyingKey::from(&foreign)));
+
+        let mut client = Handshake::new(&SERVER).await.unwrap();
+        let mut hello = client.hello();
+        hello.hostname = foreign.clone();
+
+        let server = Handshake::accept(hello).await.unwrap();
+        client.set_remote(server.hello()).unwrap();
+
+        // Signed with the key of our own service, so it can't claim another onion address
+        let mut identity = client.identity().await.unwrap();
+        identity.hostname = foreign.clone();
+        assert!(server.verify(&identity).await.is_err());
+
+        // Not even a chat has been created for it
+        assert!(get_pub_key(&foreign).await.is_none());
+
+        // Hosts that can't own an onion service don't get a handshake at all
+        let mut typo = client.hello();
+        typo.hostname = SERVER.replacen(&SERVER[..1], if SERVER.starts_with('a') { "b" } else { "a" }, 1);
+        assert!(Handshake::new(&typo.hostname).await.is_err());
+        assert!(Handshake::accept(typo).await.is_err());
+    });
+}
+
+#[test]
+fn identities_can_not_be_replayed() {
+    run_test(async {
+        let (client, server) = handshake().await;
+        let identity = client.identity().await.unwrap();
+        server.verify(&identity).await.unwrap();
+        server.verify(&client.identity().await.unwrap()).await.unwrap();
+
+        // The same hello again, but the server challenges with a new nonce
+        let replayed = Handshake::accept(client.hello()).await.unwrap();
+        assert!(replayed.verify(&identity).await.is_err());
+    });
+}
+
+#[test]
+fn packets_are_authenticated_with_the_session_key() {
+    run_test(async {
+        let (client, server) = handshake().await;
+        let (client, server) = (client.session().unwrap(), server.session().unwrap());
+
+        let C2SPacket::Authenticated(mut sealed) = client.seal_c2s(C2SPacket::MessageReceived(1)).unwrap() else {
+            panic!("Expected an authenticated packet");
+        };
+        assert!(matches!(server.open_c2s(&sealed), Ok(C2SPacket::MessageReceived(1))));
+
+        // Packets can't be reflected to the sender, authenticated with another session or modified
+        assert!(client.open_s2c(&sealed).is_err());
+        let (other, _) = handshake().await;
+        assert!(other.session().unwrap().open_c2s(&sealed).is_err());
+
+        sealed.packet[4] ^= 1;
+        assert!(server.open_c2s(&sealed).is_err());
+    });
+}
+
+#[test]
+fn messages_are_delivered_and_acknowledged() {
+    run_test(async {
+        let (client, server) = connect().await;
+
+        client.send_msg("Hello server").await.unwrap();
+        let received = wait_for::<WsMessagePayload>(|p| p.receiver == *CLIENT).await;
+        assert_eq!(received.message, "Hello server");
+        let date = wait_for_msg_status(&SERVER, WsMessageStatus::Success).await;
+
+        server.send_msg("Hello client").await.unwrap();
+        let received = wait_for::<WsMessagePayload>(|p| p.receiver == *SERVER).await;
+        assert_eq!(received.message, "Hello client");
+        wait_for_msg_status(&CLIENT, WsMessageStatus::Success).await;
+
+        // The sent message has been acknowledged and the received one is stored on the other side
+        let (sent, stored) = STORAGE
+            .read()
+            .await
+            .get_data(|e| {
+                let sent = e.chats[SERVER.as_str()].messages.iter().find(|m| m.date == date).cloned();
+                let stored = e.chats[CLIENT.as_str()].messages.iter().find(|m| m.date == date).cloned();
+                Ok((sent.unwrap(), stored.unwrap()))
+            })
+            .await
+            .unwrap();
+
+        assert!(sent.self_sent && matches!(sent.status, WsMessageStatus::Success));
+        assert!(!stored.self_sent && stored.msg == "Hello server");
+
+        disconnect(&server).await;
+    });
+}
+
+#[test]
+fn server_sends_queued_packets_without_delay() {
+    run_test(async {
+        let (client, server) = connect().await;
+
+        // Every message is answered with a receipt, so this queues a lot of packets on both sides
+        const COUNT: usize = 40;
+        let start = std::time::Instant::now();
+        for i in 0..COUNT {
+            server.send_msg(&format!("History {}", i)).await.unwrap();
+        }
+
+        for _ in 0..COUNT {
+            wait_for_msg_status(&CLIENT, WsMessageStatus::Success).await;
+        }
+
+        // Polling the queue would have sent just 10 packets per second
+        let elapsed = start.elapsed();
+        assert!(elapsed < Duration::from_secs(3), "Took {:?}", elapsed);
+
+        disconnect(&client).await;
+    });
+}
+
+#[test]
+fn undecryptable_messages_fail_on_the_sender() {
+    run_test(async {
+        let (client, _) = connect().await;
+
+        // Encrypting with a key the other side does not have, so it can't decrypt the message
+        let original = get_pub_key(&SERVER).await.unwrap();
+        let wrong: PublicKey = PrivateKey::generate_pair().unwrap().try_into().unwrap();
+        set_pub_key(&SERVER, wrong).await;
+
+        client.send_msg("Can't read this").await.unwrap();
+        let failed = wait_for_msg_status(&SERVER, WsMessageStatus::Failed).await;
+        set_pub_key(&SERVER, original).await;
+
+        let status = STORAGE
+            .read()
+            .await
+            .get_data(|e| {
+                let msg = e.chats[SERVER.as_str()].messages.iter().find(|m| m.date == failed);
+                Ok(msg.unwrap().status.clone())
+            })
+            .await
+            .unwrap();
+        assert!(matches!(status, WsMessageStatus::Failed));
+
+        disconnect(&client).await;
+    });
+}
+
+#[test]
+fn dropped_connections_are_reconnected() {
+    run_test(async {
+        let (_, server) = connect().await;
+        assert_eq!(MESSAGING.read().await.get_peer_state(&SERVER).await, PeerState::Connected);
+
+        // The accepting side drops the connection, the dialing side still wants it
+        server.close().await.unwrap();
+        let backoff = wait_for_peer_state(&SERVER, |s| matches!(s, PeerState::Backoff { .. })).await;
+        assert!(matches!(backoff, PeerState::Backoff { attempt: 1, .. }));
+
+        wait_for_peer_state(&SERVER, |s| *s == PeerState::Connecting).await;
+        wait_for_peer_state(&SERVER, |s| *s == PeerState::Connected).await;
+        wait_for_peer_state(&CLIENT, |s| *s == PeerState::Connected).await;
+
+        let client = MESSAGING.read().await.get_or_connect(&SERVER).await.unwrap();
+        disconnect(&client).await;
+        assert_eq!(MESSAGING.read().await.get_peer_state(&SERVER).await, PeerState::Idle);
+    });
+}
+
+#[test]
+fn backoff_grows_with_jitter_up_to_the_limit() {
+    for attempt in 1..10 {
+        let full = RECONNECT_BASE_DELAY.saturating_mul(1 << (attempt - 1)).min(*RECONNECT_MAX_DELAY);
+        let delay = backoff_delay(attempt);
+        assert!(delay >= full / 2 && delay <= full, "attempt {}: {:?}", attempt, delay);
+    }
+
+    assert!(backoff_delay(u32::MAX) <= *RECONNECT_MAX_DELAY);
+}
+
+#[test]
+fn exactly_one_side_keeps_its_dialed_connection() {
+    let a = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    let b = "baaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+
+    assert!(keeps_dialed(a, b));
+    assert!(!keeps_dialed(b, a));
+    assert!(keeps_dialed(&CLIENT, &SERVER) != keeps_dialed(&SERVER, &CLIENT));
+}
+
+#[test]
+fn simultaneous_dials_keep_one_connection_and_move_queued_packets() {
+    run_test(async {
+        let (client, _) = connect().await;
+
+        // Pretending the other side dialed us at the same time, its ws actor has not sent anything yet
+        let (_c_tx, c_rx) = async_channel::unbounded();
+        let (s_tx, s_rx) = async_channel::bounded(64);
+        let channels = (c_rx, s_tx.clone(), s_rx);
+
+        let accepted = Connection::new_server(&SERVER, channels.clone()).await;
+        accepted.send_msg("Sent while dialing").await.unwrap();
+
+        // Our hostname is the smaller one, so the connection we dialed wins
+        assert!(keeps_dialed(&CLIENT, &SERVER));
+        let res = MESSAGING.read().await.get_or_insert(&SERVER, &channels).await;
+        assert!(res.is_err());
+
+        let kept = MESSAGING.read().await.connections.read().await[SERVER.as_str()].clone();
+        assert!(kept.is_same(&client));
+        assert!(s_tx.is_closed());
+
+        // The queued message is delivered over the kept connection instead
+        let received = wait_for::<WsMessagePayload>(|p| p.receiver == *CLIENT).await;
+        assert_eq!(received.message, "Sent while dialing");
+        wait_for_msg_status(&SERVER, WsMessageStatus::Success).await;
+
+        disconnect(&client).await;
+    });
+}
+
+#[test]
+fn accepted_connections_hold_back_moved_packets_until_verified() {
+    run_test(async {
+        // Two connections the peer dialed, the older one still has more packets queued than fit into a send queue
+        let (old_c_tx, c_rx) = async_channel::unbounded();
+        let (s_tx, s_rx) = async_channel::bounded(128);
+        let old = Connection::new_server(&CLIENT, (c_rx, s_tx.clone(), s_rx)).await;
+        for date in 0..100 {
+            s_tx.send(S2CPacket::MessageReceived(date)).await.unwrap();
+        }
+
+        let (new_c_tx, c_rx) = async_channel::unbounded();
+        let (s_tx, s_rx) = async_channel::bounded(64);
+        let new = Connection::new_server(&CLIENT, (c_rx, s_tx, s_rx.clone())).await;
+
+        let messaging = MESSAGING.read().await;
+        messaging.insert_connection(&CLIENT, old.clone()).await.unwrap();
+        // The accepted connection is kept, its ws actor does not drain the queue until the handshake is done
+        let kept = timeout(TIMEOUT, messaging.insert_connection(&CLIENT, new.clone()))
+            .await
+            .expect("Moving the queued packets blocked")
+            .unwrap();
+        drop(messaging);
+
+        assert!(kept.is_same(&new));
+        assert!(s_rx.is_empty());
+
+        // Once the peer knows it is verified, every packet is sent in order
+        kept.release_queued().await;
+        for date in 0..100 {
+            let packet = timeout(TIMEOUT, s_rx.recv()).await.unwrap().unwrap();
+            assert!(matches!(packet, S2CPacket::MessageReceived(d) if d == date));
+        }
+
+        drop((old_c_tx, new_c_tx));
+        kept.shutdown().await.unwrap();
+        let res = timeout(TIMEOUT, async {
+            while MESSAGING.read().await.is_connected(&CLIENT).await {
+                sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await;
+        res.expect("Connections have not been removed");
+    });
+}
+
+#[test]
+fn replayed_packets_are_rejected() {
+    run_test(async {
+        let (client, server) = handshake().await;
+        let (client, server) = (client.session().unwrap(), server.session().unwrap());
+
+        let sealed: Vec<_> = (0..3)
+            .map(|i| match server.seal_s2c(S2CPacket::MessageReceived(i)).unwrap() {
+                S2CPacket::Authenticated(p) => p,
+                _ => panic!("Expected an authenticated packet"),
+            })
+            .collect();
+
+        // Every packet is accepted just once and only after the ones sent before it
+        client.open_s2c(&sealed[1]).unwrap();
+        assert!(client.open_s2c(&sealed[0]).is_err());
+        assert!(client.open_s2c(&sealed[1]).is_err());
+        client.open_s2c(&sealed[2]).unwrap();
+
+        // The sequence number is authenticated as well
+        let mut changed = sealed[2].clone();
+        changed.sequence += 1;
+        assert!(client.open_s2c(&changed).is_err());
+    });
+}
+
+#[test]
+fn replay_guard_rejects_duplicates_and_out_of_order_packets() {
+    let mut guard = ReplayGuard::default();
+    assert!(guard.check(0).is_err());
+
+    guard.check(1).unwrap();
+    guard.check(2).unwrap();
+    assert!(guard.check(2).is_err());
+
+    // Skipped sequence numbers can't be delivered later on
+    guard.check(10).unwrap();
+    assert!(guard.check(9).is_err());
+    assert!(guard.check(3).is_err());
+    assert!(guard.check(10).is_err());
+    guard.check(11).unwrap();
+}
+
+#[test]
+fn packets_are_padded_to_blocks() {
+    for len in [0, 1, 200, PADDED_PACKET_SIZE - 4, PADDED_PACKET_SIZE - 3, 5000] {
+        let packet = vec![b'a'; len];
+        let padded = pad_packet(&packet).unwrap();
+
+        assert_eq!(padded.len() % PADDED_PACKET_SIZE, 0);
+        assert_eq!(padded.len(), (len + 4).div_ceil(PADDED_PACKET_SIZE) * PADDED_PACKET_SIZE);
+        assert_eq!(unpad_packet(&padded).unwrap(), packet);
+    }
+
+    assert!(unpad_packet(b"not padded").is_err());
+    assert!(unpad_packet(&[0xff; PADDED_PACKET_SIZE]).is_err());
+}
+
+#[test]
+fn cover_packets_look_like_messages_on_the_wire() {
+    let key: PublicKey = PrivateKey::generate_pair().unwrap().try_into().unwrap();
+    let session = Session::derive(b"shared secret", b"transcript").unwrap();
+
+    let packets = [
+        S2CPacket::Message((now_millis(), key.encrypt(b"Hi").unwrap())),
+        S2CPacket::Message((now_millis(), key.encrypt(&[b'a'; 400]).unwrap())),
+        S2CPacket::MessageReceived(now_millis()),
+        S2CPacket::Cover(cover_payload()),
+    ];
+
+    // What actually gets sent is the sealed packet, so that one has to have the same size every time
+    let sizes: Vec<usize> = packets
+        .into_iter()
+        .map(|p| {
+            let sealed: Vec<u8> = session.seal_s2c(p).unwrap().try_into().unwrap();
+            sealed.len()
+        })
+        .collect();
+
+    assert!(sizes.iter().all(|s| *s == sizes[0]), "{:?}", sizes);
+}
+
+#[test]
+fn cover_traffic_is_dropped_by_the_receiver() {
+    run_test(async {
+        set_cover_traffic(Some(Duration::from_millis(20))).unwrap();
+        assert!(fs::read_to_string(get_cover_traffic_path()).unwrap().contains("20"));
+        let (client, server) = connect().await;
+        sleep(Duration::from_millis(500)).await;
+
+        // Real messages still get through and the dummy packets never show up as messages
+        server.send_msg("Between the cover traffic").await.unwrap();
+        let received = wait_for::<WsMessagePayload>(|_| true).await;
+        assert_eq!(received.message, "Between the cover traffic");
+        wait_for_msg_status(&CLIENT, WsMessageStatus::Success).await;
+
+        set_cover_traffic(None).unwrap();
+        disconnect(&client).await;
+    });
+}
+
+#[test]
+fn locking_the_storage_closes_connections_until_unlocked() {
+    run_test(async {
+        connect().await;
+
+        MESSAGING.read().await.close_all().await;
+        STORAGE.write().await.lock().await.unwrap();
+        wait_for_status(&SERVER, WsClientStatus::Disconnected).await;
+        wait_for_status(&CLIENT, WsClientStatus::Disconnected).await;
+
+        // Nothing is dialed while the storage is locked
+        sleep(*RECONNECT_BASE_DELAY * 2).await;
+        let mgr = MESSAGING.read().await;
+        assert!(!mgr.is_connected(&SERVER).await && !mgr.is_connected(&CLIENT).await);
+        assert_eq!(mgr.get_peer_state(&SERVER).await, PeerState::Idle);
+        drop(mgr);
+
+        STORAGE.write().await.try_unlock(b"loopback").await.unwrap();
+        MESSAGING.read().await.reconnect_active().await;
+        wait_for_status(&SERVER, WsClientStatus::Connected).await;
+        wait_for_status(&CLIENT, WsClientStatus::Connected).await;
+
+        let client = MESSAGING.read().await.connections.read().await[SERVER.as_str()].clone();
+        disconnect(&client).await;
+    });
+}
+
+#[test]
+fn client_auth_keys_are_written_for_tor() {
+    run_test(async {
+        let authorized = get_authorized_clients_dir().join(format!("{}.auth", *HOSTNAME));
+        let private = std::path::Path::new(CONFIG.client_auth_dir()).join(format!("{}.auth_private", *HOSTNAME));
+
+        // Issuing the same contact twice hands out the same key
+        let key = issue_client_auth(&CLIENT).await.unwrap();
+        assert_eq!(issue_client_auth(&CLIENT).await.unwrap(), key);
+
+        let public = encode_client_auth_key(&client_auth_public_key(&key).unwrap());
+        assert_eq!(fs::read_to_string(&authorized).unwrap(), format!("descriptor:x25519:{}", public));
+
+        // The contact got our key in its invitation and uses it to dial us
+        let encoded = encode_client_auth_key(&key);
+        set_remote_client_auth(&SERVER, Some(decode_client_auth_key(&encoded).unwrap())).await.unwrap();
+        assert_eq!(
+            fs::read_to_string(&private).unwrap(),
+            format!("{}:descriptor:x25519:{}", *HOSTNAME, encoded)
+        );
+
+        assert!(set_remote_client_auth(&SERVER, Some(vec![1; 5])).await.is_err());
+        assert!(decode_client_auth_key("not a key").is_err());
+
+        // Revoked keys are removed, so tor does not accept them anymore
+        revoke_client_auth(&CLIENT).await.unwrap();
+        set_remote_client_auth(&SERVER, None).await.unwrap();
+        assert!(!authorized.exists());
+        assert!(!private.exists());
+    });
+}
+
+/// Replaces the key and the pinned fingerprint we know the given contact by
+async fn set_known_key(hostname: &str, key: Option<PublicKey>, pinned: Option<String>) {
+    STORAGE
+        .read()
+        .await
+        .modify_storage_data(|e| {
+            let chat = e.chats.get_mut(hostname).unwrap();
+            chat.rec_pub_key = key;
+            chat.rec_key_fingerprint = pinned;
+            Ok(())
+        })
+        .await
+        .unwrap();
+}
+
+#[test]
+fn contact_cards_are_signed_by_the_issuer() {
+    run_test(async {
+        // Our server invites the dialing side
+        let card = ContactCard::create(&CLIENT, Some("Server & co".to_string()), true).await.unwrap();
+        let uri = card.to_uri();
+        assert!(uri.starts_with(&format!("enkrypton:{}?", *SERVER)));
+        assert_eq!(ContactCard::parse(&uri).unwrap(), card);
+        card.verify(&CLIENT).unwrap();
+
+        // Issued to someone else or changed afterwards
+        assert!(card.verify(&SERVER).is_err());
+        let renamed = ContactCard::parse(&uri.replace("Server", "Mallory")).unwrap();
+        assert!(renamed.verify(&CLIENT).is_err());
+        assert!(renamed.import().await.is_err());
+
+        assert!(ContactCard::parse(&uri.replace("v=1", "v=2")).is_err());
+        assert!(ContactCard::parse(&uri.replace("enkrypton:", "mailto:")).is_err());
+        assert!(ContactCard::parse(&format!("enkrypton:{}", *SERVER)).is_err());
+
+        assert!(render_qr_svg(&uri).unwrap().contains("<svg"));
+        assert!(render_qr_png(&uri).unwrap().starts_with(b"\x89PNG"));
+
+        revoke_client_auth(&CLIENT).await.unwrap();
+    });
+}
+
+#[test]
+fn contact_cards_pin_the_key_of_the_issuer() {
+    run_test(async {
+        let card = ContactCard::create(&CLIENT, Some("Server".to_string()), false).await.unwrap();
+        card.import().await.unwrap();
+
+        let known = get_pub_key(&SERVER).await;
+        let nickname = STORAGE
+            .read()
+            .await
+            .get_data(|e| Ok(e.chats[SERVER.as_str()].nickname.clone()))
+            .await
+            .unwrap();
+        assert_eq!(nickname.as_deref(), Some("Server"));
+
+        // The first identity has to use the key of the card
+        set_known_key(&SERVER, None, Some(card.fingerprint.clone())).await;
+        let (client, server) = handshake().await;
+        client.verify(&server.identity().await.unwrap()).await.unwrap();
+        assert_eq!(get_pub_key(&SERVER).await.unwrap().fingerprint().unwrap(), card.fingerprint);
+
+        set_known_key(&SERVER, None, Some("00".repeat(32))).await;
+        let (client, server) = handshake().await;
+        assert!(client.verify(&server.identity().await.unwrap()).await.is_err());
+        assert!(get_pub_key(&SERVER).await.is_none());
+
+        set_known_key(&SERVER, known, None).await;
+    });
+}
+
+#[test]
+fn tcp_transport_only_dials_known_peers() {
+    HARNESS.runtime.block_on(async {
+        let transport = TcpTransport::new(CONFIG.get_hidden_service_host().parse().unwrap());
+        let url = url::Url::parse(&format!("ws://{}.onion/ws/", *HOSTNAME)).unwrap();
+        assert!(!transport.uses_tor(&HOSTNAME));
+        assert!(transport.dial(&HOSTNAME, &url).await.is_err());
+
+        // Our own webserver is listening there, so dialing it works
+        transport.add_peer(&HOSTNAME, CONFIG.get_hidden_service_host().parse().unwrap());
+        assert!(transport.dial(&HOSTNAME, &url).await.is_ok());
+    });
+}
+
+#[test]
+fn disconnects_remove_both_connections() {
+    run_test(async {
+        // Once closed by the dialing side...
+        let (client, _) = connect().await;
+        disconnect(&client).await;
+
+        // ...and once by the accepting side
+        let (_, server) = connect().await;
+        disconnect(&server).await;
+
+        // Reconnecting works afterwards and verifies with the stored keys
+        let (client, _) = connect().await;
+        disconnect(&client).await;
+    });
+}
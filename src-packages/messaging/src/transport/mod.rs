@@ -0,0 +1,92 @@
+mod tcp;
+mod tor;
+#[cfg(unix)]
+mod unix;
+
+use std::{
+    fmt::Debug,
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+};
+#[cfg(unix)]
+use std::path::PathBuf;
+
+use anyhow::Result;
+use lazy_static::lazy_static;
+use tokio::io::{AsyncRead, AsyncWrite};
+use url::Url;
+
+pub use tcp::TcpTransport;
+pub use tor::TorTransport;
+#[cfg(unix)]
+pub use unix::UnixTransport;
+
+lazy_static! {
+    /// The transport every connection is dialed and accepted with, tor unless another one is set at startup
+    static ref TRANSPORT: RwLock<Arc<dyn Transport>> = RwLock::new(Arc::new(TorTransport));
+}
+
+/// Any stream the websocket to a peer can run over
+pub trait AsyncStream: AsyncRead + AsyncWrite + Unpin + Send + Sync + Debug {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + Sync + Debug> AsyncStream for T {}
+
+/// The stream a transport has dialed
+pub type TransportStream = Box<dyn AsyncStream>;
+
+/// Where the webserver accepts connections of other peers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenAddr {
+    /// A tcp address, e.g. the local port tor forwards the hidden service to
+    Tcp(SocketAddr),
+    /// A unix socket at the given path
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+/// Describes how peers are reached and where we can be reached by them.
+/// The protocol itself does not care, it just needs a stream to run the websocket over
+#[async_trait::async_trait]
+pub trait Transport: Send + Sync + Debug {
+    /// Opens a stream to the given peer
+    ///
+    /// # Arguments
+    ///
+    /// * `onion_hostname` - The hostname of the peer
+    /// * `onion_addr` - The websocket url of the peer
+    ///
+    /// # Returns
+    ///
+    /// The opened stream
+    async fn dial(&self, onion_hostname: &str, onion_addr: &Url) -> Result<TransportStream>;
+
+    /// # Returns
+    ///
+    /// Where the webserver should listen for other peers
+    fn listen_addr(&self) -> ListenAddr;
+
+    /// Whether dialing the given peer needs tor to be running
+    ///
+    /// # Arguments
+    ///
+    /// * `onion_hostname` - The hostname of the peer
+    ///
+    fn uses_tor(&self, onion_hostname: &str) -> bool;
+}
+
+/// Sets the transport every following connection uses, should be called once at startup (before the webserver is started)
+///
+/// # Arguments
+///
+/// * `transport` - The transport to use from now on
+///
+pub fn set_transport(transport: Arc<dyn Transport>) {
+    *TRANSPORT.write().unwrap() = transport;
+}
+
+/// # Returns
+///
+/// The transport that is currently used
+pub fn get_transport() -> Arc<dyn Transport> {
+    TRANSPORT.read().unwrap().clone()
+}
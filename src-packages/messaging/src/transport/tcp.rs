@@ -0,0 +1,72 @@
+use std::{collections::HashMap, net::SocketAddr, sync::RwLock};
+
+use anyhow::{anyhow, Result};
+use tokio::net::TcpStream;
+use url::Url;
+
+use super::{ListenAddr, Transport, TransportStream};
+
+/// Connects to peers over plain tcp, without tor (e.g. in a LAN or for testing).
+/// As there is no tor to resolve onion hostnames, the address of every peer has to be added first
+#[derive(Debug)]
+pub struct TcpTransport {
+    /// Where our webserver listens
+    listen: SocketAddr,
+    /// The address of every peer by its onion hostname
+    peers: RwLock<HashMap<String, SocketAddr>>,
+}
+
+impl TcpTransport {
+    /// Creates a new tcp transport without any known peers
+    ///
+    /// # Arguments
+    ///
+    /// * `listen` - The address our webserver should listen to
+    ///
+    /// # Returns
+    ///
+    /// The constructed transport
+    pub fn new(listen: SocketAddr) -> Self {
+        Self {
+            listen,
+            peers: RwLock::default(),
+        }
+    }
+
+    /// Adds or replaces the address of a peer
+    ///
+    /// # Arguments
+    ///
+    /// * `onion_hostname` - The hostname of the peer
+    /// * `addr` - The address its webserver listens to
+    ///
+    pub fn add_peer(&self, onion_hostname: &str, addr: SocketAddr) {
+        self.peers
+            .write()
+            .unwrap()
+            .insert(onion_hostname.to_string(), addr);
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for TcpTransport {
+    async fn dial(&self, onion_hostname: &str, _onion_addr: &Url) -> Result<TransportStream> {
+        let addr = self
+            .peers
+            .read()
+            .unwrap()
+            .get(onion_hostname)
+            .cloned()
+            .ok_or(anyhow!("The address of {} is unknown", onion_hostname))?;
+
+        Ok(Box::new(TcpStream::connect(addr).await?))
+    }
+
+    fn listen_addr(&self) -> ListenAddr {
+        ListenAddr::Tcp(self.listen)
+    }
+
+    fn uses_tor(&self, _onion_hostname: &str) -> bool {
+        false
+    }
+}
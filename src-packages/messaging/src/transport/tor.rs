@@ -0,0 +1,30 @@
+use std::net::{Ipv4Addr, SocketAddr};
+
+use anyhow::Result;
+use shared::config::CONFIG;
+use url::Url;
+
+use crate::client::SocksProxy;
+
+use super::{ListenAddr, Transport, TransportStream};
+
+/// Dials peers through the tor socks proxy and listens on the local port tor forwards our hidden service to
+#[derive(Debug, Default)]
+pub struct TorTransport;
+
+#[async_trait::async_trait]
+impl Transport for TorTransport {
+    async fn dial(&self, onion_hostname: &str, onion_addr: &Url) -> Result<TransportStream> {
+        // Every contact gets its own tor circuits, so conversations can't be correlated
+        let proxy = SocksProxy::for_peer(onion_hostname)?;
+        Ok(Box::new(proxy.connect(onion_addr).await?))
+    }
+
+    fn listen_addr(&self) -> ListenAddr {
+        ListenAddr::Tcp(SocketAddr::from((Ipv4Addr::LOCALHOST, CONFIG.service_port())))
+    }
+
+    fn uses_tor(&self, _onion_hostname: &str) -> bool {
+        true
+    }
+}
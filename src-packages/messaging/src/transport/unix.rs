@@ -0,0 +1,71 @@
+use std::{collections::HashMap, path::PathBuf, sync::RwLock};
+
+use anyhow::{anyhow, Result};
+use tokio::net::UnixStream;
+use url::Url;
+
+use super::{ListenAddr, Transport, TransportStream};
+
+/// Connects to peers on the same machine over unix sockets
+#[derive(Debug)]
+pub struct UnixTransport {
+    /// The socket our webserver listens on
+    listen: PathBuf,
+    /// The socket of every peer by its onion hostname
+    peers: RwLock<HashMap<String, PathBuf>>,
+}
+
+impl UnixTransport {
+    /// Creates a new unix socket transport without any known peers
+    ///
+    /// # Arguments
+    ///
+    /// * `listen` - The path of the socket our webserver should listen on
+    ///
+    /// # Returns
+    ///
+    /// The constructed transport
+    pub fn new(listen: PathBuf) -> Self {
+        Self {
+            listen,
+            peers: RwLock::default(),
+        }
+    }
+
+    /// Adds or replaces the socket of a peer
+    ///
+    /// # Arguments
+    ///
+    /// * `onion_hostname` - The hostname of the peer
+    /// * `path` - The socket its webserver listens on
+    ///
+    pub fn add_peer(&self, onion_hostname: &str, path: PathBuf) {
+        self.peers
+            .write()
+            .unwrap()
+            .insert(onion_hostname.to_string(), path);
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for UnixTransport {
+    async fn dial(&self, onion_hostname: &str, _onion_addr: &Url) -> Result<TransportStream> {
+        let path = self
+            .peers
+            .read()
+            .unwrap()
+            .get(onion_hostname)
+            .cloned()
+            .ok_or(anyhow!("The socket of {} is unknown", onion_hostname))?;
+
+        Ok(Box::new(UnixStream::connect(path).await?))
+    }
+
+    fn listen_addr(&self) -> ListenAddr {
+        ListenAddr::Unix(self.listen.clone())
+    }
+
+    fn uses_tor(&self, _onion_hostname: &str) -> bool {
+        false
+    }
+}
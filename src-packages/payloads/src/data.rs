@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use encryption::{PublicKey, PrivateKey};
+use encryption::{PublicKey, PrivateKey, SecretString};
 use serde::{Deserialize, Serialize};
 
 use zeroize::{Zeroize, ZeroizeOnDrop};
@@ -14,10 +14,8 @@ use crate::payloads::WsMessageStatus;
 /// This struct includes all data that can be stored on the disk
 #[cfg_attr(feature="export_ts", derive(TS))]
 #[cfg_attr(feature="export_ts", ts(export))]
-#[derive(Clone, Debug, Zeroize, ZeroizeOnDrop, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct StorageData {
-    //REVIEW don't just skip this, key is pretty important to erase
-    #[zeroize(skip)]
     /// All chats that are stored on the disk stored by the onion address of the receiver and the chat
     pub chats: HashMap<String, StorageChat>,
 }
@@ -40,6 +38,19 @@ pub struct StorageChat {
     /// Private key of this messenger used to decrypt the messages that are being received
     #[cfg_attr(feature="export_ts", ts(skip))]
     pub priv_key: PrivateKey,
+
+    /// The fingerprint of the key the receiver will identify with, pinned by a contact card
+    #[serde(default)]
+    pub rec_key_fingerprint: Option<String>,
+
+    /// The client authorization key we issued to the receiver, so it can discover our onion service
+    #[cfg_attr(feature="export_ts", ts(skip))]
+    #[serde(default)]
+    pub auth_key: Option<Vec<u8>>,
+    /// The client authorization key the receiver issued to us, used by tor to dial its onion service
+    #[cfg_attr(feature="export_ts", ts(skip))]
+    #[serde(default)]
+    pub rec_auth_key: Option<Vec<u8>>,
 }
 
 impl StorageChat {
@@ -48,17 +59,22 @@ impl StorageChat {
     /// # Arguments
     ///
     /// * `receiver_onion` - The onion address of the receiver
+    /// * `priv_key` - Our key pair for this chat, should come from `PrivateKey::generate_pair_async`
     ///
     /// # Returns
     ///
     /// The constructed storage chat
-    pub fn new(_receiver_onion: &str) -> Self {
+    pub fn new(_receiver_onion: &str, priv_key: PrivateKey) -> Self {
         Self {
             messages: Vec::new(),
             nickname: None,
 
             rec_pub_key: None,
-            priv_key: PrivateKey::generate_pair().unwrap()
+            priv_key,
+
+            rec_key_fingerprint: None,
+            auth_key: None,
+            rec_auth_key: None,
         }
     }
 }
@@ -73,13 +89,31 @@ pub struct ChatMessage {
     #[zeroize(skip)]
     /// The current status of this message
     pub status: WsMessageStatus,
-    //NOTE This message should not be lying around in memory unencrypted I guess
-    pub msg: String,
+    /// The decrypted message, kept in locked memory that is wiped once it is dropped
+    #[cfg_attr(feature="export_ts", ts(type="string"))]
+    pub msg: SecretString,
     #[cfg_attr(feature="export_ts", ts(type="number"))]
     /// The date when this message was sent. Also acts as id.
     pub date: u128,
 }
 
+/// Wipes every chat including their keys and messages, the map is empty afterwards
+impl Zeroize for StorageData {
+    fn zeroize(&mut self) {
+        for (mut receiver, mut chat) in self.chats.drain() {
+            receiver.zeroize();
+            chat.zeroize();
+        }
+    }
+}
+
+impl Drop for StorageData {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+impl ZeroizeOnDrop for StorageData {}
+
 impl Default for StorageData {
     fn default() -> Self {
         Self {
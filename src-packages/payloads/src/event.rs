@@ -1,27 +1,110 @@
+use std::sync::{Arc, RwLock};
+
+use anyhow::anyhow;
+use async_channel::{Receiver, Sender};
+use lazy_static::lazy_static;
 use serde::{Serialize, Deserialize};
-use tauri::{AppHandle, Emitter, Error};
+use serde_json::Value;
+
+lazy_static! {
+    /// The sink every event of the core is emitted to, drops everything until a sink is set at startup
+    static ref EVENT_SINK: RwLock<Arc<dyn EventSink>> = RwLock::new(Arc::new(NoopEventSink));
+}
 
 //noinspection SpellCheckingInspection
 /// Describes any sendable payload and contains a function to get the name of the payload
 pub trait SendablePayload: Serialize + for<'de> Deserialize<'de> + Clone {
-    /// The name of the payload that should be used for tauri
+    /// The name of the payload, frontends listen to events with this name
     fn get_name(&self) -> String;
 }
 
-/// A trait to extend the AppHandle with a function to send a payload
-/// Extension trait for the `AppHandle` type.
-pub trait AppHandleExt {
-    /// Emits a payload of type `T`.
+/// Receives the events the core emits, so the core does not have to know whether
+/// a tauri frontend, the daemon, a test or nobody at all is listening
+pub trait EventSink: Send + Sync {
+    /// Emits a serialized payload
     ///
     /// # Arguments
     ///
-    /// * `payload` - The payload to be emitted.
+    /// * `name` - The name of the payload (see `SendablePayload::get_name`)
+    /// * `payload` - The serialized payload
+    ///
+    fn emit_event(&self, name: &str, payload: Value) -> anyhow::Result<()>;
+}
+
+/// Drops every event, used as long as no other sink has been set
+pub struct NoopEventSink;
+
+impl EventSink for NoopEventSink {
+    fn emit_event(&self, _name: &str, _payload: Value) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// An event that has been emitted to a `ChannelEventSink`
+#[derive(Debug, Clone)]
+pub struct EmittedEvent {
+    /// The name of the payload
+    pub name: String,
+    /// The serialized payload
+    pub payload: Value,
+}
+
+impl EmittedEvent {
+    /// Deserializes the payload again
+    ///
+    /// # Returns
     ///
-    fn emit_payload<T: SendablePayload>(&self, payload: T) -> Result<(), Error>;
+    /// The payload, or an error if this event is not a `T`
+    pub fn parse<T: SendablePayload>(&self) -> anyhow::Result<T> {
+        Ok(serde_json::from_value(self.payload.clone())?)
+    }
+}
+
+/// Sends every event into a channel, so they can be consumed somewhere else (or asserted in tests)
+pub struct ChannelEventSink {
+    tx: Sender<EmittedEvent>,
 }
 
-impl AppHandleExt for AppHandle {
-    fn emit_payload<T: SendablePayload>(&self, payload: T) -> Result<(), Error> {
-        self.emit(&payload.get_name(), payload)
+impl ChannelEventSink {
+    /// Creates a new sink with an unbounded channel
+    ///
+    /// # Returns
+    ///
+    /// The sink and the receiver of every event that is emitted to it
+    pub fn new() -> (Self, Receiver<EmittedEvent>) {
+        let (tx, rx) = async_channel::unbounded();
+        (Self { tx }, rx)
     }
-}
\ No newline at end of file
+}
+
+impl EventSink for ChannelEventSink {
+    fn emit_event(&self, name: &str, payload: Value) -> anyhow::Result<()> {
+        self.tx
+            .try_send(EmittedEvent {
+                name: name.to_string(),
+                payload,
+            })
+            .map_err(|e| anyhow!("Could not send event {}: {}", name, e))
+    }
+}
+
+/// Sets the sink every following event is emitted to, should be called once at startup
+///
+/// # Arguments
+///
+/// * `sink` - The sink to use from now on
+///
+pub fn set_event_sink(sink: Arc<dyn EventSink>) {
+    *EVENT_SINK.write().unwrap() = sink;
+}
+
+/// Emits a payload to the current event sink.
+///
+/// # Arguments
+///
+/// * `payload` - The payload to be emitted.
+///
+pub fn emit_payload<T: SendablePayload>(payload: T) -> anyhow::Result<()> {
+    let sink = EVENT_SINK.read().unwrap().clone();
+    sink.emit_event(&payload.get_name(), serde_json::to_value(payload)?)
+}
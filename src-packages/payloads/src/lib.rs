@@ -2,7 +2,10 @@
 pub mod packets;
 /// Payloads that are sent between frontend and backend
 pub mod payloads;
-/// Simple trait extension of the app handle to send payloads most easily
+/// The event sink payloads are emitted to (tauri, the daemon, ...)
 pub mod event;
 /// Data structures that are used in payloads, can be exported to typescript using `cargo test`
-pub mod data;
\ No newline at end of file
+pub mod data;
+
+#[cfg(test)]
+mod tests;
\ No newline at end of file
@@ -1,12 +1,14 @@
 use serde::{Deserialize, Serialize};
-use super::Identity;
+use super::{AuthenticatedPacket, Hello, Identity};
 
 
 /// All possible packets that can be sent from the client to the server
 #[derive(Debug, Serialize, Deserialize)]
 pub enum C2SPacket {
+    /// Starts the handshake, challenging the server
+    Hello(Hello),
     /// Sends over the identity of this client and the public key / hostname with it.
-    /// Contains a signature to verify the identity.
+    /// Contains a signature of the handshake to verify the identity.
     SetIdentity(Identity),
     /// A packet to tell the client that their identity has been verified
     IdentityVerified,
@@ -15,5 +17,18 @@ pub enum C2SPacket {
     /// Tell the server that the message with the given date could be successfully received
     MessageReceived(u128),
     /// And again, tell the server that the message was failed to send
-    MessageFailed(u128)
+    MessageFailed(u128),
+    /// Any packet after the handshake, authenticated with the session key
+    Authenticated(AuthenticatedPacket),
+    /// A dummy packet as long as a message, sent as cover traffic and dropped by the server
+    Cover(Vec<u8>),
+}
+
+impl C2SPacket {
+    /// # Returns
+    ///
+    /// Whether this packet is part of the handshake, so it is sent before there is a session key
+    pub fn is_handshake(&self) -> bool {
+        matches!(self, C2SPacket::Hello(_) | C2SPacket::SetIdentity(_))
+    }
 }
\ No newline at end of file
@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+
+/// The first packet of the handshake each side sends, contains a fresh challenge for the other side
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hello {
+    /// The onion hostname of the sender
+    pub hostname: String,
+    /// A random nonce, which has to be signed by the other side (so old handshakes can't be replayed)
+    pub nonce: Vec<u8>,
+    /// The ephemeral x25519 public key, used to derive the key of this session
+    pub ephemeral_key: Vec<u8>,
+}
+
+/// A packet that has been authenticated with the key of the current session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthenticatedPacket {
+    /// Increases with every packet of the session, so packets can't be replayed
+    pub sequence: u64,
+    /// The serialized packet, padded so every packet has a similar size
+    pub packet: Vec<u8>,
+    /// The HMAC of the packet, created with the session key
+    pub mac: Vec<u8>,
+}
@@ -2,13 +2,17 @@ use encryption::PublicKey;
 use serde::{Serialize, Deserialize};
 
 
-/// The identity of a client or server used to well verify the identity of the given side
+/// The identity of a client or server used to well verify the identity of the given side.
+/// Both signatures cover the hellos of the current handshake, so the identity is only valid for this session
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Identity {
     /// The hostname of the client / server
     pub hostname: String,
-    /// The signature of that hostname, used to verify its identity (derived from the generated RSA Private Key)
+    /// The signature of the handshake, used to verify its identity (derived from the generated RSA Private Key)
     pub signature: Vec<u8>,
     /// And the public key that should be used when sending messages to the side
     pub pub_key: PublicKey,
+    /// Signature of the handshake with the ed25519 key of the onion service.
+    /// Proves that the side actually owns the onion address it claims
+    pub onion_signature: Vec<u8>,
 }
\ No newline at end of file
@@ -8,8 +8,10 @@ use tokio_tungstenite::tungstenite::Message as TungsteniteMessage;
 mod client_2_server;
 mod server_2_client;
 mod identity;
+mod handshake;
 
 pub use identity::*;
+pub use handshake::*;
 pub use client_2_server::*;
 pub use server_2_client::*;
 
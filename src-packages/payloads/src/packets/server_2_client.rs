@@ -1,10 +1,12 @@
 use serde::{Deserialize, Serialize};
-use super::Identity;
+use super::{AuthenticatedPacket, Hello, Identity};
 
 /// All possible packets that can be sent from the server to the client
 #[derive(Debug, Serialize, Deserialize)]
 pub enum S2CPacket {
-    /// A packet to verify the server on client side. Again, contains the identity struct
+    /// Answers the hello of the client with our own challenge
+    Hello(Hello),
+    /// A packet to verify the server on client side, sent once the client has been verified. Again, contains the identity struct
     VerifyIdentity(Identity),
     /// Used to tell the client that its identity has been verified successfully
     IdentityVerified,
@@ -13,5 +15,18 @@ pub enum S2CPacket {
     /// Tell the client that the message with the given date could be successfully received
     MessageReceived(u128),
     /// And again, tell the client that the message was failed to send
-    MessageFailed(u128)
+    MessageFailed(u128),
+    /// Any packet after the handshake, authenticated with the session key
+    Authenticated(AuthenticatedPacket),
+    /// A dummy packet as long as a message, sent as cover traffic and dropped by the client
+    Cover(Vec<u8>),
+}
+
+impl S2CPacket {
+    /// # Returns
+    ///
+    /// Whether this packet is part of the handshake, so it is sent before there is a session key
+    pub fn is_handshake(&self) -> bool {
+        matches!(self, S2CPacket::Hello(_) | S2CPacket::VerifyIdentity(_))
+    }
 }
\ No newline at end of file
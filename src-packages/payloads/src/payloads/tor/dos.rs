@@ -0,0 +1,27 @@
+use serde::{Serialize, Deserialize};
+
+/// How many introductions the introduction points of our onion service let through
+#[cfg_attr(feature="export_ts", derive(ts_rs::TS))]
+#[cfg_attr(feature="export_ts", ts(export))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IntroDosRate {
+    /// The introductions allowed per second on average
+    pub rate_per_sec: u32,
+    /// The introductions allowed in a single second
+    pub burst_per_sec: u32,
+}
+
+/// Protections of our onion service against denial of service attacks, written to the `torrc`
+#[cfg_attr(feature="export_ts", derive(ts_rs::TS))]
+#[cfg_attr(feature="export_ts", ts(export))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TorDosDefenses {
+    /// Whether clients have to solve a proof-of-work puzzle once our service is under load
+    pub pow_enabled: bool,
+    /// Rate limits our introduction points enforce, disabled if None
+    pub intro_rate: Option<IntroDosRate>,
+    /// The most streams a single rendezvous circuit may open, 0 for unlimited
+    pub max_streams: u32,
+    /// Whether circuits exceeding `max_streams` are closed instead of just refusing the stream
+    pub max_streams_close_circuit: bool,
+}
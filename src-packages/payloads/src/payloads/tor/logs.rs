@@ -0,0 +1,52 @@
+use serde::{Serialize, Deserialize};
+
+/// The severity of a line tor has logged, ordered from least to most severe
+#[cfg_attr(feature="export_ts", derive(ts_rs::TS))]
+#[cfg_attr(feature="export_ts", ts(export))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TorLogLevel {
+    Debug,
+    Info,
+    Notice,
+    Warn,
+    Error,
+}
+
+impl TorLogLevel {
+    /// Parses the level out of a line tor has written to stdout (e.g. `... [notice] Bootstrapped 5%`)
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - The line to get the level of
+    ///
+    /// # Returns
+    ///
+    /// The level of the line, `Info` if the line does not contain one
+    pub fn from_line(line: &str) -> Self {
+        if line.contains("[err]") {
+            TorLogLevel::Error
+        } else if line.contains("[warn]") {
+            TorLogLevel::Warn
+        } else if line.contains("[notice]") {
+            TorLogLevel::Notice
+        } else if line.contains("[debug]") {
+            TorLogLevel::Debug
+        } else {
+            TorLogLevel::Info
+        }
+    }
+}
+
+/// A single line tor has logged
+#[cfg_attr(feature="export_ts", derive(ts_rs::TS))]
+#[cfg_attr(feature="export_ts", ts(export))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TorLogEntry {
+    /// How severe this line is
+    pub level: TorLogLevel,
+    #[cfg_attr(feature="export_ts", ts(type="number"))]
+    /// When this line was read, in milliseconds since the unix epoch
+    pub timestamp: u128,
+    /// The line itself
+    pub message: String,
+}
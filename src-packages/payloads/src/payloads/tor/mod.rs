@@ -1,3 +1,9 @@
 mod start_tor;
+mod logs;
+mod status;
+mod dos;
 
-pub use start_tor::*;
\ No newline at end of file
+pub use start_tor::*;
+pub use logs::*;
+pub use status::*;
+pub use dos::*;
\ No newline at end of file
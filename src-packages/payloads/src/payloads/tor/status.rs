@@ -0,0 +1,46 @@
+use serde::{Serialize, Deserialize};
+
+use crate::event::SendablePayload;
+
+/// The current health of the tor process as seen by the supervisor
+#[cfg_attr(feature="export_ts", derive(ts_rs::TS))]
+#[cfg_attr(feature="export_ts", ts(export))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TorStatus {
+    /// Tor has not been started yet or was stopped on purpose
+    Stopped,
+    /// Tor is starting up and bootstrapping into the network
+    Bootstrapping,
+    /// Tor is bootstrapped and the socks proxy is answering
+    Ready,
+    /// Tor is still running but the socks proxy stopped answering
+    Degraded,
+    /// Tor crashed or stalled and is being restarted
+    Restarting,
+}
+
+impl TorStatus {
+    /// # Returns
+    ///
+    /// Whether connections over the tor network can be made right now
+    pub fn is_ready(&self) -> bool {
+        *self == TorStatus::Ready
+    }
+}
+
+/// Tells the frontend (and other listeners) that the status of tor has changed
+#[cfg_attr(feature="export_ts", derive(ts_rs::TS))]
+#[cfg_attr(feature="export_ts", ts(export))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TorStatusPayload {
+    /// The new status of tor
+    pub status: TorStatus,
+    /// A short description why the status changed
+    pub message: String,
+}
+
+impl SendablePayload for TorStatusPayload {
+    fn get_name(&self) -> String {
+        "tor_status".to_string()
+    }
+}
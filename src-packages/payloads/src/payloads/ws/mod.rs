@@ -1,7 +1,9 @@
 mod msg;
 mod client_update;
 mod message_status;
+mod peer_state;
 
 pub use msg::*;
 pub use client_update::*;
-pub use message_status::*;
\ No newline at end of file
+pub use message_status::*;
+pub use peer_state::*;
\ No newline at end of file
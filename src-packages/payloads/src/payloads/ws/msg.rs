@@ -1,14 +1,15 @@
 use serde::{Serialize, Deserialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
 use crate::event::SendablePayload;
 #[cfg(feature="export_ts")]
 use ts_rs::TS;
 
 
-/// Payload is used to send newly arrived messages to the frontend
+/// Payload is used to send newly arrived messages to the frontend, the message is wiped once it has been emitted
 #[cfg_attr(feature="export_ts", derive(TS))]
 #[cfg_attr(feature="export_ts", ts(export))]
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
 pub struct WsMessagePayload {
     /// The hostname of the receiver that should receive this message
     pub receiver: String,
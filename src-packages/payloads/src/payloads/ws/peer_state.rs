@@ -0,0 +1,45 @@
+use serde::{Serialize, Deserialize};
+
+use crate::event::SendablePayload;
+
+/// The state of the connection to a single peer, more detailed than `WsClientStatus`
+#[cfg_attr(feature="export_ts", derive(ts_rs::TS))]
+#[cfg_attr(feature="export_ts", ts(export))]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PeerState {
+    /// Not connected and not trying to
+    #[default]
+    Idle,
+    /// Dialing the peer
+    Connecting,
+    /// Connected to the peer, both sides are verifying each other
+    Handshaking,
+    /// Both sides are verified, messages can be sent
+    Connected,
+    /// The connection failed or dropped, retrying after the given delay
+    Backoff {
+        /// How many attempts have failed in a row
+        attempt: u32,
+        /// How long to wait until the next attempt
+        retry_in_ms: u64,
+    },
+}
+
+/// Tells the frontend (and other listeners) that the connection to a peer changed its state
+#[cfg_attr(feature="export_ts", derive(ts_rs::TS))]
+#[cfg_attr(feature="export_ts", ts(export))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerStatePayload {
+    /// The peer whose state changed
+    pub hostname: String,
+    /// The state before this transition
+    pub previous: PeerState,
+    /// The new state
+    pub state: PeerState,
+}
+
+impl SendablePayload for PeerStatePayload {
+    fn get_name(&self) -> String {
+        "peer_state_update".to_string()
+    }
+}
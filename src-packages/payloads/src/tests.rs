@@ -0,0 +1,90 @@
+use std::sync::{Arc, Mutex};
+
+use lazy_static::lazy_static;
+
+use crate::{
+    event::{emit_payload, set_event_sink, ChannelEventSink, NoopEventSink, SendablePayload},
+    payloads::{WsClientStatus, WsClientUpdatePayload, WsMessagePayload},
+};
+
+lazy_static! {
+    /// The event sink is global, so tests that replace it must not run at the same time
+    static ref SINK_LOCK: Mutex<()> = Mutex::new(());
+}
+
+#[test]
+fn channel_sink_receives_payloads() {
+    let _lock = SINK_LOCK.lock().unwrap();
+    let (sink, rx) = ChannelEventSink::new();
+    set_event_sink(Arc::new(sink));
+
+    let payload = WsMessagePayload {
+        receiver: "receiver".to_string(),
+        message: "Hello".to_string(),
+    };
+    emit_payload(payload.clone()).unwrap();
+
+    let event = rx.try_recv().unwrap();
+    assert_eq!(event.name, payload.get_name());
+
+    let received: WsMessagePayload = event.parse().unwrap();
+    assert_eq!(received.receiver, payload.receiver);
+    assert_eq!(received.message, payload.message);
+    assert!(rx.try_recv().is_err());
+}
+
+#[test]
+fn channel_sink_keeps_order() {
+    let _lock = SINK_LOCK.lock().unwrap();
+    let (sink, rx) = ChannelEventSink::new();
+    set_event_sink(Arc::new(sink));
+
+    for status in [WsClientStatus::ConnectingProxy, WsClientStatus::Connected, WsClientStatus::Disconnected] {
+        emit_payload(WsClientUpdatePayload {
+            hostname: "host".to_string(),
+            status,
+        })
+        .unwrap();
+    }
+
+    let statuses: Vec<_> = (0..3)
+        .map(|_| rx.try_recv().unwrap().parse::<WsClientUpdatePayload>().unwrap().status)
+        .collect();
+
+    assert!(matches!(
+        statuses.as_slice(),
+        [WsClientStatus::ConnectingProxy, WsClientStatus::Connected, WsClientStatus::Disconnected]
+    ));
+}
+
+#[test]
+fn parse_rejects_other_payloads() {
+    let _lock = SINK_LOCK.lock().unwrap();
+    let (sink, rx) = ChannelEventSink::new();
+    set_event_sink(Arc::new(sink));
+
+    emit_payload(WsMessagePayload {
+        receiver: "receiver".to_string(),
+        message: "Hello".to_string(),
+    })
+    .unwrap();
+
+    let event = rx.try_recv().unwrap();
+    assert!(event.parse::<WsClientUpdatePayload>().is_err());
+}
+
+#[test]
+fn noop_sink_drops_payloads() {
+    let _lock = SINK_LOCK.lock().unwrap();
+    let (sink, rx) = ChannelEventSink::new();
+    set_event_sink(Arc::new(sink));
+    set_event_sink(Arc::new(NoopEventSink));
+
+    emit_payload(WsMessagePayload {
+        receiver: "receiver".to_string(),
+        message: "Hello".to_string(),
+    })
+    .unwrap();
+
+    assert!(rx.try_recv().is_err());
+}
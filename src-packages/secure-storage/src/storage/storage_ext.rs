@@ -1,9 +1,9 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, io::{self, Write}};
 
 use anyhow::{anyhow, bail, Result};
 use argon2::{password_hash::PasswordHashString, Argon2, PasswordVerifier};
 use byteorder::{WriteBytesExt, LE};
-use zeroize::{Zeroize, ZeroizeOnDrop};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 use crate::{
     consts::{FILE_ID_BYTES, KEY_LENGTH},
@@ -74,7 +74,7 @@ where
             .expect("Why did this come through?");
 
         // Then we can decrypt the data using the crypto key
-        let decrypted = aes_decrypt(&self.encrypted_data, crypto_key, &self.iv)?;
+        let decrypted = Zeroizing::new(aes_decrypt(&self.encrypted_data, crypto_key, &self.iv)?);
 
         // And parse the actual data
         let parsed: T = serde_json::from_slice(&decrypted) //.
@@ -97,17 +97,30 @@ where
         let key = self.crypto_key.as_ref().unwrap();
 
         let d = self.data.as_ref().unwrap();
+        // Growing the buffer would leave copies of the data in freed memory, so we measure it first
+        let mut counter = ByteCounter(0);
+        serde_json::to_writer(&mut counter, d) //.
+            .or_else(|e| bail!(Errors::JsonSerialize(e)))?;
+
         // Serialize the data
-        let serialized = serde_json::to_string(d) //.
+        let mut serialized = Zeroizing::new(Vec::with_capacity(counter.0));
+        serde_json::to_writer(&mut *serialized, d) //.
             .or_else(|e| bail!(Errors::JsonSerialize(e)))?;
 
         // And encrypts the data with the crypto key
-        let encrypted = aes_encrypt(serialized.as_bytes(), key, &self.iv)?;
+        let encrypted = aes_encrypt(&serialized, key, &self.iv)?;
 
         self.encrypted_data = encrypted.into_boxed_slice();
         Ok(())
     }
 
+    /// Wipes the decrypted data and the crypto key, the password is needed to decrypt the data again.
+    /// Changes since the last call to `to_raw` are lost.
+    pub fn lock(&mut self) {
+        self.data.zeroize();
+        self.crypto_key.zeroize();
+    }
+
     /// # Returns
     /// 
     /// the data that should be written to disk, including hashes, iv and encrypted_data for example
@@ -141,3 +154,17 @@ where
             .or_else(|e| bail!(Errors::PasswordError(e)))
     }
 }
+
+/// Just counts the bytes written to it
+struct ByteCounter(usize);
+
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
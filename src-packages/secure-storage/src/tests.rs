@@ -40,4 +40,18 @@ fn decrypt() -> Result<()> {
 
     assert_eq!(storage.data.as_ref().unwrap(), new_str.data.as_ref().unwrap());
     Ok(())
-}
\ No newline at end of file
+}
+
+#[test]
+fn lock_wipes_data_until_decrypted_again() -> Result<()> {
+    let mut storage = generate()?;
+    storage.to_raw()?;
+
+    storage.lock();
+    assert!(storage.data.is_none());
+    assert!(storage.to_raw().is_err());
+
+    storage.try_decrypt(PASS)?;
+    assert_eq!(storage.data, Some(TestStruct::default()));
+    Ok(())
+}
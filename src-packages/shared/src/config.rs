@@ -13,7 +13,7 @@ use anyhow::{anyhow, Result};
 use lazy_static::lazy_static;
 use port_check::free_local_port;
 
-use crate::{get_data_dir, get_service_dir};
+use crate::{get_client_auth_dir, get_data_dir, get_service_dir};
 
 /// Contains the configuration for the Tor process
 /// Such as the port, data dir, service dir, etc.
@@ -26,6 +26,8 @@ pub struct TorConfig {
     service_dir: OsString,
     /// The port of the service
     service_port: u16,
+    /// The directory tor reads the keys from that authorize us at other services
+    client_auth_dir: OsString,
 
     /// The directory to store tor data in
     data_dir: OsString,
@@ -48,6 +50,12 @@ impl TorConfig {
         // We are restricting the permissions of the service dir
         fs::set_permissions(&service_dir, Permissions::from_mode(0o700)).unwrap();
 
+        // Same for the keys authorizing us at other services
+        let client_auth_dir = get_client_auth_dir()?;
+
+        #[cfg(target_family = "unix")]
+        fs::set_permissions(&client_auth_dir, Permissions::from_mode(0o700)).unwrap();
+
         // And the data directory
         let data_dir = get_data_dir()?;
         // And finding a free port for the service
@@ -60,6 +68,7 @@ impl TorConfig {
             data_dir,
             service_dir,
             service_port,
+            client_auth_dir,
         })
     }
 
@@ -80,6 +89,11 @@ impl TorConfig {
         &self.service_dir
     }
 
+    /// The directory tor reads the client authorization keys from
+    pub fn client_auth_dir(&self) -> &OsString {
+        &self.client_auth_dir
+    }
+
     /// The service directory that tor should use
     pub fn data_dir(&self) -> &OsString {
         &self.data_dir
@@ -1,15 +1,20 @@
-use std::{env::current_exe, path::{PathBuf, Path}, fs::{create_dir_all, self}, ffi::OsString};
+use std::{env::{self, current_exe}, path::{PathBuf, Path}, fs::{create_dir_all, self}, ffi::OsString};
 
 use anyhow::Result;
 
+/// Set to use another root directory than `enkrypton_root` next to the executable (e.g. for a daemon or tests)
+pub const ROOT_DIR_ENV: &str = "ENKRYPTON_ROOT";
+
 /// The main directory of enkrypton, creating it if it does not exist
 ///
 /// # Returns
 ///
 /// Returns the path of the root directory
 pub fn get_root_dir() -> PathBuf {
-    let mut buf = current_exe().unwrap().parent().unwrap().to_path_buf();
-    buf.push("enkrypton_root/");
+    let buf = match env::var_os(ROOT_DIR_ENV) {
+        Some(dir) => PathBuf::from(dir),
+        None => current_exe().unwrap().parent().unwrap().join("enkrypton_root/"),
+    };
 
     if !buf.is_dir() {
         create_dir_all(&buf).unwrap();
@@ -56,6 +61,24 @@ pub fn get_service_dir() -> Result<OsString> {
     Ok(dir.into_os_string())
 }
 
+/// The directory tor reads the client authorization keys from, so we can dial services that restrict
+/// who may discover them. This is `enkrypton_root/onion_auth`.
+/// Creates if it does not exist
+///
+/// # Returns
+///
+/// A path to the client authorization directory
+pub fn get_client_auth_dir() -> Result<OsString> {
+    let mut dir = get_root_dir();
+    dir.push("onion_auth");
+
+    if !dir.is_dir() {
+        fs::create_dir(&dir)?;
+    }
+
+    Ok(dir.into_os_string())
+}
+
 /// The data directory tor should use. This is `enkrypton_root/data` for now.
 /// Creates if it does not exist
 ///
@@ -87,7 +110,32 @@ pub fn get_torrc() -> PathBuf {
     dir
 }
 
-/// The path to the tor executable. This is `enkrypton_root/tor.exe` for windows and `enkrypton_root/tor` for linux.
+/// The file the protections of our onion service against denial of service attacks are stored in
+///
+/// # Returns
+///
+/// A path to the `dos_defenses.json` file
+pub fn get_dos_defenses_path() -> PathBuf {
+    let mut dir = get_root_dir();
+    dir.push("dos_defenses.json");
+
+    dir
+}
+
+/// The file the cover traffic setting is stored in
+///
+/// # Returns
+///
+/// A path to the `cover_traffic.json` file
+pub fn get_cover_traffic_path() -> PathBuf {
+    let mut dir = get_root_dir();
+    dir.push("cover_traffic.json");
+
+    dir
+}
+
+/// The path to the tor executable. This is `enkrypton_root/tor.exe` for windows, `enkrypton_root/libTor.so` for android
+/// and `enkrypton_root/tor` for every other platform.
 ///
 /// # Returns
 ///
@@ -97,6 +145,8 @@ pub fn get_tor_path() -> PathBuf {
 
     return tor_write_path.join(if cfg!(target_os="windows") {
         "tor.exe"
+    } else if cfg!(target_os="android") {
+        "libTor.so"
     } else {
         "tor"
     });
@@ -0,0 +1,138 @@
+use anyhow::{anyhow, Result};
+use data_encoding::BASE32_NOPAD;
+use ed25519_dalek::VerifyingKey;
+use sha3::{Digest, Sha3_256};
+
+/// The length of a v3 onion address without `.onion`
+pub const ONION_HOSTNAME_LENGTH: usize = 56;
+/// The version byte of v3 onion addresses
+const ONION_VERSION: u8 = 3;
+/// The length of the decoded address: public key, checksum and version
+const ONION_RAW_LENGTH: usize = 35;
+
+/// Calculates the checksum that is part of every v3 onion address
+///
+/// # Arguments
+///
+/// * `key` - The public key of the onion service
+///
+/// # Returns
+///
+/// The first two bytes of the checksum
+fn onion_checksum(key: &[u8]) -> [u8; 2] {
+    let mut hasher = Sha3_256::new();
+    hasher.update(b".onion checksum");
+    hasher.update(key);
+    hasher.update([ONION_VERSION]);
+
+    let hash = hasher.finalize();
+    [hash[0], hash[1]]
+}
+
+/// Strips everything from the given hostname that is not part of the onion address itself
+///
+/// # Arguments
+///
+/// * `hostname` - The onion hostname
+///
+/// # Returns
+///
+/// The bare onion address
+pub fn onion_address(hostname: &str) -> &str {
+    let hostname = hostname.trim_end_matches(".onion");
+
+    // Just our own service messaging itself, the suffix is not part of the address
+    #[cfg(feature = "dev")]
+    let hostname = hostname
+        .trim_end_matches("-dev-client")
+        .trim_end_matches("-dev-server");
+
+    hostname
+}
+
+/// # Arguments
+///
+/// * `key` - The public key of an onion service
+///
+/// # Returns
+///
+/// The v3 onion address of the service (without `.onion`)
+pub fn onion_hostname(key: &VerifyingKey) -> String {
+    let mut raw = key.as_bytes().to_vec();
+    raw.extend(onion_checksum(key.as_bytes()));
+    raw.push(ONION_VERSION);
+
+    BASE32_NOPAD.encode(&raw).to_lowercase()
+}
+
+/// Validates the given v3 onion address and reads the public key encoded in it.
+/// Catches typos, as every address contains a checksum of the key
+///
+/// # Arguments
+///
+/// * `hostname` - The onion address in lowercase, with or without `.onion`
+///
+/// # Returns
+///
+/// The ed25519 public key of the onion service, fails if the address is not a valid v3 address
+pub fn onion_public_key(hostname: &str) -> Result<VerifyingKey> {
+    let address = onion_address(hostname);
+    if address.len() != ONION_HOSTNAME_LENGTH {
+        return Err(anyhow!("Onion addresses are {} characters long, got {}", ONION_HOSTNAME_LENGTH, address.len()));
+    }
+
+    if let Some(c) = address.chars().find(|c| !matches!(c, 'a'..='z' | '2'..='7')) {
+        return Err(anyhow!("Invalid character {:?} in onion address {}", c, address));
+    }
+
+    let raw = BASE32_NOPAD
+        .decode(address.to_uppercase().as_bytes())
+        .map_err(|e| anyhow!("Invalid onion address {}: {}", address, e))?;
+
+    if raw.len() != ONION_RAW_LENGTH || raw[ONION_RAW_LENGTH - 1] != ONION_VERSION {
+        return Err(anyhow!("{} is not a v3 onion address", address));
+    }
+
+    let (key, checksum) = raw[..ONION_RAW_LENGTH - 1].split_at(32);
+    if checksum != onion_checksum(key) {
+        return Err(anyhow!("Invalid checksum of onion address {}, is there a typo?", address));
+    }
+
+    let key: [u8; 32] = key.try_into()?;
+    let key = VerifyingKey::from_bytes(&key)
+        .map_err(|_| anyhow!("{} does not contain a valid public key", address))?;
+
+    if key.is_weak() {
+        return Err(anyhow!("{} contains a weak public key", address));
+    }
+
+    Ok(key)
+}
+
+/// # Arguments
+///
+/// * `hostname` - The onion hostname to check
+///
+/// # Returns
+///
+/// Whether the given hostname is a valid v3 onion address without `.onion`
+pub fn is_onion_hostname(hostname: &str) -> bool {
+    !hostname.ends_with(".onion") && onion_public_key(hostname).is_ok()
+}
+
+/// Reads an onion hostname entered by the user, e.g. `Abc...xyz.onion `
+///
+/// # Arguments
+///
+/// * `input` - The entered hostname
+///
+/// # Returns
+///
+/// The hostname in the form we store it (lowercase, without `.onion`), fails if it is not a valid v3 address
+pub fn parse_onion_hostname(input: &str) -> Result<String> {
+    let hostname = input.trim().to_lowercase();
+    let hostname = hostname.trim_end_matches('/').trim_end_matches(".onion");
+
+    onion_public_key(hostname)?;
+    Ok(hostname.to_string())
+}
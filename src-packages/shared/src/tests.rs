@@ -0,0 +1,46 @@
+use ed25519_dalek::SigningKey;
+
+use crate::onion::{is_onion_hostname, onion_hostname, onion_public_key, parse_onion_hostname};
+
+/// Some onion address with a valid checksum
+fn hostname() -> String {
+    onion_hostname(&SigningKey::from_bytes(&[7; 32]).verifying_key())
+}
+
+#[test]
+fn onion_addresses_contain_their_key() {
+    let key = SigningKey::from_bytes(&[7; 32]).verifying_key();
+    let hostname = hostname();
+
+    assert_eq!(hostname.len(), 56);
+    assert!(is_onion_hostname(&hostname));
+    assert_eq!(onion_public_key(&hostname).unwrap(), key);
+    assert_eq!(onion_public_key(&format!("{}.onion", hostname)).unwrap(), key);
+}
+
+#[test]
+fn invalid_onion_addresses_are_refused() {
+    let hostname = hostname();
+
+    // A typo in the middle breaks the checksum
+    let mut typo = hostname.clone().into_bytes();
+    typo[20] = if typo[20] == b'a' { b'b' } else { b'a' };
+    let typo = String::from_utf8(typo).unwrap();
+    assert!(!is_onion_hostname(&typo));
+    assert!(onion_public_key(&typo).unwrap_err().to_string().contains("checksum"));
+
+    assert!(!is_onion_hostname(&hostname[1..]));
+    assert!(!is_onion_hostname(&format!("{}.onion", hostname)));
+    assert!(!is_onion_hostname(&hostname.to_uppercase()));
+    assert!(!is_onion_hostname(&hostname.replacen(&hostname[..1], "1", 1)));
+    // Only the version byte is different
+    assert!(!is_onion_hostname(&format!("{}a", &hostname[..55])));
+}
+
+#[test]
+fn entered_onion_addresses_are_normalized() {
+    let hostname = hostname();
+
+    assert_eq!(parse_onion_hostname(&format!(" {}.onion/\n", hostname.to_uppercase())).unwrap(), hostname);
+    assert!(parse_onion_hostname("abc.onion").is_err());
+}
@@ -1,7 +1,7 @@
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
-use payloads::{data::ChatMessage, payloads::{WsMessageStatus, WsMessageStatusPayload}, event::AppHandleExt};
-use shared::APP_HANDLE;
+use log::warn;
+use payloads::{data::ChatMessage, payloads::{WsMessageStatus, WsMessageStatusPayload}, event::emit_payload};
 
 use crate::StorageManager;
 
@@ -38,7 +38,7 @@ impl ChatStorageHelper for StorageManager {
 
             c.messages.push(ChatMessage {
                 self_sent: sent_self,
-                msg: msg.to_string(),
+                msg: msg.into(),
                 date,
                 status: status.clone()
             });
@@ -48,11 +48,15 @@ impl ChatStorageHelper for StorageManager {
         .await?;
 
         // Notifies the frontend about the newly created message
-        APP_HANDLE.read().await.as_ref().map(|e| e.emit_payload(WsMessageStatusPayload {
+        let res = emit_payload(WsMessageStatusPayload {
             hostname: receiver.to_string(),
             date,
             status
-        }));
+        });
+
+        if let Err(e) = res {
+            warn!("Could not emit message status: {:?}", e);
+        }
         Ok(date)
     }
 }
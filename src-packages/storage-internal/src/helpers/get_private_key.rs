@@ -1,12 +1,12 @@
 use anyhow::Result;
 use encryption::PrivateKey;
-use log::info;
 
 use async_trait::async_trait;
-use payloads::data::StorageChat;
 
 use crate::{StorageManager, STORAGE};
 
+use super::ModifyChat;
+
 /// Trait to either get a private key or if it does not exist, generate a new key pair and store it
 #[async_trait]
 /// A trait for getting or creating a private key.
@@ -27,30 +27,18 @@ pub trait GetPrivateKey {
 impl GetPrivateKey for StorageManager {
     async fn get_or_create_private_key(receiver: &str) -> Result<PrivateKey> {
         // Gets the private key from the storage
-        let mut priv_key = STORAGE.read().await
+        let priv_key = STORAGE.read().await
             .get_data(|e| {
                 let k = e.chats.get(receiver).and_then(|e| Some(e.priv_key.clone()));
 
                 Ok(k)
             })
             .await?;
-        if priv_key.is_none() {
-            // If it does not exist, generate a new chat and store it
-            priv_key = STORAGE.read().await
-                .modify_storage_data(|e| {
-                    if !e.chats.contains_key(receiver) {
-                        info!("No private key for receiver '{}' yet. Adding new receiver...", receiver);
-                        e.chats
-                            .insert(receiver.to_string(), StorageChat::new(receiver));
-                    }
-
-                    let priv_key = e.chats.get(receiver).and_then(|e| Some(e.priv_key.clone()));
-
-                    Ok(priv_key)
-                })
-                .await?;
+        if let Some(priv_key) = priv_key {
+            return Ok(priv_key);
         }
 
-        Ok(priv_key.expect("Should always be true"))
+        // If it does not exist, generate a new chat and store it
+        Self::modify_chat(receiver, |chat| Ok(chat.priv_key.clone())).await
     }
 }
\ No newline at end of file
@@ -1,5 +1,7 @@
 mod get_private_key;
 mod chats;
+mod modify_chat;
 
 pub use chats::*;
-pub use get_private_key::*;
\ No newline at end of file
+pub use get_private_key::*;
+pub use modify_chat::*;
\ No newline at end of file
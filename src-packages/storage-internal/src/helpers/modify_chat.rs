@@ -0,0 +1,61 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use encryption::PrivateKey;
+use log::info;
+use payloads::data::StorageChat;
+
+use crate::{StorageManager, STORAGE};
+
+/// Extension trait to modify a chat, creating it first if it does not exist yet
+#[async_trait]
+pub trait ModifyChat {
+    /// Modifies the chat of the given receiver. If there is no chat for the receiver yet,
+    /// a new one is created. Its key pair is generated before the storage is locked.
+    ///
+    /// # Arguments
+    ///
+    /// * `receiver` - The onion hostname of the receiver of the chat
+    /// * `f` - The function that modifies the chat
+    ///
+    /// # Returns
+    ///
+    /// The value returned by `f`
+    async fn modify_chat<F, T>(receiver: &str, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut StorageChat) -> Result<T> + Send,
+        T: Send;
+}
+
+#[async_trait]
+impl ModifyChat for StorageManager {
+    async fn modify_chat<F, T>(receiver: &str, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut StorageChat) -> Result<T> + Send,
+        T: Send,
+    {
+        let exists = STORAGE.read().await
+            .get_data(|e| Ok(e.chats.contains_key(receiver)))
+            .await?;
+
+        // Generating a key takes a while, so this is done without holding any lock
+        let mut priv_key = match exists {
+            true => None,
+            false => Some(PrivateKey::generate_pair_async().await?),
+        };
+
+        STORAGE.read().await
+            .modify_storage_data(|e| {
+                if !e.chats.contains_key(receiver) {
+                    let priv_key = priv_key
+                        .take()
+                        .ok_or(anyhow!("Chat of receiver '{}' has been removed while modifying it", receiver))?;
+
+                    info!("No chat for receiver '{}' yet. Adding new receiver...", receiver);
+                    e.chats.insert(receiver.to_string(), StorageChat::new(receiver, priv_key));
+                }
+
+                f(e.chats.get_mut(receiver).expect("Should always exist"))
+            })
+            .await
+    }
+}
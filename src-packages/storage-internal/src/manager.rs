@@ -4,17 +4,17 @@ use std::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
-    time::Duration, thread,
+    time::Duration,
 };
 
 use anyhow::{anyhow, Result};
+use encryption::{clear_key_pool, fill_key_pool};
 use log::{debug, error, warn};
 use payloads::{
-    data::StorageData, event::AppHandleExt, payloads::storage_changed::StorageChangedPayload,
+    data::StorageData, event::emit_payload, payloads::storage_changed::StorageChangedPayload,
 };
 use secure_storage::{Generate, Parsable, SecureStorage};
 use shared::get_storage_path;
-use shared::APP_HANDLE;
 
 #[cfg(target_family = "unix")]
 use smol::fs::unix::PermissionsExt;
@@ -26,6 +26,7 @@ use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
     sync::RwLock,
     task::{spawn, JoinHandle},
+    time::sleep,
 };
 
 /// The general type of storage that is used in this application
@@ -85,7 +86,7 @@ impl StorageManager {
         e
     }
 
-    /// Tells the save thread to exit and waits for it to
+    /// Tells the save thread to exit and waits for it to. Wipes the decrypted data afterwards, so save before calling this
     pub async fn exit(&mut self) -> Result<()> {
         self.should_exit.store(true, Ordering::Relaxed);
         let val = self.save_thread.take();
@@ -94,10 +95,35 @@ impl StorageManager {
             v.await?;
         }
 
+        self.wipe().await;
         Ok(())
     }
 
-    //noinspection RsSleepInsideAsyncFunction
+    /// Saves the storage and wipes the decrypted data and keys from memory.
+    /// The storage has to be unlocked with the password again afterwards.
+    pub async fn lock(&mut self) -> Result<()> {
+        if !self.is_unlocked {
+            return Ok(());
+        }
+
+        self.save().await?;
+        self.dirty.store(false, Ordering::Relaxed);
+
+        self.wipe().await;
+        self.is_unlocked = false;
+
+        Ok(())
+    }
+
+    /// Wipes the decrypted data, the crypto key and the pre-generated keys
+    async fn wipe(&self) {
+        if let Some(storage) = self.storage.write().await.as_mut() {
+            storage.lock();
+        }
+
+        clear_key_pool();
+    }
+
     /// Checks every 20 seconds if the storage is marked as dirty and if so, encrypts the data again and saves it to disk.
     fn run_save_thread(&mut self) {
         let temp = self.storage.clone();
@@ -110,14 +136,15 @@ impl StorageManager {
             while !should_exit.load(Ordering::Relaxed) {
                 //TODO Cleanup to not duplicate this func
 
-                thread::sleep(Duration::from_secs(20));
+                sleep(Duration::from_secs(20)).await;
                 // Return if none of the files have been modified
                 if !dirty.load(Ordering::Relaxed) {
                     continue;
                 }
 
                 let mut storage = temp.write().await;
-                if storage.is_none() {
+                // There is nothing to save while the storage is locked
+                if storage.as_ref().is_none_or(|s| s.data.is_none()) {
                     continue;
                 }
 
@@ -143,6 +170,10 @@ impl StorageManager {
 
                 debug!("Writing total of {} bytes...", raw.len());
                 let res = f.write_all(&raw).await;
+                let res = match res {
+                    Ok(_) => f.flush().await,
+                    e => e,
+                };
                 if res.is_err() {
                     error!("Could not write to storage file: {}", res.unwrap_err());
                     continue;
@@ -193,6 +224,7 @@ impl StorageManager {
         if newly_generated {
             self.save().await?;
             self.is_unlocked = true;
+            fill_key_pool();
         }
 
         Ok(())
@@ -207,6 +239,8 @@ impl StorageManager {
         self.modify_storage(move |e| e.try_decrypt(pass)).await?;
 
         self.is_unlocked = true;
+        // New chats need a key pair, so we are generating some ahead of time
+        fill_key_pool();
         Ok(())
     }
 
@@ -219,6 +253,8 @@ impl StorageManager {
         debug!("Writing total of {} bytes...", raw.len());
         let mut f = File::create(&self.path).await?;
         f.write_all(&raw).await?;
+        // tokio writes in the background, so the file might still be empty without flushing
+        f.flush().await?;
 
         #[cfg(target_family = "unix")]
         if self.path.is_file() {
@@ -243,12 +279,7 @@ impl StorageManager {
     /// Marks the storage as dirty (so it will be saved later)
     pub async fn mark_dirty(&self) {
         self.dirty.store(true, Ordering::Relaxed);
-        let res = APP_HANDLE
-            .read()
-            .await
-            .as_ref()
-            .ok_or(anyhow!("app handle not there"))
-            .map(|e| e.emit_payload(StorageChangedPayload {}));
+        let res = emit_payload(StorageChangedPayload {});
 
         if res.is_err() {
             warn!("Could not emit dirty event: {:?}", res.unwrap_err());
@@ -1,19 +1,45 @@
-use std::{env, path::Path};
+use std::{env, fs, path::Path};
 
 fn main() {
     // Re-run build script if the version file changes
     println!("cargo:rerun-if-changed=assets/tor_expert_bundle_version.txt");
+    println!("cargo:rerun-if-changed=assets/tor_expert_bundle_sha256sums.txt");
+    // Re-run if the bundle should be read from somewhere else
+    println!("cargo:rerun-if-env-changed=TOR_BUNDLE_PATH");
+    println!("cargo:rerun-if-env-changed=TOR_SIGNING_KEY_PATH");
+    println!("cargo:rerun-if-env-changed=DOCS_RS");
 
-    // Only download the bundles if not in docs.rs build environment
-    if env::var("DOCS_RS").is_err() {
-        // Get output directory for assets
-        let out_dir = env::var("OUT_DIR").unwrap();
-        let out_path = Path::new(&out_dir);
+    // Get output directory for assets
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let out_path = Path::new(&out_dir);
 
+    // Docs can be built for targets without a bundle, which is never run there anyway
+    let docs = env::var("DOCS_RS").is_ok();
+    let bundle_dir = match tor_updater::detect_current_platform() {
+        Ok(platform) => platform.bundle_dir(out_path),
+        Err(_) if docs => out_path.join("unsupported"),
+        Err(e) => panic!("Can not build for this platform: {}", e),
+    };
+
+    // Tells the crate where the tor.hash and tor.zip of the target platform are
+    println!("cargo:rustc-env=TOR_BUNDLE_DIR={}", bundle_dir.display());
+
+    if docs {
+        // The crate includes these files, so they have to exist even without a download
+        fs::create_dir_all(&bundle_dir).unwrap();
+        for file in ["tor.hash", "tor.zip", "snowflake-client.hash"] {
+            let path = bundle_dir.join(file);
+            if !path.exists() {
+                fs::write(path, "").unwrap();
+            }
+        }
+    } else {
         // Download the Tor expert bundles
-        match tor_updater::download_version(out_path, include_str!("./assets/tor_expert_bundle_version.txt").trim()) {
+        let version = include_str!("./assets/tor_expert_bundle_version.txt").trim();
+        let manifest = include_str!("./assets/tor_expert_bundle_sha256sums.txt");
+        match tor_updater::download_version(out_path, version, manifest) {
             Ok(_) => println!("cargo:warning=Successfully downloaded Tor expert bundles to {}", out_path.display()),
             Err(e) => panic!("Failed to download Tor expert bundles: {}", e),
         }
     }
-}
\ No newline at end of file
+}
@@ -15,6 +15,8 @@ use tokio::{
 
 use async_trait::async_trait;
 
+use crate::dos::{dos_defenses_torrc, get_dos_defenses, get_tor_daemon};
+
 #[async_trait]
 pub trait ConfigExt {
     async fn to_text(&self) -> Result<String>;
@@ -46,17 +48,27 @@ impl ConfigExt for TorConfig {
         let geo_ip = data.clone().join("geoip");
         let geo_ip6 = data.clone().join("geoip6");
 
+        // The protections have to follow the HiddenServiceDir they belong to
+        let defenses = get_dos_defenses().await;
+        let dos: String = dos_defenses_torrc(&defenses, get_tor_daemon().await.as_ref())
+            .iter()
+            .map(|l| format!("\n{}", l))
+            .collect();
+
         #[allow(unused_mut)]
         let mut config = format!(
-            "SocksPort {}
+            "SocksPort {} IsolateSOCKSAuth
 HiddenServiceDir \"{}\"
-HiddenServicePort 80 {}
+HiddenServicePort 80 {}{}
+ClientOnionAuthDir \"{}\"
 DataDirectory \"{}\"
 GeoIPFile \"{}\"
 GeoIPv6File \"{}\"",
             self.get_socks_host(),
             self.service_dir().to_string_lossy().replace("\\", "/"),
             self.get_hidden_service_host(),
+            dos,
+            self.client_auth_dir().to_string_lossy().replace("\\", "/"),
             self.data_dir().to_string_lossy().replace("\\", "/"),
             geo_ip.to_string_lossy().replace("\\", "/"),
             geo_ip6.to_string_lossy().replace("\\", "/"),
@@ -1,9 +1,10 @@
-use std::{path::PathBuf, sync::Arc, thread::JoinHandle};
+use std::{collections::VecDeque, path::PathBuf, sync::{atomic::AtomicBool, Arc}, thread::JoinHandle, time::Duration};
 
 use async_channel::{Receiver, Sender};
 use lazy_static::lazy_static;
+use payloads::payloads::TorLogEntry;
 use shared::get_tor_path;
-use tokio::sync::RwLock;
+use tokio::{sync::{Mutex, Notify, RwLock}, task::JoinHandle as TaskHandle};
 
 use super::misc::messages::{Client2TorMsg, Tor2ClientMsg};
 #[cfg(feature = "snowflake")]
@@ -33,7 +34,36 @@ lazy_static! {
 
     /// Keep 20 log messages in memory
     pub(super) static ref MAX_LOG_SIZE: usize = 20;
-
+    /// Every line tor has logged, capped at `MAX_TOR_LOG_ENTRIES` (oldest ones are dropped first)
+    pub(super) static ref TOR_LOGS: Arc<RwLock<VecDeque<TorLogEntry>>> = Arc::default();
+    /// How many lines of tor are kept in memory to query them at runtime / export them
+    pub static ref MAX_TOR_LOG_ENTRIES: usize = 1000;
+    /// The version of the bundled tor expert bundle
+    pub static ref TOR_VERSION: String = include_str!("../assets/tor_expert_bundle_version.txt").trim().to_string();
+
+    /// The task which watches tor and restarts it if needed
+    pub(super) static ref SUPERVISOR_HANDLE: Arc<RwLock<Option<TaskHandle<()>>>> = Arc::default();
+    /// Whether the supervisor should stop restarting tor (set when the app exits)
+    pub(super) static ref SUPERVISOR_EXIT: Arc<AtomicBool> = Arc::default();
+    /// Held while the supervisor restarts tor, so exiting the app waits for it
+    pub(super) static ref SUPERVISOR_RESTART_LOCK: Arc<Mutex<()>> = Arc::default();
+    /// Notified to make the supervisor restart tor, so it reads its configuration again
+    pub(super) static ref SUPERVISOR_RESTART_REQUEST: Arc<Notify> = Arc::default();
+
+    /// How often the supervisor checks if the socks proxy is still answering
+    pub static ref HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+    /// How long the socks proxy has to answer a health check
+    pub static ref HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+    /// Failed health checks in a row after which tor is considered stalled and restarted
+    pub static ref MAX_FAILED_HEALTH_CHECKS: u32 = 3;
+    /// The first delay before tor is restarted, doubled on every restart
+    pub static ref MIN_RESTART_BACKOFF: Duration = Duration::from_secs(1);
+    /// The longest delay between two restarts
+    pub static ref MAX_RESTART_BACKOFF: Duration = Duration::from_secs(60);
+    /// After running this long without problems, the restart backoff is reset
+    pub static ref STABLE_RUN_DURATION: Duration = Duration::from_secs(120);
+    /// How long a restarted tor process has to bootstrap before it is restarted again
+    pub static ref TOR_BOOTSTRAP_TIMEOUT: Duration = Duration::from_secs(180);
 }
 
 #[cfg(feature="snowflake")]
@@ -60,21 +90,8 @@ pub async fn setup_tor_channels() {
 ///
 /// The hash of the snowflake binary encoded in hex
 fn get_tor_binary_hash() -> String {
-    #[cfg(all(target_os = "windows", target_arch = "x86_64"))]
-    let hash = include_str!(concat!(env!("OUT_DIR"), "/windows/x86_64/tor.hash"));
-
-    #[cfg(all(
-        target_os = "windows",
-        target_arch = "x86",
-        not(target_arch = "x86_64")
-    ))]
-    let hash = include_str!(concat!(env!("OUT_DIR"), "/windows/i686/tor.hash"));
-
-    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
-    let hash = include_str!(concat!(env!("OUT_DIR"), "/linux/x86_64/tor.hash"));
-
-    #[cfg(all(target_os = "linux", target_arch = "x86", not(target_arch = "x86_64")))]
-    let hash = include_str!(concat!(env!("OUT_DIR"), "/windows/i686/tor.hash"));
+    // The build script points TOR_BUNDLE_DIR to the bundle of the target platform
+    let hash = include_str!(concat!(env!("TOR_BUNDLE_DIR"), "/tor.hash"));
 
     // Checks if the hash is valid
     hex::decode(hash).unwrap();
@@ -90,21 +107,7 @@ fn get_tor_binary_hash() -> String {
 /// The hash of the tor binary encoded in hex
 #[cfg(feature="snowflake")]
 fn get_snowflake_binary_hash() -> String {
-    #[cfg(all(target_os = "windows", target_arch = "x86_64"))]
-    let hash = include_str!(concat!(env!("OUT_DIR"), "/windows/x86_64/snowflake-client.hash"));
-
-    #[cfg(all(
-        target_os = "windows",
-        target_arch = "x86",
-        not(target_arch = "x86_64")
-    ))]
-    let hash = include_str!(concat!(env!("OUT_DIR"), "/windows/i686/snowflake-client.hash"));
-
-    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
-    let hash = include_str!(concat!(env!("OUT_DIR"), "/linux/x86_64/snowflake-client.hash"));
-
-    #[cfg(all(target_os = "linux", target_arch = "x86", not(target_arch = "x86_64")))]
-    let hash = include_str!(concat!(env!("OUT_DIR"), "/windows/i686/snowflake-client.hash"));
+    let hash = include_str!(concat!(env!("TOR_BUNDLE_DIR"), "/snowflake-client.hash"));
 
     // Checks if the hash is valid
     hex::decode(hash).unwrap();
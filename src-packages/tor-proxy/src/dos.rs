@@ -0,0 +1,280 @@
+use std::{fmt, fs};
+
+use anyhow::{anyhow, Result};
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
+use payloads::payloads::TorDosDefenses;
+use shared::get_dos_defenses_path;
+use tokio::sync::RwLock;
+
+use crate::{
+    consts::TOR_THREAD,
+    mainloop::tor_command,
+    manager::{reload_tor, write_torrc},
+};
+
+/// The first tor version that lets introduction points rate limit introductions
+pub const INTRO_DOS_MIN_VERSION: TorDaemonVersion = TorDaemonVersion(0, 4, 2, 1);
+/// The first tor version with proof-of-work defenses for onion services
+pub const POW_MIN_VERSION: TorDaemonVersion = TorDaemonVersion(0, 4, 8, 1);
+/// The highest introduction rate and burst tor accepts
+pub const MAX_INTRO_DOS_RATE: u32 = i32::MAX as u32;
+/// The highest stream limit per rendezvous circuit tor accepts
+pub const MAX_STREAMS_LIMIT: u32 = 65535;
+
+lazy_static! {
+    /// The protections of our onion service, read from the settings file once
+    static ref DOS_DEFENSES: RwLock<TorDosDefenses> = RwLock::new(read_dos_defenses());
+    /// What the bundled tor binary supports, detected before tor is started
+    static ref TOR_DAEMON: RwLock<Option<TorDaemonInfo>> = RwLock::default();
+}
+
+/// The version of the tor daemon itself (e.g. `0.4.8.17`), not the one of the expert bundle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TorDaemonVersion(pub u32, pub u32, pub u32, pub u32);
+
+impl fmt::Display for TorDaemonVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}.{}", self.0, self.1, self.2, self.3)
+    }
+}
+
+/// What the bundled tor binary is able to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TorDaemonInfo {
+    /// The version of the binary
+    pub version: TorDaemonVersion,
+    /// Whether the binary has been built with the proof-of-work module (needs `--enable-gpl`)
+    pub has_pow_module: bool,
+}
+
+impl TorDaemonInfo {
+    /// Parses the output of `tor --version`
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - What tor printed, e.g. `Tor version 0.4.8.17.`
+    ///
+    /// # Returns
+    ///
+    /// The parsed info, fails if there is no version in the output
+    pub fn parse(output: &str) -> Result<Self> {
+        let raw = output
+            .lines()
+            .find_map(|l| l.trim().strip_prefix("Tor version "))
+            .and_then(|l| l.split_whitespace().next())
+            .ok_or(anyhow!("Could not find the tor version in {:?}", output))?;
+
+        // Like `0.4.9.1-alpha.` or `0.4.8.17.`
+        let raw = raw.trim_end_matches('.');
+        let raw = raw.split('-').next().unwrap_or(raw);
+
+        let parts = raw
+            .split('.')
+            .map(|p| p.parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow!("Invalid tor version {}: {}", raw, e))?;
+
+        let [major, minor, micro, patch] = parts[..] else {
+            return Err(anyhow!("Invalid tor version {}", raw));
+        };
+
+        let version = TorDaemonVersion(major, minor, micro, patch);
+        Ok(Self {
+            version,
+            has_pow_module: version >= POW_MIN_VERSION && output.contains("GNU General Public License"),
+        })
+    }
+
+    /// # Returns
+    ///
+    /// Whether introduction points can be told to rate limit introductions
+    pub fn supports_intro_dos(&self) -> bool {
+        self.version >= INTRO_DOS_MIN_VERSION
+    }
+
+    /// # Returns
+    ///
+    /// Whether our service can ask clients for a proof-of-work
+    pub fn supports_pow(&self) -> bool {
+        self.version >= POW_MIN_VERSION && self.has_pow_module
+    }
+}
+
+/// Runs the bundled tor binary to find out what it supports.
+/// The binary has to be extracted already
+///
+/// # Returns
+///
+/// The version and modules of the tor binary
+pub fn detect_tor_daemon() -> Result<TorDaemonInfo> {
+    let output = tor_command().arg("--version").output()?;
+    if !output.status.success() {
+        return Err(anyhow!("tor --version exited with {}", output.status));
+    }
+
+    TorDaemonInfo::parse(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Detects what the bundled tor binary supports and remembers it for generating the `torrc`
+pub(crate) async fn refresh_tor_daemon() {
+    let daemon = match detect_tor_daemon() {
+        Ok(d) => d,
+        Err(e) => {
+            warn!("Could not detect the version of tor: {:?}", e);
+            return;
+        }
+    };
+
+    debug!("Bundled tor is {} (proof-of-work: {})", daemon.version, daemon.has_pow_module);
+    TOR_DAEMON.write().await.replace(daemon);
+}
+
+/// # Returns
+///
+/// What the bundled tor binary supports, None if tor has not been started yet
+pub async fn get_tor_daemon() -> Option<TorDaemonInfo> {
+    *TOR_DAEMON.read().await
+}
+
+/// Checks that the given protections are valid and supported by the given tor binary
+///
+/// # Arguments
+///
+/// * `defenses` - The protections to check
+/// * `daemon` - What the tor binary supports, version dependent checks are skipped if None
+///
+/// # Returns
+///
+/// Fails with a description of the first invalid setting
+pub fn validate_dos_defenses(defenses: &TorDosDefenses, daemon: Option<&TorDaemonInfo>) -> Result<()> {
+    if let Some(rate) = &defenses.intro_rate {
+        if rate.rate_per_sec == 0 || rate.rate_per_sec > MAX_INTRO_DOS_RATE {
+            return Err(anyhow!("The introduction rate must be between 1 and {}", MAX_INTRO_DOS_RATE));
+        }
+
+        if rate.burst_per_sec < rate.rate_per_sec || rate.burst_per_sec > MAX_INTRO_DOS_RATE {
+            return Err(anyhow!("The introduction burst must be between the rate and {}", MAX_INTRO_DOS_RATE));
+        }
+
+        if let Some(d) = daemon
+            && !d.supports_intro_dos()
+        {
+            return Err(anyhow!("The bundled tor {} does not support introduction rate limits", d.version));
+        }
+    }
+
+    if defenses.max_streams > MAX_STREAMS_LIMIT {
+        return Err(anyhow!("At most {} streams per circuit can be allowed", MAX_STREAMS_LIMIT));
+    }
+
+    if defenses.max_streams_close_circuit && defenses.max_streams == 0 {
+        return Err(anyhow!("Circuits can only be closed if the streams are limited"));
+    }
+
+    if defenses.pow_enabled
+        && let Some(d) = daemon
+        && !d.supports_pow()
+    {
+        return Err(anyhow!("The bundled tor {} does not support proof-of-work defenses", d.version));
+    }
+
+    Ok(())
+}
+
+/// Converts the protections to the hidden service options of the `torrc`.
+/// Options the tor binary does not support are skipped, as tor would not start otherwise
+///
+/// # Arguments
+///
+/// * `defenses` - The protections of our onion service
+/// * `daemon` - What the tor binary supports, version dependent options are skipped if None
+///
+/// # Returns
+///
+/// The options, one per line
+pub fn dos_defenses_torrc(defenses: &TorDosDefenses, daemon: Option<&TorDaemonInfo>) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    if let Some(rate) = &defenses.intro_rate {
+        if daemon.is_some_and(|d| d.supports_intro_dos()) {
+            lines.push("HiddenServiceEnableIntroDoSDefense 1".to_string());
+            lines.push(format!("HiddenServiceEnableIntroDoSRatePerSec {}", rate.rate_per_sec));
+            lines.push(format!("HiddenServiceEnableIntroDoSBurstPerSec {}", rate.burst_per_sec));
+        } else {
+            warn!("Skipping introduction rate limits, not supported by tor {:?}", daemon.map(|d| d.version));
+        }
+    }
+
+    if defenses.pow_enabled {
+        if daemon.is_some_and(|d| d.supports_pow()) {
+            lines.push("HiddenServicePoWDefensesEnabled 1".to_string());
+        } else {
+            warn!("Skipping proof-of-work defenses, not supported by tor {:?}", daemon.map(|d| d.version));
+        }
+    }
+
+    if defenses.max_streams > 0 {
+        lines.push(format!("HiddenServiceMaxStreams {}", defenses.max_streams));
+        lines.push(format!(
+            "HiddenServiceMaxStreamsCloseCircuit {}",
+            defenses.max_streams_close_circuit as u8
+        ));
+    }
+
+    lines
+}
+
+/// Reads the protections from the settings file, the defaults of tor are used if there is none
+///
+/// # Returns
+///
+/// The stored protections
+fn read_dos_defenses() -> TorDosDefenses {
+    let path = get_dos_defenses_path();
+    if !path.is_file() {
+        return TorDosDefenses::default();
+    }
+
+    fs::read_to_string(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|raw| Ok(serde_json::from_str(&raw)?))
+        .unwrap_or_else(|e| {
+            warn!("Could not read {:?}, using the defaults: {:?}", path, e);
+            TorDosDefenses::default()
+        })
+}
+
+/// # Returns
+///
+/// The current protections of our onion service
+pub async fn get_dos_defenses() -> TorDosDefenses {
+    *DOS_DEFENSES.read().await
+}
+
+/// Validates and stores new protections for our onion service.
+/// If tor is running, the `torrc` is written again and tor is reloaded
+///
+/// # Arguments
+///
+/// * `defenses` - The new protections
+///
+/// # Returns
+///
+/// Fails if the protections are invalid or not supported by the bundled tor
+pub async fn set_dos_defenses(defenses: TorDosDefenses) -> Result<()> {
+    validate_dos_defenses(&defenses, get_tor_daemon().await.as_ref())?;
+
+    let raw = serde_json::to_string_pretty(&defenses)?;
+    tokio::fs::write(get_dos_defenses_path(), raw).await?;
+
+    *DOS_DEFENSES.write().await = defenses;
+    info!("Updated onion service protections: {:?}", defenses);
+
+    if TOR_THREAD.read().await.is_some() {
+        write_torrc().await?;
+        reload_tor().await?;
+    }
+
+    Ok(())
+}
@@ -2,7 +2,13 @@ pub mod misc;
 pub mod manager;
 mod config;
 pub mod consts;
+pub mod logs;
 mod parser;
 //noinspection SpellCheckingInspection
 mod mainloop;
-pub mod service;
\ No newline at end of file
+pub mod service;
+pub mod supervisor;
+pub mod dos;
+
+#[cfg(test)]
+mod tests;
\ No newline at end of file
@@ -0,0 +1,48 @@
+use payloads::payloads::{TorLogEntry, TorLogLevel};
+use shared::util::now_millis;
+
+use crate::consts::{MAX_TOR_LOG_ENTRIES, TOR_LOGS};
+
+/// Stores a line tor has written to stdout in the log buffer, dropping the oldest entry if the buffer is full
+///
+/// # Arguments
+///
+/// * `line` - The line tor has logged
+pub(crate) async fn push_tor_log(line: &str) {
+    let entry = TorLogEntry {
+        level: TorLogLevel::from_line(line),
+        timestamp: now_millis(),
+        message: line.to_string(),
+    };
+
+    let mut logs = TOR_LOGS.write().await;
+    while logs.len() >= *MAX_TOR_LOG_ENTRIES {
+        logs.pop_front();
+    }
+
+    logs.push_back(entry);
+}
+
+/// Gets the lines tor has logged since the app started (across restarts of tor)
+///
+/// # Arguments
+///
+/// * `min_level` - Lines below this level are skipped
+/// * `limit` - Only the newest `limit` lines are returned if set
+///
+/// # Returns
+///
+/// The matching lines, oldest first
+pub async fn get_tor_logs(min_level: TorLogLevel, limit: Option<usize>) -> Vec<TorLogEntry> {
+    let logs = TOR_LOGS.read().await;
+    let mut entries: Vec<TorLogEntry> = logs
+        .iter()
+        .rev()
+        .filter(|e| e.level >= min_level)
+        .take(limit.unwrap_or(usize::MAX))
+        .cloned()
+        .collect();
+
+    entries.reverse();
+    entries
+}
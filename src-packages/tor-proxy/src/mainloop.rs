@@ -15,10 +15,12 @@ use smol::fs::unix::PermissionsExt;
 #[cfg(target_family = "unix")]
 use std::{env, fs};
 use sysinfo::{Pid, System};
+#[cfg(target_family = "unix")]
+use sysinfo::Signal;
 
 use anyhow::{anyhow, Result};
 use log::{debug, error, info};
-use tauri::async_runtime::block_on;
+use tokio::runtime::Handle;
 
 use crate::{
     consts::TOR_BINARY_PATH,
@@ -35,6 +37,60 @@ use crate::consts::get_rel_snowflake;
 #[cfg(target_os = "windows")]
 const CREATE_NO_WINDOW: u32 = 0x08000000;
 
+/// Creates a command running the bundled tor binary, without any arguments yet
+///
+/// # Returns
+///
+/// The command, ready to be given arguments and spawned
+pub(crate) fn tor_command() -> Command {
+    let mut child = Command::new(TOR_BINARY_PATH.clone());
+    child.current_dir(TOR_BINARY_PATH.parent().unwrap());
+
+    #[cfg(target_family = "unix")]
+    // We need to tell Linux about the additional dynamic libraries provided by tor
+    {
+        let ld = env::var("LD_LIBRARY_PATH").unwrap_or_default();
+        let ld = format!(
+            "{}:{}",
+            ld,
+            TOR_BINARY_PATH.parent().unwrap().to_string_lossy()
+        );
+        child.env("LD_LIBRARY_PATH", ld);
+    }
+
+    #[cfg(target_os = "windows")]
+    // And we don't want to create a new window for the tor process
+    child.creation_flags(CREATE_NO_WINDOW);
+
+    child
+}
+
+/// Sends SIGHUP to the tor process, so it reads its configuration and key files again.
+/// Other platforms pick up the changes on the next start
+///
+/// # Arguments
+///
+/// * `id` - The process id of tor
+fn reload_process(id: u32) {
+    #[cfg(target_family = "unix")]
+    {
+        let s = System::new_all();
+        let sent = s
+            .process(Pid::from_u32(id))
+            .and_then(|p| p.kill_with(Signal::Hangup))
+            .unwrap_or(false);
+
+        if sent {
+            debug!("Told tor to reload its configuration");
+        } else {
+            log::warn!("Could not tell tor to reload its configuration");
+        }
+    }
+
+    #[cfg(not(target_family = "unix"))]
+    debug!("Can not reload tor ({}) on this platform, changes apply on the next start", id);
+}
+
 /// Spawns the tor process
 /// Controls and interprets the output of the tor process
 pub(super) async fn tor_main_loop() -> Result<()> {
@@ -60,28 +116,11 @@ pub(super) async fn tor_main_loop() -> Result<()> {
     }
 
     // Starts tor
-    let mut child = Command::new(TOR_BINARY_PATH.clone());
+    let mut child = tor_command();
     child.args(["-f", &get_torrc().to_string_lossy()]);
-    child.current_dir(TOR_BINARY_PATH.parent().unwrap());
     child.stdout(Stdio::piped());
     child.stderr(Stdio::piped());
 
-    #[cfg(target_family = "unix")]
-    // We need to tell Linux about the additional dynamic libraries provided by tor
-    {
-        let ld = env::var("LD_LIBRARY_PATH").unwrap_or_default();
-        let ld = format!(
-            "{}:{}",
-            ld,
-            TOR_BINARY_PATH.parent().unwrap().to_string_lossy()
-        );
-        child.env("LD_LIBRARY_PATH", ld);
-    }
-
-    #[cfg(target_os = "windows")]
-    // And we don't want to create a new window for the tor process
-    child.creation_flags(CREATE_NO_WINDOW);
-
     // Actually spawning tor
     let child = child.spawn()?;
     let id = child.id();
@@ -92,10 +131,11 @@ pub(super) async fn tor_main_loop() -> Result<()> {
     let temp = should_exit.clone();
 
     // Spawns the tor thread to handle tor stdout
+    let runtime = Handle::current();
     let handle = thread::Builder::new()
         .name("tor-stdout".to_string())
         .spawn(move || {
-            let res = block_on(handle_tor_stdout(temp, child));
+            let res = runtime.block_on(handle_tor_stdout(temp, child));
             if res.is_ok() {
                 info!("TOR: Thread finished");
             } else {
@@ -106,21 +146,20 @@ pub(super) async fn tor_main_loop() -> Result<()> {
         .unwrap();
 
     let rx = get_to_tor_rx().await;
-    // If we should exit, break and tell the tor process to exit as well
+    // Wait for the exit signal and tell the tor process to exit as well
     // Oh and don't listen for should_exit here because well the tor process is not changing it
-    while !rx.is_closed() {
-        let msg = rx.recv().await;
-        if msg.is_err() {
-            // channel is empty and closed, so the process exited
-            break;
-        }
-
-        let msg = msg.unwrap();
-        match msg {
-            Client2TorMsg::Exit() => {
+    loop {
+        match rx.recv().await {
+            Ok(Client2TorMsg::Exit()) => {
                 debug!("Got exit signal");
                 break;
             }
+            Ok(Client2TorMsg::Reload()) => reload_process(id),
+            // channel is empty and closed, so the process exited
+            Err(_) => {
+                debug!("Tor channel closed");
+                break;
+            }
         }
     }
 
@@ -5,12 +5,12 @@ use std::{
 };
 
 use anyhow::{anyhow, bail, Result};
-use payloads::payloads::StartTorPayload;
-use shared::{get_torrc, config::CONFIG};
-use log::{debug, error, info};
-use tauri::async_runtime::block_on;
+use payloads::payloads::{StartTorPayload, TorStatus};
+use shared::{get_root_dir, get_torrc, config::CONFIG};
+use log::{debug, error, info, warn};
+use tokio::runtime::Handle;
 
-use crate::{misc::{integrity_check::check_integrity, tools::{get_to_tor_tx, get_from_tor_rx}, messages::{Client2TorMsg, Tor2ClientMsg, TorStartError}}, consts::{TOR_START_LOCK, TOR_THREAD}, mainloop::tor_main_loop, service::get_service_hostname, config::ConfigExt};
+use crate::{misc::{integrity_check::check_integrity, tools::{get_to_tor_tx, get_from_tor_rx}, messages::{Client2TorMsg, Tor2ClientMsg, TorStartError}}, consts::{TOR_START_LOCK, TOR_THREAD}, mainloop::tor_main_loop, dos::refresh_tor_daemon, service::get_service_hostname, config::ConfigExt, supervisor::{request_restart, set_status}};
 
 /// Starts tor and accepts a function that will be used to report about the progress
 ///
@@ -26,12 +26,15 @@ pub async fn start_tor(on_event: impl Fn(StartTorPayload) -> ()) -> Result<()> {
     let mut lock = TOR_START_LOCK.write().await;
     drop(already_started);
 
+    set_status(TorStatus::Bootstrapping, "Starting tor").await;
+
     info!("Checking integrity...");
     on_event(StartTorPayload {
         message: "Checking integrity / writing torrc...".to_owned(),
         progress: 0.0,
     });
     check_integrity()?;
+    refresh_tor_daemon().await;
 
     write_torrc().await?;
 
@@ -43,9 +46,10 @@ pub async fn start_tor(on_event: impl Fn(StartTorPayload) -> ()) -> Result<()> {
     debug!("Creating unbounded channels...");
     debug!("Writing to rwlock...");
 
-    // Starts the tor thread
+    // Starts the tor thread, still running its futures on the current runtime
+    let runtime = Handle::current();
     let handle = thread::Builder::new().name("tor-mainloop".to_string()).spawn(move || {
-        let res = block_on(tor_main_loop());
+        let res = runtime.block_on(tor_main_loop());
         if res.is_ok() {
             info!("TOR: thread has finished!");
             return;
@@ -62,25 +66,23 @@ pub async fn start_tor(on_event: impl Fn(StartTorPayload) -> ()) -> Result<()> {
     // Handle tor startup messages
     let rx = get_from_tor_rx().await;
     loop {
-        if rx.len() > 0 {
-            let msg = rx.recv().await?;
-            match msg {
-                Tor2ClientMsg::BootstrapProgress(progress, status) => {
-                    on_event(StartTorPayload {
-                        progress: progress / 3.0 + 2.0 / 3.0,
-                        message: status,
-                    });
-
-                    if progress == 1.0 {
-                        // Tor is done starting up so we are exiting the read loop
-                        break;
-                    }
-                }
-                Tor2ClientMsg::ExitMsg(status, logs) => {
-                    bail!(TorStartError { logs, status });
+        let msg = rx.recv().await?;
+        match msg {
+            Tor2ClientMsg::BootstrapProgress(progress, status) => {
+                on_event(StartTorPayload {
+                    progress: progress / 3.0 + 2.0 / 3.0,
+                    message: status,
+                });
+
+                if progress == 1.0 {
+                    // Tor is done starting up so we are exiting the read loop
+                    break;
                 }
-                _ => {}
             }
+            Tor2ClientMsg::ExitMsg(status, logs) => {
+                bail!(TorStartError { logs, status });
+            }
+            _ => {}
         }
     }
 
@@ -88,6 +90,8 @@ pub async fn start_tor(on_event: impl Fn(StartTorPayload) -> ()) -> Result<()> {
     let hostname = get_service_hostname(true).await?;
     info!("Onion Service Hostname is {:?}", hostname);
     *lock = true;
+
+    set_status(TorStatus::Ready, "Tor has bootstrapped").await;
     Ok(())
 }
 
@@ -102,6 +106,20 @@ pub async fn write_torrc() -> Result<()> {
     Ok(())
 }
 
+/// Gets the configuration file for tor with every path inside the app directory replaced,
+/// so it can be shared in bug reports without leaking the user name
+///
+/// # Returns
+///
+/// The `torrc` file as a string
+pub async fn get_redacted_torrc() -> Result<String> {
+    let root = get_root_dir().to_string_lossy().replace("\\", "/");
+    let root = root.trim_end_matches('/');
+
+    let config = CONFIG.to_text().await?;
+    Ok(config.replace(root, "<enkrypton_root>"))
+}
+
 /// Waits for tor to exit and blocks the main handle
 pub async fn wait_for_exit() {
     let mut handle = TOR_THREAD.write().await;
@@ -114,6 +132,28 @@ pub async fn wait_for_exit() {
         .expect("msg");
 }
 
+/// Tells tor to read its configuration again, so changed client authorization keys are used.
+/// Does nothing if tor is not running, it reads them on start anyway.
+/// Tor can't be reloaded on platforms without signals, so the supervisor restarts it there instead
+pub async fn reload_tor() -> Result<()> {
+    if TOR_THREAD.read().await.is_none() {
+        return Ok(());
+    }
+
+    if !cfg!(target_family = "unix") {
+        if !request_restart().await {
+            warn!("Tor can not reload its configuration on this platform, changes apply after restarting enkrypton");
+        }
+
+        return Ok(());
+    }
+
+    debug!("Sending reload signal...");
+    get_to_tor_tx().await.send(Client2TorMsg::Reload()).await?;
+
+    Ok(())
+}
+
 /// Sends the exit signal to tor
 pub async fn stop_tor() -> Result<()> {
     let handle = TOR_THREAD.read().await;
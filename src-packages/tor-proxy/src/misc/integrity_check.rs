@@ -29,18 +29,8 @@ pub fn check_integrity() -> Result<()> {
 
 /// Extracts the tor binary from the assets into the `TOR_BINARY_PATH`
 fn extract_tor() -> Result<()> {
-
-    #[cfg(all(target_os ="windows", target_arch = "x86_64"))]
-    let tor_zip = include_bytes!(concat!(env!("OUT_DIR"), "/windows/x86_64/tor.zip"));
-
-    #[cfg(all(target_os ="windows", target_arch = "x86", not(target_arch="x86_64")))]
-    let tor_zip = include_bytes!(concat!(env!("OUT_DIR"), "/windows/i686/tor.zip"));
-
-    #[cfg(all(target_os ="linux", target_arch = "x86_64"))]
-    let tor_zip = include_bytes!(concat!(env!("OUT_DIR"), "/linux/x86_64/tor.zip"));
-
-    #[cfg(all(target_os ="linux", target_arch = "x86", not(target_arch="x86_64")))]
-    let tor_zip = include_bytes!(concat!(env!("OUT_DIR"), "/windows/i686/tor.zip"));
+    // The build script points TOR_BUNDLE_DIR to the bundle of the target platform
+    let tor_zip = include_bytes!(concat!(env!("TOR_BUNDLE_DIR"), "/tor.zip"));
 
     let target_dir = get_root_dir();
     zip_extract::extract(Cursor::new(tor_zip),&target_dir, true)?;
@@ -20,6 +20,8 @@ pub enum Tor2ClientMsg {
 pub enum Client2TorMsg {
     /// make sure just to send this one when we REALLY want the program to exit, bit difficult to start tor all over again
     Exit(),
+    /// Makes tor read its configuration again, including the client authorization keys (unix only)
+    Reload(),
 }
 
 // Define our error types. These may be customized for our error handling cases.
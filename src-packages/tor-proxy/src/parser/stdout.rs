@@ -13,6 +13,7 @@ use log::{debug, error, warn};
 
 use crate::{
     consts::MAX_LOG_SIZE,
+    logs::push_tor_log,
     manager::stop_tor,
     misc::{messages::Tor2ClientMsg, tools::get_from_tor_tx},
 };
@@ -73,6 +74,7 @@ pub async fn handle_tor_stdout(should_exit: Arc<AtomicBool>, mut child: Child) -
                 // Trimming messages to remove trailing \r\n
                 let msg = buf.trim_end_matches("\r\n").trim_end_matches('\n');
                 let msg = msg.to_string();
+                push_tor_log(&msg).await;
 
                 // Process the tor message
                 let res = handle_msg(&msg, &tx).await;
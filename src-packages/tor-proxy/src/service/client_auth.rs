@@ -0,0 +1,157 @@
+use std::{collections::HashSet, ffi::OsStr, path::{Path, PathBuf}};
+
+use anyhow::{anyhow, Result};
+use data_encoding::BASE32_NOPAD;
+use log::debug;
+use openssl::pkey::{Id, PKey};
+use shared::{config::CONFIG, onion::onion_address};
+use tokio::fs;
+
+/// The length of raw x25519 keys
+const CLIENT_AUTH_KEY_LENGTH: usize = 32;
+/// The extension of the files in the `authorized_clients` directory of our service
+const AUTHORIZED_CLIENT_EXT: &str = "auth";
+/// The extension of the files in the `ClientOnionAuthDir`
+const CLIENT_AUTH_EXT: &str = "auth_private";
+
+/// Generates a new x25519 key a contact can use to discover our onion service.
+/// Once at least one client is authorized, tor does not publish our descriptor in a readable form anymore,
+/// so strangers knowing our onion address can't reach us
+///
+/// # Returns
+///
+/// The raw private key, which should be given to the contact
+pub fn generate_client_auth_key() -> Result<Vec<u8>> {
+    let key = PKey::generate_x25519()?;
+    Ok(key.raw_private_key()?)
+}
+
+/// # Arguments
+///
+/// * `private_key` - The raw private client authorization key
+///
+/// # Returns
+///
+/// The raw public key of the given private key, which is what our service has to know
+pub fn client_auth_public_key(private_key: &[u8]) -> Result<Vec<u8>> {
+    let key = PKey::private_key_from_raw_bytes(private_key, Id::X25519)?;
+    Ok(key.raw_public_key()?)
+}
+
+/// Encodes a client authorization key the way tor does, so it can be shared in invitations
+///
+/// # Arguments
+///
+/// * `key` - The raw key
+///
+/// # Returns
+///
+/// The key as base32 without padding
+pub fn encode_client_auth_key(key: &[u8]) -> String {
+    BASE32_NOPAD.encode(key)
+}
+
+/// Decodes a client authorization key encoded by `encode_client_auth_key`
+///
+/// # Arguments
+///
+/// * `key` - The base32 encoded key
+///
+/// # Returns
+///
+/// The raw key, fails if this is not a x25519 key
+pub fn decode_client_auth_key(key: &str) -> Result<Vec<u8>> {
+    let raw = BASE32_NOPAD
+        .decode(key.trim().to_uppercase().as_bytes())
+        .map_err(|e| anyhow!("Invalid client authorization key: {}", e))?;
+
+    if raw.len() != CLIENT_AUTH_KEY_LENGTH {
+        return Err(anyhow!("Client authorization keys must be {} bytes long", CLIENT_AUTH_KEY_LENGTH));
+    }
+
+    Ok(raw)
+}
+
+/// # Returns
+///
+/// The directory tor reads the authorized clients of our onion service from
+pub fn get_authorized_clients_dir() -> PathBuf {
+    let mut dir = PathBuf::from(CONFIG.service_dir());
+    dir.push("authorized_clients");
+
+    dir
+}
+
+/// Replaces every file with the given extension in the given directory with the given files
+///
+/// # Arguments
+///
+/// * `dir` - The directory to write to, created if it does not exist
+/// * `ext` - The extension of the files that are managed by us
+/// * `files` - The name (without extension) and the content of every file
+///
+async fn replace_key_files(dir: &Path, ext: &str, files: Vec<(String, String)>) -> Result<()> {
+    fs::create_dir_all(dir).await?;
+
+    let mut names = HashSet::new();
+    for (name, content) in files {
+        let path = dir.join(format!("{}.{}", name, ext));
+        fs::write(&path, content).await?;
+
+        names.insert(path);
+    }
+
+    // Removing the keys that have been revoked
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension() == Some(OsStr::new(ext)) && !names.contains(&path) {
+            debug!("Removing stale key file {:?}", path);
+            fs::remove_file(path).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the keys of every contact that may discover our onion service.
+/// Files of contacts that are not given anymore are removed.
+/// Tor has to be reloaded for the changes to take effect
+///
+/// # Arguments
+///
+/// * `clients` - The onion hostname of every contact and the raw public key we issued to it
+///
+pub async fn write_authorized_clients(clients: &[(String, Vec<u8>)]) -> Result<()> {
+    let files = clients
+        .iter()
+        .map(|(hostname, key)| {
+            let content = format!("descriptor:x25519:{}", encode_client_auth_key(key));
+            (onion_address(hostname).to_string(), content)
+        })
+        .collect();
+
+    replace_key_files(&get_authorized_clients_dir(), AUTHORIZED_CLIENT_EXT, files).await
+}
+
+/// Writes the keys our contacts gave us, so tor can dial their onion services.
+/// Files of contacts that are not given anymore are removed.
+/// Tor has to be reloaded for the changes to take effect
+///
+/// # Arguments
+///
+/// * `services` - The onion hostname of every contact and the raw private key it issued to us
+///
+pub async fn write_client_auth(services: &[(String, Vec<u8>)]) -> Result<()> {
+    let files = services
+        .iter()
+        .map(|(hostname, key)| {
+            let address = onion_address(hostname);
+            let content = format!("{}:descriptor:x25519:{}", address, encode_client_auth_key(key));
+            (address.to_string(), content)
+        })
+        .collect();
+
+    let dir = PathBuf::from(CONFIG.client_auth_dir());
+    replace_key_files(&dir, CLIENT_AUTH_EXT, files).await
+}
@@ -1,3 +1,7 @@
 mod hostname;
+mod ownership;
+mod client_auth;
 
-pub use hostname::get_service_hostname;
\ No newline at end of file
+pub use hostname::get_service_hostname;
+pub use ownership::*;
+pub use client_auth::*;
\ No newline at end of file
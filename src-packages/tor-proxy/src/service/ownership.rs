@@ -0,0 +1,57 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use ed25519_dalek::{
+    hazmat::{raw_sign, ExpandedSecretKey},
+    Signature, VerifyingKey,
+};
+use sha2::Sha512;
+use shared::{config::CONFIG, onion::onion_public_key};
+
+/// The header tor writes in front of the expanded secret key of the onion service
+const SECRET_KEY_HEADER: &[u8] = b"== ed25519v1-secret: type0 ==\0\0\0";
+
+/// Signs the given data with the secret key of our onion service.
+/// Everyone knowing our onion address can verify that the data has been signed by the owner of the address
+///
+/// # Arguments
+///
+/// * `data` - The data to sign
+///
+/// # Returns
+///
+/// The ed25519 signature of the data
+pub async fn sign_as_service(data: &[u8]) -> Result<Vec<u8>> {
+    let mut key_path = PathBuf::from(CONFIG.service_dir());
+    key_path.push("hs_ed25519_secret_key");
+
+    let raw = tokio::fs::read(key_path).await?;
+    let secret = raw
+        .strip_prefix(SECRET_KEY_HEADER)
+        .ok_or(anyhow!("Invalid secret key file of the onion service"))?;
+
+    let secret = ExpandedSecretKey::from_slice(secret)?;
+    let public = VerifyingKey::from(&secret);
+
+    let signature = raw_sign::<Sha512>(&secret, data, &public);
+    Ok(signature.to_bytes().to_vec())
+}
+
+/// Verifies that the given data has been signed by the owner of the given onion address
+///
+/// # Arguments
+///
+/// * `hostname` - The onion address that should have signed the data
+/// * `data` - The data that has been signed
+/// * `signature` - The signature created by `sign_as_service`
+///
+/// # Returns
+///
+/// Fails if the address is invalid or the signature has not been created by its owner
+pub fn verify_service_signature(hostname: &str, data: &[u8], signature: &[u8]) -> Result<()> {
+    let key = onion_public_key(hostname)?;
+    let signature = Signature::from_slice(signature)?;
+
+    key.verify_strict(data, &signature)
+        .map_err(|_| anyhow!("{} did not sign the data", hostname))
+}
@@ -0,0 +1,42 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use shared::config::CONFIG;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::timeout,
+};
+
+/// SOCKS5 greeting offering just the "no authentication" method
+const SOCKS5_GREETING: [u8; 3] = [0x05, 0x01, 0x00];
+
+/// Checks if the socks proxy of tor is still answering by sending a SOCKS5 greeting.
+/// A process which is alive but stalled won't answer and fails this check.
+///
+/// # Arguments
+///
+/// * `max_wait` - How long the proxy has to answer
+///
+/// # Returns
+///
+/// Ok if the proxy answered with a valid SOCKS5 reply
+pub(super) async fn check_socks_alive(max_wait: Duration) -> Result<()> {
+    let probe = async {
+        let mut stream = TcpStream::connect(CONFIG.get_socks_host()).await?;
+        stream.write_all(&SOCKS5_GREETING).await?;
+
+        let mut reply = [0u8; 2];
+        stream.read_exact(&mut reply).await?;
+
+        if reply[0] != 0x05 {
+            return Err(anyhow!("Socks proxy replied with invalid version {}", reply[0]));
+        }
+
+        Ok(())
+    };
+
+    timeout(max_wait, probe)
+        .await
+        .map_err(|_| anyhow!("Socks proxy did not answer within {:?}", max_wait))?
+}
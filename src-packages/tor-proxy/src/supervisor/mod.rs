@@ -0,0 +1,213 @@
+mod health;
+mod status;
+
+use std::{sync::atomic::Ordering, time::{Duration, Instant}};
+
+use anyhow::{anyhow, Result};
+use log::{debug, error, info, warn};
+use payloads::payloads::TorStatus;
+use tokio::{
+    select,
+    task::spawn_blocking,
+    time::{interval_at, sleep, timeout},
+};
+
+pub use status::{current_status, subscribe_status, wait_until_ready};
+pub(crate) use status::set_status;
+
+use crate::{
+    consts::{
+        setup_tor_channels, HEALTH_CHECK_INTERVAL, HEALTH_CHECK_TIMEOUT, MAX_FAILED_HEALTH_CHECKS,
+        MAX_RESTART_BACKOFF, MIN_RESTART_BACKOFF, STABLE_RUN_DURATION, SUPERVISOR_EXIT,
+        SUPERVISOR_HANDLE, SUPERVISOR_RESTART_LOCK, SUPERVISOR_RESTART_REQUEST, TOR_BOOTSTRAP_TIMEOUT, TOR_THREAD,
+    },
+    manager::{start_tor, stop_tor},
+    misc::{messages::Tor2ClientMsg, tools::get_from_tor_rx},
+    service::get_service_hostname,
+};
+use health::check_socks_alive;
+
+/// Spawns the supervisor which watches the tor process after it has been started.
+/// It restarts tor with a backoff if the process crashes or the socks proxy stalls.
+/// Should only be called after `start_tor` was successful.
+pub async fn spawn_supervisor() -> Result<()> {
+    let mut handle = SUPERVISOR_HANDLE.write().await;
+    if handle.is_some() {
+        return Err(anyhow!("The tor supervisor is already running."));
+    }
+
+    SUPERVISOR_EXIT.store(false, Ordering::Relaxed);
+    let h = tokio::spawn(async move {
+        let res = supervisor_loop().await;
+        if let Err(e) = res {
+            error!("TOR: supervisor has failed: {:?}", e);
+        } else {
+            info!("TOR: supervisor has finished");
+        }
+    });
+
+    handle.replace(h);
+    Ok(())
+}
+
+/// Stops the supervisor so tor can be stopped without it being restarted.
+/// Waits for a restart that is currently in progress to finish first.
+pub async fn stop_supervisor() {
+    SUPERVISOR_EXIT.store(true, Ordering::Relaxed);
+
+    let _restart = SUPERVISOR_RESTART_LOCK.lock().await;
+    if let Some(h) = SUPERVISOR_HANDLE.write().await.take() {
+        h.abort();
+    }
+
+    set_status(TorStatus::Stopped, "Tor is shutting down").await;
+}
+
+/// Asks the supervisor to restart tor, so it reads its configuration and key files again.
+/// Used where tor can't be told to reload
+///
+/// # Returns
+///
+/// Whether the supervisor is running and will restart tor
+pub(crate) async fn request_restart() -> bool {
+    if SUPERVISOR_HANDLE.read().await.is_none() || SUPERVISOR_EXIT.load(Ordering::Relaxed) {
+        return false;
+    }
+
+    SUPERVISOR_RESTART_REQUEST.notify_one();
+    true
+}
+
+/// Watches the messages of the tor process and checks the socks proxy periodically.
+/// Restarts tor if it exited, the proxy stopped answering too often in a row or a restart has been requested.
+async fn supervisor_loop() -> Result<()> {
+    let expected_hostname = get_service_hostname(true).await?;
+
+    let mut backoff = *MIN_RESTART_BACKOFF;
+    let mut failed_checks = 0;
+    let mut last_restart: Option<Instant> = None;
+
+    let mut checks = interval_at(
+        (Instant::now() + *HEALTH_CHECK_INTERVAL).into(),
+        *HEALTH_CHECK_INTERVAL,
+    );
+
+    while !SUPERVISOR_EXIT.load(Ordering::Relaxed) {
+        // Fetched every iteration as a restart replaces the channels
+        let rx = get_from_tor_rx().await;
+
+        let mut requested = false;
+        let restart_reason = select! {
+            _ = SUPERVISOR_RESTART_REQUEST.notified() => {
+                requested = true;
+                Some("Configuration changed".to_string())
+            },
+            msg = rx.recv() => match msg {
+                Ok(Tor2ClientMsg::ExitMsg(status, _)) => Some(format!("Tor exited unexpectedly ({})", status)),
+                Ok(_) => None,
+                Err(_) => Some("Tor message channel was closed".to_string()),
+            },
+            _ = checks.tick() => match check_socks_alive(*HEALTH_CHECK_TIMEOUT).await {
+                Ok(()) => {
+                    failed_checks = 0;
+                    set_status(TorStatus::Ready, "Socks proxy is answering again").await;
+
+                    // Tor ran long enough without problems, so the next restart can be quick again
+                    if last_restart.is_some_and(|e| e.elapsed() > *STABLE_RUN_DURATION) {
+                        backoff = *MIN_RESTART_BACKOFF;
+                        last_restart = None;
+                    }
+
+                    None
+                }
+                Err(e) => {
+                    failed_checks += 1;
+                    warn!("TOR: health check {}/{} failed: {}", failed_checks, *MAX_FAILED_HEALTH_CHECKS, e);
+                    set_status(TorStatus::Degraded, &e.to_string()).await;
+
+                    (failed_checks >= *MAX_FAILED_HEALTH_CHECKS)
+                        .then(|| format!("Socks proxy stalled ({})", e))
+                }
+            },
+        };
+
+        let Some(reason) = restart_reason else {
+            continue;
+        };
+
+        if SUPERVISOR_EXIT.load(Ordering::Relaxed) {
+            break;
+        }
+
+        // Restarting until tor is up again or the app is exiting.
+        // Requested restarts happen right away and don't increase the backoff
+        loop {
+            let delay = if requested { Duration::ZERO } else { backoff };
+            warn!("TOR: {}. Restarting in {:?}...", reason, delay);
+            set_status(TorStatus::Restarting, &reason).await;
+            sleep(delay).await;
+
+            if !requested {
+                backoff = (backoff * 2).min(*MAX_RESTART_BACKOFF);
+                last_restart = Some(Instant::now());
+            }
+            requested = false;
+
+            let lock = SUPERVISOR_RESTART_LOCK.lock().await;
+            if SUPERVISOR_EXIT.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+
+            let res = restart_tor().await;
+            drop(lock);
+
+            match res {
+                Ok(()) => break,
+                Err(e) => error!("TOR: could not restart: {:?}", e),
+            }
+        }
+
+        failed_checks = 0;
+        checks.reset();
+
+        // The onion service is published again with the keys in the service dir, so this should never change
+        let hostname = get_service_hostname(true).await?;
+        if hostname != expected_hostname {
+            warn!(
+                "TOR: onion service hostname changed after restart from {:?} to {:?}",
+                expected_hostname, hostname
+            );
+        }
+
+        info!("TOR: restarted, onion service {:?} is published again", hostname);
+    }
+
+    Ok(())
+}
+
+/// Stops what is left of the old tor process and starts a new one
+async fn restart_tor() -> Result<()> {
+    // The mainloop may already be done if tor crashed, so failing to stop is fine here
+    if let Err(e) = stop_tor().await {
+        debug!("TOR: could not send exit signal before restart: {}", e);
+    }
+
+    let handle = TOR_THREAD.write().await.take();
+    if let Some(handle) = handle {
+        spawn_blocking(move || handle.join())
+            .await?
+            .map_err(|_| anyhow!("Could not wait for old tor thread to exit"))?;
+    }
+
+    // Fresh channels so no stale exit messages are read by the new process
+    setup_tor_channels().await;
+
+    timeout(
+        *TOR_BOOTSTRAP_TIMEOUT,
+        start_tor(|payload| debug!("TOR: restart progress {}: {}", payload.progress, payload.message)),
+    )
+    .await
+    .map_err(|_| anyhow!("Tor did not bootstrap within {:?}", *TOR_BOOTSTRAP_TIMEOUT))??;
+
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use lazy_static::lazy_static;
+use log::{info, warn};
+use payloads::{
+    event::emit_payload,
+    payloads::{TorStatus, TorStatusPayload},
+};
+use tokio::{sync::watch, time::timeout};
+
+lazy_static! {
+    /// Holds the latest status of tor. Every subscriber is notified when it changes
+    static ref TOR_STATUS: watch::Sender<TorStatus> = watch::channel(TorStatus::Stopped).0;
+}
+
+/// # Returns
+///
+/// The status tor currently has
+pub fn current_status() -> TorStatus {
+    *TOR_STATUS.borrow()
+}
+
+/// Subscribes to status changes of tor, used by the messaging layer to pause and resume connections
+///
+/// # Returns
+///
+/// A receiver which is notified every time the status changes
+pub fn subscribe_status() -> watch::Receiver<TorStatus> {
+    TOR_STATUS.subscribe()
+}
+
+/// Waits until tor is ready to accept connections
+///
+/// # Arguments
+///
+/// * `max_wait` - How long to wait for tor before giving up
+pub async fn wait_until_ready(max_wait: Duration) -> Result<()> {
+    let mut rx = subscribe_status();
+    timeout(max_wait, rx.wait_for(|s| s.is_ready()))
+        .await
+        .map_err(|_| anyhow!("Tor is not ready (status is {:?})", current_status()))??;
+
+    Ok(())
+}
+
+/// Sets the new status of tor and notifies subscribers and the frontend
+///
+/// # Arguments
+///
+/// * `status` - The new status
+/// * `message` - Why the status has changed
+pub(crate) async fn set_status(status: TorStatus, message: &str) {
+    let old = TOR_STATUS.send_replace(status);
+    if old == status {
+        return;
+    }
+
+    info!("Tor status changed from {:?} to {:?}: {}", old, status, message);
+    let res = emit_payload(TorStatusPayload {
+        status,
+        message: message.to_string(),
+    });
+
+    if let Err(e) = res {
+        warn!("Could not emit tor status: {:?}", e);
+    }
+}
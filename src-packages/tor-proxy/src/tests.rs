@@ -0,0 +1,92 @@
+use payloads::payloads::{IntroDosRate, TorDosDefenses};
+
+use crate::dos::{dos_defenses_torrc, validate_dos_defenses, TorDaemonInfo, TorDaemonVersion};
+
+/// What the tor of the expert bundle prints for `tor --version`
+const BUNDLED_OUTPUT: &str = "Tor version 0.4.8.17.
+This build of Tor is covered by the GNU General Public License (https://www.gnu.org/licenses/gpl-3.0.en.html)
+Tor is running on Linux with Libevent 2.1.12-stable, OpenSSL 3.5.1, Zlib 1.2.13, Liblzma N/A, Libzstd N/A and Glibc 2.36 as libc.
+Tor compiled with GCC version 12.2.0
+";
+
+fn bundled() -> TorDaemonInfo {
+    TorDaemonInfo::parse(BUNDLED_OUTPUT).unwrap()
+}
+
+#[test]
+fn tor_version_output_is_parsed() {
+    let daemon = bundled();
+    assert_eq!(daemon.version, TorDaemonVersion(0, 4, 8, 17));
+    assert!(daemon.supports_pow());
+    assert!(daemon.supports_intro_dos());
+
+    // Without the GPL modules there is no proof-of-work
+    let lgpl = TorDaemonInfo::parse("Tor version 0.4.9.1-alpha (git-abcdef).").unwrap();
+    assert_eq!(lgpl.version, TorDaemonVersion(0, 4, 9, 1));
+    assert!(!lgpl.supports_pow());
+
+    let old = TorDaemonInfo::parse("Tor version 0.4.1.9.\nGNU General Public License").unwrap();
+    assert!(!old.supports_pow());
+    assert!(!old.supports_intro_dos());
+
+    assert!(TorDaemonInfo::parse("Not tor").is_err());
+    assert!(TorDaemonInfo::parse("Tor version 0.4.").is_err());
+}
+
+#[test]
+fn dos_defenses_are_validated() {
+    let daemon = bundled();
+    let valid = TorDosDefenses {
+        pow_enabled: true,
+        intro_rate: Some(IntroDosRate { rate_per_sec: 25, burst_per_sec: 200 }),
+        max_streams: 10,
+        max_streams_close_circuit: true,
+    };
+    validate_dos_defenses(&valid, Some(&daemon)).unwrap();
+    validate_dos_defenses(&TorDosDefenses::default(), Some(&daemon)).unwrap();
+
+    let invalid = [
+        TorDosDefenses { intro_rate: Some(IntroDosRate { rate_per_sec: 0, burst_per_sec: 10 }), ..valid },
+        TorDosDefenses { intro_rate: Some(IntroDosRate { rate_per_sec: 20, burst_per_sec: 10 }), ..valid },
+        TorDosDefenses { max_streams: 70000, ..valid },
+        TorDosDefenses { max_streams: 0, ..valid },
+    ];
+
+    for defenses in invalid {
+        assert!(validate_dos_defenses(&defenses, Some(&daemon)).is_err(), "{:?}", defenses);
+    }
+
+    // Tor versions that don't know the options are refused
+    let old = TorDaemonInfo { version: TorDaemonVersion(0, 4, 7, 16), has_pow_module: false };
+    assert!(validate_dos_defenses(&valid, Some(&old)).is_err());
+    validate_dos_defenses(&TorDosDefenses { pow_enabled: false, ..valid }, Some(&old)).unwrap();
+}
+
+#[test]
+fn dos_defenses_are_written_to_the_torrc() {
+    let defenses = TorDosDefenses {
+        pow_enabled: true,
+        intro_rate: Some(IntroDosRate { rate_per_sec: 25, burst_per_sec: 200 }),
+        max_streams: 10,
+        max_streams_close_circuit: true,
+    };
+
+    assert_eq!(
+        dos_defenses_torrc(&defenses, Some(&bundled())),
+        [
+            "HiddenServiceEnableIntroDoSDefense 1",
+            "HiddenServiceEnableIntroDoSRatePerSec 25",
+            "HiddenServiceEnableIntroDoSBurstPerSec 200",
+            "HiddenServicePoWDefensesEnabled 1",
+            "HiddenServiceMaxStreams 10",
+            "HiddenServiceMaxStreamsCloseCircuit 1",
+        ]
+    );
+    assert!(dos_defenses_torrc(&TorDosDefenses::default(), Some(&bundled())).is_empty());
+
+    // Unknown options would keep tor from starting, so they are left out
+    assert_eq!(
+        dos_defenses_torrc(&defenses, None),
+        ["HiddenServiceMaxStreams 10", "HiddenServiceMaxStreamsCloseCircuit 1"]
+    );
+}
@@ -1,129 +1,65 @@
 use std::{
-    fmt::Display,
+    collections::HashMap,
+    env,
     fs::{self, File},
     io::{BufReader, Cursor, Read, Write},
     path::{Path, PathBuf},
 };
 
+mod manifest;
+mod platform;
+mod signature;
+#[cfg(test)]
+mod tests;
+
+pub use platform::*;
+
 use flate2::bufread::GzDecoder;
 use lazy_static::lazy_static;
 use openssl::hash::{Hasher, MessageDigest};
 use tar::Archive;
+use manifest::{check_pinned_hash, hash_bundle, parse_manifest};
+use signature::{get_signing_key, verify_detached};
 use zip::{write::SimpleFileOptions, ZipWriter};
 
 lazy_static! {
     pub static ref DIGEST: MessageDigest = MessageDigest::sha256();
 }
 
+/// Set to a bundle archive or a directory containing the bundles to build without network access
+pub const TOR_BUNDLE_ENV: &str = "TOR_BUNDLE_PATH";
+
 /// Logs a message in the cargo build script format
 fn cargo_log(message: &str) {
     println!("cargo:warning={}", message);
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-enum Os {
-    Windows,
-    Linux,
-}
-
-impl Display for Os {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                Os::Windows => "windows",
-                Os::Linux => "linux",
-            }
-        )
-    }
-}
-
-/// Generates download information for the supported platforms
-fn get_download_info(version: &str) -> Vec<((Os, String), String)> {
-    let base_url = format!(
-        "https://archive.torproject.org/tor-package-archive/torbrowser/{}/",
-        version
-    );
-    let mut downloads = Vec::new();
-
-    // Windows x86_64
-    downloads.push((
-        (Os::Windows, "x86_64".to_string()),
-        format!(
-            "{}tor-expert-bundle-windows-x86_64-{}.tar.gz",
-            base_url, version
-        ),
-    ));
-
-    // Windows i686
-    downloads.push((
-        (Os::Windows, "i686".to_string()),
-        format!(
-            "{}tor-expert-bundle-windows-i686-{}.tar.gz",
-            base_url, version
-        ),
-    ));
-
-    // Linux x86_64
-    downloads.push((
-        (Os::Linux, "x86_64".to_string()),
-        format!(
-            "{}tor-expert-bundle-linux-x86_64-{}.tar.gz",
-            base_url, version
-        ),
-    ));
-
-    // Linux i686
-    downloads.push((
-        (Os::Linux, "i686".to_string()),
-        format!(
-            "{}tor-expert-bundle-linux-i686-{}.tar.gz",
-            base_url, version
-        ),
-    ));
-
-    downloads
-}
-
-pub fn download_version(out_dir: &Path, version: &str) -> anyhow::Result<()> {
+/// Downloads (or reads from `TOR_BUNDLE_PATH`) the expert bundle of the given version for the current platform.
+/// `manifest` contains the pinned hashes of the bundles in the `sha256sum` format.
+pub fn download_version(out_dir: &Path, version: &str, manifest: &str) -> anyhow::Result<()> {
     let out_dir = out_dir.to_path_buf();
     let version = version.to_string();
+    let manifest = parse_manifest(manifest)?;
 
     // Detect current platform
-    let current_platform = detect_current_platform()?;
-    cargo_log(&format!("Detected platform: {} {}", current_platform.0, current_platform.1));
-    
-    // Get all download info but filter for current platform
-    let downloads = get_download_info(&version);
-    let matching_download = downloads
-        .into_iter()
-        .find(|((os, arch), _)| *os == current_platform.0 && *arch == current_platform.1);
-    
-    if let Some(download_info) = matching_download {
-        // Downloading archive
-        fs::create_dir_all(&out_dir)?;
-        process(download_info, version, out_dir)?;
-    } else {
-        return Err(anyhow::anyhow!(
-            "No matching download found for current platform: {} {}",
-            current_platform.0,
-            current_platform.1
-        ));
-    }
+    let platform = detect_current_platform()?;
+    cargo_log(&format!("Detected platform: {} {}", platform.bundle_os, platform.bundle_arch));
+
+    // Downloading archive
+    fs::create_dir_all(&out_dir)?;
+    process(platform, version, out_dir, &manifest)?;
 
     Ok(())
 }
 
 fn process(
-    ((os, arch), download_url): ((Os, String), String),
+    platform: &Platform,
     version: String,
     out_dir: PathBuf,
+    manifest: &HashMap<String, String>,
 ) -> anyhow::Result<()> {
-    let download_dir = out_dir
-        .join(format!("{}", os))
-        .join(arch.to_lowercase())
-        .into_boxed_path();
+    let (os, arch) = (platform.bundle_os, platform.bundle_arch);
+    let download_dir = platform.bundle_dir(&out_dir).into_boxed_path();
 
     let version_file = download_dir.join("version.txt").into_boxed_path();
     if version_file.is_file() {
@@ -153,17 +89,48 @@ fn process(
 
     let out_file = download_dir.join("tor.tar.gz");
 
-    cargo_log(&format!("Downloading {} {} from {}", os, arch, download_url));
-    let resp = reqwest::blocking::get(&download_url)?;
-    if !resp.status().is_success() {
-        return Err(anyhow::anyhow!(
-            "Failed to download: HTTP {}",
-            resp.status()
-        ));
-    }
+    let file_name = platform.file_name(&version);
+    let download_url = platform.download_url(&version);
+
+    // Nothing is written or extracted before the bundle is verified
+    let bundle = match env::var_os(TOR_BUNDLE_ENV) {
+        Some(path) => {
+            let archive = local_archive_path(Path::new(&path), &file_name);
+            let bundle = read_local_bundle(&archive)?;
+            if manifest.contains_key(&file_name) {
+                check_pinned_hash(manifest, &file_name, &bundle)?;
+            } else {
+                verify_local_signature(&archive, &bundle)?;
+                cargo_log(&format!(
+                    "No hash pinned for {}, add \"{}  {}\" to the manifest",
+                    file_name,
+                    hash_bundle(&bundle)?,
+                    file_name
+                ));
+            }
+
+            bundle
+        }
+        None => {
+            cargo_log(&format!("Downloading {} {}", os, arch));
+            let bundle = download_bundle(&download_url)?;
+            if manifest.contains_key(&file_name) {
+                check_pinned_hash(manifest, &file_name, &bundle)?;
+            } else {
+                cargo_log(&format!(
+                    "No hash pinned for {}, add \"{}  {}\" to the manifest for offline builds",
+                    file_name,
+                    hash_bundle(&bundle)?,
+                    file_name
+                ));
+            }
+
+            bundle
+        }
+    };
 
     let mut file = File::create(&out_file)?;
-    let mut content = Cursor::new(resp.bytes()?);
+    let mut content = Cursor::new(bundle);
     std::io::copy(&mut content, &mut file)?;
 
     cargo_log("Unpacking...");
@@ -177,20 +144,9 @@ fn process(
 
     archive.unpack(&out_archive)?;
 
-    // Find the Tor and Snowflake binaries within the unpacked directory
-    cargo_log("Calculating hashes...");
-
-    // For Windows, look in Tor/tor.exe and Tor/PluggableTransports/snowflake-client.exe
-    // For Linux, look in tor and tor/pluggable_transports/snowflake-client
-
-    let tor_binary_dir = if os == Os::Windows {
-        out_archive.join("Tor")
-    } else {
-        out_archive.join("tor")
-    };
-
     // Calculate the hash for the Tor binary
-    let tor_hash = get_hash(&tor_binary_dir, "tor", os)?;
+    cargo_log("Calculating hashes...");
+    let tor_hash = get_hash(&out_archive.join(platform.tor_binary))?;
     let tor_hash_f = download_dir.join("tor.hash");
     File::create(tor_hash_f)?.write_all(tor_hash.as_bytes())?;
 
@@ -222,15 +178,70 @@ fn process(
     Ok(())
 }
 
-fn get_hash(path: &Path, file_name: &str, os: Os) -> anyhow::Result<String> {
-    let mut path = path.to_path_buf();
-    match os {
-        Os::Windows => path.push(format!("{}.exe", file_name)),
-        _ => path.push(file_name),
+/// Downloads the bundle and verifies its detached signature
+fn download_bundle(download_url: &str) -> anyhow::Result<Vec<u8>> {
+    cargo_log(&format!("Downloading from {}", download_url));
+    let resp = reqwest::blocking::get(download_url)?;
+    if !resp.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "Failed to download: HTTP {}",
+            resp.status()
+        ));
+    }
+
+    let bundle = resp.bytes()?;
+
+    let signature_url = format!("{}.asc", download_url);
+    cargo_log(&format!("Verifying signature from {}", signature_url));
+    let signature = reqwest::blocking::get(&signature_url)?.error_for_status()?.bytes()?;
+
+    let key = get_signing_key()?;
+    verify_detached(&key, &bundle, &signature)
+        .map_err(|e| anyhow::anyhow!("Signature of {} is invalid: {}", download_url, e))?;
+
+    Ok(bundle.to_vec())
+}
+
+/// Resolves `path`, which is either the archive itself or a directory containing it, to the archive
+fn local_archive_path(path: &Path, file_name: &str) -> PathBuf {
+    if path.is_dir() {
+        path.join(file_name)
+    } else {
+        path.to_path_buf()
     }
+}
+
+/// Reads the bundle from the given archive
+fn read_local_bundle(archive: &Path) -> anyhow::Result<Vec<u8>> {
+    cargo_log(&format!("Reading bundle from {}", archive.display()));
+    fs::read(archive).map_err(|e| {
+        anyhow::anyhow!("Could not read bundle {}: {}", archive.display(), e)
+    })
+}
+
+/// Verifies a local bundle whose hash is not pinned with the detached signature next to it (`<archive>.asc`).
+/// Set `TOR_SIGNING_KEY_PATH` as well to build entirely offline.
+fn verify_local_signature(archive: &Path, bundle: &[u8]) -> anyhow::Result<()> {
+    let mut signature_path = archive.as_os_str().to_owned();
+    signature_path.push(".asc");
+    let signature_path = PathBuf::from(signature_path);
+
+    cargo_log(&format!("Verifying signature from {}", signature_path.display()));
+    let signature = fs::read(&signature_path).map_err(|e| {
+        anyhow::anyhow!(
+            "Hash of {} is not pinned and its signature {} could not be read: {}",
+            archive.display(),
+            signature_path.display(),
+            e
+        )
+    })?;
 
-    let path: Box<Path> = path.into_boxed_path();
+    let key = get_signing_key()?;
+    verify_detached(&key, bundle, &signature)
+        .map_err(|e| anyhow::anyhow!("Signature of {} is invalid: {}", archive.display(), e))
+}
 
+fn get_hash(path: &Path) -> anyhow::Result<String> {
     if !path.exists() {
         return Err(anyhow::anyhow!("Binary file not found at {:?}", path));
     }
@@ -248,26 +259,3 @@ fn get_hash(path: &Path, file_name: &str, os: Os) -> anyhow::Result<String> {
 
     Ok(hex::encode(hash))
 }
-
-/// Detects the current operating system and architecture
-fn detect_current_platform() -> anyhow::Result<(Os, String)> {
-    // Detect operating system
-    let os = if cfg!(target_os = "windows") {
-        Os::Windows
-    } else if cfg!(target_os = "linux") {
-        Os::Linux
-    } else {
-        return Err(anyhow::anyhow!("Unsupported operating system"));
-    };
-
-    // Detect architecture
-    let arch = if cfg!(target_arch = "x86_64") {
-        "x86_64".to_string()
-    } else if cfg!(target_arch = "x86") {
-        "i686".to_string()
-    } else {
-        return Err(anyhow::anyhow!("Unsupported architecture"));
-    };
-
-    Ok((os, arch))
-}
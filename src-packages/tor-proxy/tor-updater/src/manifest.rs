@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+use openssl::hash::hash;
+
+use crate::DIGEST;
+
+/// Parses a hash manifest in the `sha256sum` format (`<hex hash>  <file name>` per line).
+/// Empty lines and lines starting with `#` are skipped.
+///
+/// # Returns
+///
+/// The pinned hashes by file name
+pub(crate) fn parse_manifest(manifest: &str) -> anyhow::Result<HashMap<String, String>> {
+    let mut hashes = HashMap::new();
+    for line in manifest.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut parts = line.split_whitespace();
+        let (Some(hash), Some(file_name)) = (parts.next(), parts.next()) else {
+            return Err(anyhow::anyhow!("Invalid manifest line: {}", line));
+        };
+
+        // sha256sum marks binary files with a leading '*'
+        let file_name = file_name.trim_start_matches('*');
+        hashes.insert(file_name.to_string(), hash.to_lowercase());
+    }
+
+    Ok(hashes)
+}
+
+/// Hashes the given bundle with the same digest the manifest uses
+pub(crate) fn hash_bundle(bundle: &[u8]) -> anyhow::Result<String> {
+    Ok(hex::encode(hash(*DIGEST, bundle)?))
+}
+
+/// Checks the bundle against the hash pinned for it in the manifest
+pub(crate) fn check_pinned_hash(
+    manifest: &HashMap<String, String>,
+    file_name: &str,
+    bundle: &[u8],
+) -> anyhow::Result<()> {
+    let expected = manifest
+        .get(file_name)
+        .ok_or(anyhow::anyhow!("No hash pinned for {} in the manifest", file_name))?;
+
+    let actual = hash_bundle(bundle)?;
+    if *expected != actual {
+        return Err(anyhow::anyhow!(
+            "Hash of {} is {}, but {} is pinned in the manifest",
+            file_name,
+            actual,
+            expected
+        ));
+    }
+
+    Ok(())
+}
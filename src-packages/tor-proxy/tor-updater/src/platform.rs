@@ -0,0 +1,87 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+/// Url of the directory the expert bundles of every tor browser version are published in
+const ARCHIVE_URL: &str = "https://archive.torproject.org/tor-package-archive/torbrowser";
+
+/// A target tor publishes an expert bundle for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Platform {
+    /// The `target_os` rust uses for this platform
+    pub target_os: &'static str,
+    /// The `target_arch` rust uses for this platform
+    pub target_arch: &'static str,
+    /// The os as it is named in the file name of the bundle
+    pub bundle_os: &'static str,
+    /// The arch as it is named in the file name of the bundle
+    pub bundle_arch: &'static str,
+    /// Where the tor binary is inside of the unpacked bundle
+    pub tor_binary: &'static str,
+}
+
+/// Every expert bundle target, new targets only need a new entry here
+pub const PLATFORMS: &[Platform] = &[
+    Platform { target_os: "windows", target_arch: "x86_64", bundle_os: "windows", bundle_arch: "x86_64", tor_binary: "Tor/tor.exe" },
+    Platform { target_os: "windows", target_arch: "x86", bundle_os: "windows", bundle_arch: "i686", tor_binary: "Tor/tor.exe" },
+    Platform { target_os: "linux", target_arch: "x86_64", bundle_os: "linux", bundle_arch: "x86_64", tor_binary: "tor/tor" },
+    Platform { target_os: "linux", target_arch: "x86", bundle_os: "linux", bundle_arch: "i686", tor_binary: "tor/tor" },
+    Platform { target_os: "linux", target_arch: "aarch64", bundle_os: "linux", bundle_arch: "aarch64", tor_binary: "tor/tor" },
+    Platform { target_os: "macos", target_arch: "x86_64", bundle_os: "macos", bundle_arch: "x86_64", tor_binary: "tor/tor" },
+    Platform { target_os: "macos", target_arch: "aarch64", bundle_os: "macos", bundle_arch: "aarch64", tor_binary: "tor/tor" },
+    Platform { target_os: "android", target_arch: "aarch64", bundle_os: "android", bundle_arch: "aarch64", tor_binary: "tor/libTor.so" },
+    Platform { target_os: "android", target_arch: "arm", bundle_os: "android", bundle_arch: "armv7", tor_binary: "tor/libTor.so" },
+    Platform { target_os: "android", target_arch: "x86", bundle_os: "android", bundle_arch: "x86", tor_binary: "tor/libTor.so" },
+    Platform { target_os: "android", target_arch: "x86_64", bundle_os: "android", bundle_arch: "x86_64", tor_binary: "tor/libTor.so" },
+];
+
+impl Platform {
+    /// The file name of the expert bundle of this platform with the given version
+    pub fn file_name(&self, version: &str) -> String {
+        format!(
+            "tor-expert-bundle-{}-{}-{}.tar.gz",
+            self.bundle_os, self.bundle_arch, version
+        )
+    }
+
+    /// The url the expert bundle of this platform with the given version is downloaded from
+    pub fn download_url(&self, version: &str) -> String {
+        format!("{}/{}/{}", ARCHIVE_URL, version, self.file_name(version))
+    }
+
+    /// The directory inside of `out_dir` this platform stores its `tor.hash` and `tor.zip` in
+    pub fn bundle_dir(&self, out_dir: &Path) -> PathBuf {
+        out_dir.join(self.bundle_os).join(self.bundle_arch)
+    }
+}
+
+/// Looks up the platform for the given rust `target_os` and `target_arch`
+pub fn find_platform(target_os: &str, target_arch: &str) -> Option<&'static Platform> {
+    PLATFORMS
+        .iter()
+        .find(|p| p.target_os == target_os && p.target_arch == target_arch)
+}
+
+/// Gets every platform which has a bundle of the given version in a directory listing of the archive
+pub fn available_platforms(listing: &str, version: &str) -> Vec<&'static Platform> {
+    let files: Vec<&str> = listing.split_whitespace().collect();
+
+    PLATFORMS
+        .iter()
+        .filter(|p| files.contains(&p.file_name(version).as_str()))
+        .collect()
+}
+
+/// Detects the platform that is being built for.
+/// In build scripts this is the target (not the host) cargo sets in `CARGO_CFG_TARGET_*`.
+pub fn detect_current_platform() -> anyhow::Result<&'static Platform> {
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap_or(env::consts::OS.to_string());
+    let target_arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap_or(env::consts::ARCH.to_string());
+
+    find_platform(&target_os, &target_arch).ok_or(anyhow::anyhow!(
+        "No tor expert bundle for platform {} {}",
+        target_os,
+        target_arch
+    ))
+}
@@ -0,0 +1,72 @@
+use std::{env, fs::File, io::Cursor};
+
+use anyhow::{anyhow, Context};
+use pgp::{
+    composed::{Deserializable, DetachedSignature, SignedPublicKey},
+    types::KeyDetails,
+};
+
+use crate::cargo_log;
+
+/// Fingerprint of the "Tor Browser Developers (signing key)", which signs the expert bundles
+const TOR_SIGNING_KEY_FINGERPRINT: &str = "EF6E286DDA85EA2A4BA7DE684E2C6E8793298290";
+
+/// WKD location of the signing key for torbrowser@torproject.org
+const TOR_SIGNING_KEY_URL: &str = "https://openpgpkey.torproject.org/.well-known/openpgpkey/torproject.org/hu/kounek7zrdx745qydx6p59t9mqjpuhdf";
+
+/// Set to a local (armored or binary) copy of the signing key to skip fetching it
+const TOR_SIGNING_KEY_ENV: &str = "TOR_SIGNING_KEY_PATH";
+
+/// Loads the signing key from `TOR_SIGNING_KEY_PATH` or fetches it from the WKD of torproject.org.
+/// Wherever it came from, the key is only accepted if its fingerprint is the pinned one.
+pub(crate) fn get_signing_key() -> anyhow::Result<SignedPublicKey> {
+    let key = match env::var(TOR_SIGNING_KEY_ENV) {
+        Ok(path) => {
+            cargo_log(&format!("Reading tor signing key from {}", path));
+            let f = File::open(&path).with_context(|| format!("Could not open signing key {}", path))?;
+            SignedPublicKey::from_reader_single(f)?.0
+        }
+        Err(_) => {
+            cargo_log(&format!("Fetching tor signing key from {}", TOR_SIGNING_KEY_URL));
+            let resp = reqwest::blocking::get(TOR_SIGNING_KEY_URL)?.error_for_status()?;
+            SignedPublicKey::from_reader_single(Cursor::new(resp.bytes()?))?.0
+        }
+    };
+
+    let fingerprint = format!("{:X}", key.fingerprint());
+    if fingerprint != TOR_SIGNING_KEY_FINGERPRINT {
+        return Err(anyhow!(
+            "Signing key has fingerprint {}, expected {}",
+            fingerprint,
+            TOR_SIGNING_KEY_FINGERPRINT
+        ));
+    }
+
+    // Makes sure the subkeys actually belong to the pinned primary key
+    key.verify_bindings()?;
+    Ok(key)
+}
+
+/// Verifies a detached signature (`.asc` file) of the given content.
+/// The bundles are signed by one of the subkeys, so every key of the certificate is tried.
+pub(crate) fn verify_detached(key: &SignedPublicKey, content: &[u8], signature: &[u8]) -> anyhow::Result<()> {
+    let (signature, _) = DetachedSignature::from_reader_single(Cursor::new(signature))?;
+
+    if signature.verify(&key.primary_key, content).is_ok() {
+        return Ok(());
+    }
+
+    let verified = key
+        .public_subkeys
+        .iter()
+        .any(|sub| signature.verify(&sub.key, content).is_ok());
+
+    if !verified {
+        return Err(anyhow!(
+            "Signature is not valid for any key of {}",
+            TOR_SIGNING_KEY_FINGERPRINT
+        ));
+    }
+
+    Ok(())
+}
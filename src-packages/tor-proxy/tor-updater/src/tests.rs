@@ -0,0 +1,79 @@
+use std::path::Path;
+
+use crate::{available_platforms, find_platform, manifest::parse_manifest, verify_local_signature, PLATFORMS};
+
+/// File names in the archive directory of tor browser 14.5.4
+const LISTING: &str = include_str!("../fixtures/torbrowser_14.5.4_listing.txt");
+const VERSION: &str = "14.5.4";
+
+#[test]
+fn every_platform_is_published() {
+    let available = available_platforms(LISTING, VERSION);
+    assert_eq!(available.len(), PLATFORMS.len());
+}
+
+#[test]
+fn other_version_is_not_published() {
+    assert!(available_platforms(LISTING, "14.5.3").is_empty());
+}
+
+#[test]
+fn lookup_linux_aarch64() {
+    let platform = find_platform("linux", "aarch64").unwrap();
+    assert_eq!(
+        platform.file_name(VERSION),
+        "tor-expert-bundle-linux-aarch64-14.5.4.tar.gz"
+    );
+    assert_eq!(
+        platform.download_url(VERSION),
+        "https://archive.torproject.org/tor-package-archive/torbrowser/14.5.4/tor-expert-bundle-linux-aarch64-14.5.4.tar.gz"
+    );
+}
+
+#[test]
+fn lookup_renamed_arch() {
+    let windows = find_platform("windows", "x86").unwrap();
+    assert_eq!(windows.file_name(VERSION), "tor-expert-bundle-windows-i686-14.5.4.tar.gz");
+    assert_eq!(windows.tor_binary, "Tor/tor.exe");
+
+    let android = find_platform("android", "arm").unwrap();
+    assert_eq!(android.file_name(VERSION), "tor-expert-bundle-android-armv7-14.5.4.tar.gz");
+}
+
+#[test]
+fn lookup_macos() {
+    for arch in ["x86_64", "aarch64"] {
+        let platform = find_platform("macos", arch).unwrap();
+        assert!(LISTING.lines().any(|l| l == platform.file_name(VERSION)));
+        assert_eq!(
+            platform.bundle_dir(Path::new("out")),
+            Path::new("out").join("macos").join(arch)
+        );
+    }
+}
+
+#[test]
+fn lookup_unsupported() {
+    assert!(find_platform("freebsd", "x86_64").is_none());
+    assert!(find_platform("linux", "riscv64").is_none());
+}
+
+#[test]
+fn manifest_parsing() {
+    let manifest = parse_manifest(
+        "# comment\n\nABCDEF  tor-expert-bundle-linux-x86_64-14.5.4.tar.gz\n0123 *tor-expert-bundle-macos-aarch64-14.5.4.tar.gz\n",
+    )
+    .unwrap();
+
+    assert_eq!(manifest.len(), 2);
+    assert_eq!(manifest["tor-expert-bundle-linux-x86_64-14.5.4.tar.gz"], "abcdef");
+    assert_eq!(manifest["tor-expert-bundle-macos-aarch64-14.5.4.tar.gz"], "0123");
+    assert!(parse_manifest("only-a-hash").is_err());
+}
+
+#[test]
+fn unpinned_local_bundle_needs_signature() {
+    let archive = std::env::temp_dir().join("tor-expert-bundle-unsigned.tar.gz");
+    let err = verify_local_signature(&archive, b"bundle").unwrap_err();
+    assert!(err.to_string().contains("is not pinned"));
+}
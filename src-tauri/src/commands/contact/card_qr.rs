@@ -0,0 +1,17 @@
+use messaging::contact::{render_qr_png, render_qr_svg, ContactCard};
+
+/// Renders the given contact card as QR code to an SVG image
+#[tauri::command]
+pub async fn contact_card_qr_svg(uri: String) -> Result<String, String> {
+    ContactCard::parse(&uri).map_err(|e| e.to_string())?;
+
+    render_qr_svg(&uri).map_err(|e| e.to_string())
+}
+
+/// Renders the given contact card as QR code to a PNG image
+#[tauri::command]
+pub async fn contact_card_qr_png(uri: String) -> Result<Vec<u8>, String> {
+    ContactCard::parse(&uri).map_err(|e| e.to_string())?;
+
+    render_qr_png(&uri).map_err(|e| e.to_string())
+}
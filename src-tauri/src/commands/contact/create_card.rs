@@ -0,0 +1,21 @@
+use messaging::contact::ContactCard;
+
+use crate::util::assert_unlocked_str;
+
+/// Creates our signed contact card for the given contact and returns its URI
+///
+/// # Arguments
+///
+/// * `onion_hostname` - The contact the card is issued to
+/// * `name` - The name we want to be shown as
+/// * `with_auth` - Whether to issue a client authorization key to the contact along with the card
+#[tauri::command]
+pub async fn contact_create_card(onion_hostname: String, name: Option<String>, with_auth: bool) -> Result<String, String> {
+    assert_unlocked_str().await?;
+
+    let card = ContactCard::create(&onion_hostname, name, with_auth)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(card.to_uri())
+}
@@ -0,0 +1,15 @@
+use messaging::contact::ContactCard;
+
+use crate::util::assert_unlocked_str;
+
+/// Verifies the given contact card and adds its issuer as contact with its key pinned.
+/// Returns the onion hostname of the new contact
+#[tauri::command]
+pub async fn contact_import_card(uri: String) -> Result<String, String> {
+    assert_unlocked_str().await?;
+
+    let card = ContactCard::parse(&uri).map_err(|e| e.to_string())?;
+    card.import().await.map_err(|e| e.to_string())?;
+
+    Ok(card.hostname)
+}
@@ -0,0 +1,17 @@
+use messaging::general::issue_client_auth;
+use tor_proxy::service::encode_client_auth_key;
+
+use crate::util::assert_unlocked_str;
+
+/// Issues a client authorization key to the given contact, which should be put in its invitation.
+/// Once a key has been issued, just contacts with a key can discover our onion service
+#[tauri::command]
+pub async fn contact_issue_auth(onion_hostname: String) -> Result<String, String> {
+    assert_unlocked_str().await?;
+
+    let key = issue_client_auth(&onion_hostname)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(encode_client_auth_key(&key))
+}
@@ -0,0 +1,13 @@
+mod issue_auth;
+mod revoke_auth;
+mod set_auth;
+mod create_card;
+mod card_qr;
+mod import_card;
+
+pub use issue_auth::*;
+pub use revoke_auth::*;
+pub use set_auth::*;
+pub use create_card::*;
+pub use card_qr::*;
+pub use import_card::*;
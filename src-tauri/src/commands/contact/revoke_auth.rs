@@ -0,0 +1,13 @@
+use messaging::general::revoke_client_auth;
+
+use crate::util::assert_unlocked_str;
+
+/// Revokes the client authorization key of the given contact, so it can't discover our onion service anymore
+#[tauri::command]
+pub async fn contact_revoke_auth(onion_hostname: String) -> Result<(), String> {
+    assert_unlocked_str().await?;
+
+    revoke_client_auth(&onion_hostname)
+        .await
+        .map_err(|e| e.to_string())
+}
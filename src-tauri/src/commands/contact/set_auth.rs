@@ -0,0 +1,20 @@
+use messaging::general::set_remote_client_auth;
+use tor_proxy::service::decode_client_auth_key;
+
+use crate::util::assert_unlocked_str;
+
+/// Stores the client authorization key the given contact sent us in its invitation,
+/// so tor can dial its onion service. Removes the key if None is given
+#[tauri::command]
+pub async fn contact_set_auth(onion_hostname: String, key: Option<String>) -> Result<(), String> {
+    assert_unlocked_str().await?;
+
+    let key = key
+        .map(|k| decode_client_auth_key(&k))
+        .transpose()
+        .map_err(|e| e.to_string())?;
+
+    set_remote_client_auth(&onion_hostname, key)
+        .await
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,18 @@
+use tauri::Runtime;
+
+use crate::util::{create_diagnostics_bundle, to_str_err};
+
+/// Collects logs, the redacted config, versions and connection states into one archive for bug reports
+///
+/// # Returns
+///
+/// The path of the created archive
+#[tauri::command]
+pub async fn diagnostics_export<R: Runtime>(app: tauri::AppHandle<R>) -> Result<String, String> {
+    let app_version = app.package_info().version.to_string();
+    let path = create_diagnostics_bundle(&app_version)
+        .await
+        .or_else(|e| to_str_err(e)())?;
+
+    Ok(path.to_string_lossy().to_string())
+}
@@ -1,6 +1,9 @@
 pub mod tor;
 mod general;
+mod diagnostics;
 pub mod ws;
 pub mod storage;
+pub mod contact;
 
-pub use general::*;
\ No newline at end of file
+pub use general::*;
+pub use diagnostics::*;
\ No newline at end of file
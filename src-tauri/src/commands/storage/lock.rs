@@ -0,0 +1,21 @@
+use log::debug;
+
+use messaging::general::MESSAGING;
+use storage_internal::STORAGE;
+use crate::util::assert_unlocked_str;
+
+/// Closes every connection, saves the storage and wipes its decrypted data and keys from memory.
+/// The storage has to be unlocked with the password again afterwards.
+#[tauri::command]
+pub async fn storage_lock() -> Result<(), String> {
+    assert_unlocked_str().await?;
+
+    // Messages arriving after locking could not be decrypted anymore
+    MESSAGING.read().await.close_all().await;
+
+    debug!("Locking storage...");
+    let r = STORAGE.write().await.lock().await.map_err(|e| e.to_string());
+    debug!("Done");
+
+    r
+}
@@ -4,6 +4,7 @@ mod unlock_or_create;
 mod set;
 mod save;
 mod delete;
+mod lock;
 mod get;
 
 pub use unlocked::*;
@@ -13,4 +14,5 @@ pub use exists::*;
 pub use set::*;
 pub use save::*;
 pub use delete::*;
+pub use lock::*;
 pub use get::*;
\ No newline at end of file
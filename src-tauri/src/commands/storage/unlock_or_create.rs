@@ -1,5 +1,6 @@
 use anyhow::Result;
-use log::error;
+use log::{error, warn};
+use messaging::general::{sync_client_auth, MESSAGING};
 
 use storage_internal::STORAGE;
 
@@ -16,6 +17,14 @@ pub async fn storage_unlock_or_create(pass: &str) -> Result<(), String> {
         return Err(e.to_string());
     }
 
+    // Tor could not read the client authorization keys while the storage was locked
+    if let Err(e) = sync_client_auth().await {
+        warn!("Could not write client authorization keys: {:?}", e);
+    }
+
+    // Connections have been closed when the storage was locked
+    MESSAGING.read().await.reconnect_active().await;
+
     Ok(())
 }
 /// Inner function to catch the error if the wrong password was used
@@ -10,7 +10,7 @@ pub async fn tor_is_alive(_addr: String) -> Result<bool, String> {
         .await
         .or_else(|e| Err(e.to_string()))?;
 
-    let text = res.text().await.or_else(|e| Err(e.to_string()))?;
+    let text = res.text();
 
     Ok(text == *DEFAULT_HTTP_RETURN)
 }
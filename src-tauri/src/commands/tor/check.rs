@@ -20,21 +20,20 @@ pub struct TorCheckResponse {
 /// checks if the client is in the tor network
 #[tauri::command()]
 pub async fn tor_check() -> Result<bool, String> {
-    let mut res = TOR_CLIENT
+    let res = TOR_CLIENT
         .get("https://check.torproject.org/api/ip")
         .send()
         .await
         .or_else(|e| to_str_err(e)())?;
 
-    let status = res.status().await.or_else(|e| to_str_err(e)())?;
-    let headers = res.headers().await.or_else(|e| to_str_err(e)())?;
+    let status = res.status();
+    let headers = res.headers();
 
     debug!("Status: {}", status);
     debug!("Headers:\n{:#?}", headers);
 
     let body = res
         .json::<TorCheckResponse>()
-        .await
         .or_else(|e| to_str_err(e)())?;
 
     debug!("Body:\n{:#?}", body);
@@ -0,0 +1,19 @@
+use payloads::payloads::TorDosDefenses;
+use tor_proxy::dos::{get_dos_defenses, set_dos_defenses};
+
+/// Gets the protections of our onion service against denial of service attacks
+#[tauri::command]
+pub async fn tor_get_dos_defenses() -> Result<TorDosDefenses, String> {
+    Ok(get_dos_defenses().await)
+}
+
+/// Stores new protections of our onion service and reloads tor if it is running.
+/// Fails if the protections are invalid or not supported by the bundled tor
+///
+/// # Arguments
+///
+/// * `defenses` - The new protections
+#[tauri::command]
+pub async fn tor_set_dos_defenses(defenses: TorDosDefenses) -> Result<(), String> {
+    set_dos_defenses(defenses).await.map_err(|e| e.to_string())
+}
@@ -0,0 +1,15 @@
+use payloads::payloads::{TorLogEntry, TorLogLevel};
+use tor_proxy::logs::get_tor_logs;
+
+/// Gets the lines tor has logged, oldest first
+///
+/// # Arguments
+///
+/// * `min_level` - Lines below this level are skipped, defaults to every line
+/// * `limit` - Only the newest `limit` lines are returned if set
+#[tauri::command]
+pub async fn tor_logs(min_level: Option<TorLogLevel>, limit: Option<usize>) -> Result<Vec<TorLogEntry>, String> {
+    let min_level = min_level.unwrap_or(TorLogLevel::Debug);
+
+    Ok(get_tor_logs(min_level, limit).await)
+}
@@ -1,9 +1,13 @@
 mod check;
 mod hostname;
 mod alive;
+mod logs;
+mod dos;
 mod splashscreen_closed;
 
 pub use hostname::tor_hostname;
 pub use check::tor_check;
 pub use alive::*;
+pub use logs::*;
+pub use dos::*;
 pub use splashscreen_closed::*;
\ No newline at end of file
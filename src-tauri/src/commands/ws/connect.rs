@@ -1,18 +1,14 @@
 use log::debug;
 use messaging::general::MESSAGING;
 
-use crate::util::is_onion_hostname;
+use shared::onion::parse_onion_hostname;
 
 /// Connects to the given onion_hostname and returns if there is already a connection
 #[tauri::command]
 pub async fn ws_connect(onion_hostname: String) -> Result<(), String> {
-    if !is_onion_hostname(&onion_hostname) {
-        let should_skip = cfg!(feature="dev") && (onion_hostname.ends_with("-dev-client") || onion_hostname.ends_with("-dev-server"));
-
-        if !should_skip {
-            return Err("Invalid onion hostname".to_string());
-        }
-    }
+    // Typos are caught by the checksum of the address, before tor tries to find the service
+    let onion_hostname = parse_onion_hostname(&onion_hostname)
+        .map_err(|e| format!("Invalid onion hostname: {}", e))?;
 
     debug!("Getting or creating client...");
     if MESSAGING.read().await.is_connected(&onion_hostname).await {
@@ -0,0 +1,19 @@
+use std::time::Duration;
+
+use messaging::general::{get_cover_traffic, set_cover_traffic};
+
+/// Gets the average interval in milliseconds dummy packets are sent in, None if cover traffic is disabled
+#[tauri::command]
+pub fn ws_get_cover_traffic() -> Option<u64> {
+    get_cover_traffic().map(|i| i.as_millis() as u64)
+}
+
+/// Enables or disables cover traffic for every connection and stores the setting
+///
+/// # Arguments
+///
+/// * `interval_ms` - The average interval in milliseconds, None or 0 to disable cover traffic
+#[tauri::command]
+pub fn ws_set_cover_traffic(interval_ms: Option<u64>) -> Result<(), String> {
+    set_cover_traffic(interval_ms.map(Duration::from_millis)).map_err(|e| e.to_string())
+}
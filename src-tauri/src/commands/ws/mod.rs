@@ -1,5 +1,7 @@
 mod connect;
 mod send;
+mod cover;
 
 pub use send::*;
-pub use connect::*;
\ No newline at end of file
+pub use connect::*;
+pub use cover::*;
\ No newline at end of file
@@ -20,7 +20,8 @@ use tauri_plugin_log::TargetKind;
 use tauri_plugin_log::fern::colors::ColoredLevelConfig;
 use tor_proxy::consts::setup_tor_channels;
 
-use crate::commands::restart;
+use crate::commands::{diagnostics_export, restart};
+use crate::commands::contact::*;
 use crate::commands::storage::*;
 use crate::commands::tor::*;
 use crate::util::on_exit;
@@ -56,15 +57,29 @@ fn main() {
             tor_check,
             tor_hostname,
             tor_is_alive,
+            tor_logs,
+            tor_get_dos_defenses,
+            tor_set_dos_defenses,
+            diagnostics_export,
             ws_connect,
             ws_send,
+            ws_get_cover_traffic,
+            ws_set_cover_traffic,
             storage_exists,
             storage_is_unlocked,
             storage_unlock_or_create,
+            storage_lock,
             storage_delete,
             storage_set,
             storage_get,
             storage_save,
+            contact_issue_auth,
+            contact_revoke_auth,
+            contact_set_auth,
+            contact_create_card,
+            contact_card_qr_svg,
+            contact_card_qr_png,
+            contact_import_card,
             splashscreen_closed
         ])
         // Closes the tor process when the application is closing
@@ -1,32 +1,32 @@
-use crate::util::on_exit;
+use crate::util::{on_exit, TauriEventSink};
+use encryption::disable_core_dumps;
 use log::{error, warn};
 use payloads::{
-    event::AppHandleExt,
+    event::{emit_payload, set_event_sink},
     payloads::{TorStartupErrorPayload, splashscreen::SplashscreenClosedPayload},
 };
-use shared::APP_HANDLE;
 use signal_hook::consts::TERM_SIGNALS;
-use std::thread;
+use std::{sync::Arc, thread};
 use tauri::{
     App, Listener, Manager,
     async_runtime::{self, block_on},
 };
-use tor_proxy::{manager, misc::messages::TorStartError};
+use tor_proxy::{manager, misc::messages::TorStartError, supervisor::spawn_supervisor};
 
 #[cfg(target_family = "windows")]
-use std::sync::{
-    Arc,
-    atomic::{AtomicBool, Ordering},
-};
+use std::sync::atomic::{AtomicBool, Ordering};
 #[cfg(target_family = "windows")]
 use std::time::Duration;
 
 /// The whole startup process of this app
 pub fn startup(app: &mut App) {
-    let mut state = APP_HANDLE.blocking_write();
-    *state = Some(app.handle().clone());
+    // Decrypted messages and keys should never end up in a core dump
+    if let Err(e) = disable_core_dumps() {
+        warn!("Could not disable core dumps: {:?}", e);
+    }
 
-    drop(state);
+    // Every event of the core goes to the frontend
+    set_event_sink(Arc::new(TauriEventSink(app.handle().clone())));
 
     // Window is the main window
     let window = app.get_webview_window("main").unwrap();
@@ -44,9 +44,8 @@ pub fn startup(app: &mut App) {
     temp.once_any("splashscreen_ready", move |_event| {
         // Starting tor if the splashscreen is ready
         async_runtime::spawn(async move {
-            let temp = splashscreen_window.clone();
             let res = manager::start_tor(move |start_payload| {
-                let res = temp.app_handle().emit_payload(start_payload);
+                let res = emit_payload(start_payload);
                 if res.is_ok() {
                     return;
                 }
@@ -56,15 +55,17 @@ pub fn startup(app: &mut App) {
             .await;
 
             if res.is_ok() {
+                // Watching tor from now on, so it is restarted if it crashes
+                if let Err(e) = spawn_supervisor().await {
+                    error!("Could not start tor supervisor: {}", e);
+                }
+
                 // After starting tor, close the splashscreen and show the main window
                 #[cfg(any(debug_assertions, all(feature = "dev", feature = "enable-console")))]
                 window.open_devtools();
                 window.show().unwrap();
                 splashscreen_window.close().unwrap();
-                splashscreen_window
-                    .app_handle()
-                    .emit_payload(SplashscreenClosedPayload {})
-                    .unwrap();
+                emit_payload(SplashscreenClosedPayload {}).unwrap();
             }
 
             // If there is any error, report it
@@ -88,10 +89,7 @@ pub fn startup(app: &mut App) {
 
                 // Tell the splashscreen about the errors
                 error!("Could not start tor: {}", payload.message);
-                splashscreen_window
-                    .app_handle()
-                    .emit_payload(payload)
-                    .unwrap();
+                emit_payload(payload).unwrap();
             }
         });
     });
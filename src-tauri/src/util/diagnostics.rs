@@ -0,0 +1,144 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use log::{debug, warn};
+use messaging::general::MESSAGING;
+use payloads::payloads::TorLogLevel;
+use serde_json::json;
+use shared::{get_root_dir, util::now_millis};
+use tauri::async_runtime::spawn_blocking;
+use tor_proxy::{
+    consts::{TOR_BINARY_HASH, TOR_VERSION},
+    logs::get_tor_logs,
+    manager::get_redacted_torrc,
+    service::get_service_hostname,
+    supervisor::current_status,
+};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+/// Replaces our own onion hostname in the given text, so it is not leaked in bug reports
+fn redact_own_hostname(text: &str, own_hostname: &Option<String>) -> String {
+    match own_hostname {
+        Some(h) if !h.is_empty() => text.replace(h.as_str(), "<own-onion>"),
+        _ => text.to_string(),
+    }
+}
+
+/// Only keeps the first few characters of a hostname so connections can still be told apart
+fn redact_hostname(hostname: &str) -> String {
+    let prefix: String = hostname.chars().take(6).collect();
+    format!("{}...", prefix)
+}
+
+/// Reads every log file the app has written to the root directory
+///
+/// # Returns
+///
+/// The file names of the logs with their content
+fn read_app_logs(root: &Path) -> Result<Vec<(String, String)>> {
+    let mut logs = Vec::new();
+    for entry in fs::read_dir(root)? {
+        let path = entry?.path();
+        if !path.is_file() || path.extension().is_none_or(|e| e != "log") {
+            continue;
+        }
+
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        match fs::read(&path) {
+            Ok(content) => logs.push((name, String::from_utf8_lossy(&content).to_string())),
+            Err(e) => warn!("Could not read log file {:?} for diagnostics: {}", path, e),
+        }
+    }
+
+    Ok(logs)
+}
+
+/// Collects app and tor logs, the redacted tor config, versions and connection states
+/// into a zip archive inside `enkrypton_root/diagnostics`
+///
+/// # Arguments
+///
+/// * `app_version` - The version of the app to write into the bundle
+///
+/// # Returns
+///
+/// The path of the created archive
+pub async fn create_diagnostics_bundle(app_version: &str) -> Result<PathBuf> {
+    let own_hostname = get_service_hostname(false).await.unwrap_or(None);
+
+    let tor_logs: Vec<_> = get_tor_logs(TorLogLevel::Debug, None)
+        .await
+        .into_iter()
+        .map(|mut e| {
+            e.message = redact_own_hostname(&e.message, &own_hostname);
+            e
+        })
+        .collect();
+
+    let torrc = get_redacted_torrc()
+        .await
+        .unwrap_or_else(|e| format!("Could not generate torrc: {}", e));
+
+    let connections: Vec<_> = MESSAGING
+        .read()
+        .await
+        .get_connection_states()
+        .await
+        .into_iter()
+        .map(|mut e| {
+            e.hostname = redact_hostname(&e.hostname);
+            e
+        })
+        .collect();
+
+    let versions = json!({
+        "app": app_version,
+        "tor": *TOR_VERSION,
+        "tor_binary_hash": *TOR_BINARY_HASH,
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+    });
+
+    let state = json!({
+        "tor_status": current_status(),
+        "connections": connections,
+    });
+
+    let files = vec![
+        ("tor_logs.json".to_string(), serde_json::to_string_pretty(&tor_logs)?),
+        ("torrc.txt".to_string(), torrc),
+        ("versions.json".to_string(), serde_json::to_string_pretty(&versions)?),
+        ("state.json".to_string(), serde_json::to_string_pretty(&state)?),
+    ];
+
+    // Writing the archive is blocking io, so it should not run on the async runtime
+    spawn_blocking(move || {
+        let root = get_root_dir();
+        let out_dir = root.join("diagnostics");
+        fs::create_dir_all(&out_dir)?;
+
+        let out_path = out_dir.join(format!("enkrypton-diagnostics-{}.zip", now_millis()));
+        let mut zip = ZipWriter::new(File::create(&out_path)?);
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        for (name, content) in read_app_logs(&root)? {
+            zip.start_file(format!("app/{}", name), options)?;
+            zip.write_all(redact_own_hostname(&content, &own_hostname).as_bytes())?;
+        }
+
+        for (name, content) in files {
+            zip.start_file(name, options)?;
+            zip.write_all(content.as_bytes())?;
+        }
+
+        zip.finish()?;
+        debug!("Diagnostics bundle written to {:?}", out_path);
+
+        Ok(out_path)
+    })
+    .await?
+}
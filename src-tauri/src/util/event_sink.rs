@@ -0,0 +1,13 @@
+use anyhow::Result;
+use payloads::event::EventSink;
+use serde_json::Value;
+use tauri::{AppHandle, Emitter};
+
+/// Emits every event of the core to the windows of the app
+pub struct TauriEventSink(pub AppHandle);
+
+impl EventSink for TauriEventSink {
+    fn emit_event(&self, name: &str, payload: Value) -> Result<()> {
+        Ok(self.0.emit(name, payload)?)
+    }
+}
@@ -1,5 +1,9 @@
 mod general;
+mod diagnostics;
 mod storage_helper;
+mod event_sink;
 
 pub use storage_helper::*;
-pub use general::*;
\ No newline at end of file
+pub use general::*;
+pub use diagnostics::*;
+pub use event_sink::*;
\ No newline at end of file